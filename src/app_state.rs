@@ -1,219 +1,749 @@
-use std::sync::Arc;
-use std::sync::Mutex;
-use lazy_static::lazy_static;
-
-use crate::graphics::camera::Camera;
-use crate::math::mat4::Matrix4;
+use crate::graphics::camera::OrbitCamera;
 use crate::math::vec2::Vector2;
-use crate::math::vec3::Vector3;
 
-lazy_static! {
-    static ref APP_STATE: Mutex<Arc<AppState>> = Mutex::new(Arc::new(AppState::new(800.0, 600.0)));
+/// Longest time between two taps of a double tap in ms
+pub const DOUBLE_TAP_MS: f64 = 300.0;
+
+/// Furthest distance between two taps of a double tap in pixels
+pub const DOUBLE_TAP_DISTANCE: f32 = 30.0;
+
+/// Distance in pixels a pressed pointer has to move before it drags
+/// 
+/// Releasing the pointer before that is still a click.
+pub const DRAG_THRESHOLD: f32 = 5.0;
+
+/// Duration of the flight back to the initial view in seconds
+pub const RESET_DURATION: f32 = 1.0;
+
+/// An input event on the canvas, independent of the DOM
+/// 
+/// Positions are in CSS pixels from the top left of the canvas, times in ms
+/// and pointer IDs as given by the browser.
+#[derive(Debug, Clone, PartialEq)]
+pub enum InputEvent {
+    PointerDown { id: i32, x: f32, y: f32 },
+    /// A pressed or hovering pointer moved
+    PointerMove { id: i32, x: f32, y: f32 },
+    PointerUp { id: i32, x: f32, y: f32, time: f64, touch: bool },
+    /// The browser took over a pointer, e.g. to scroll the page
+    PointerCancel,
+    /// A hovering pointer left the canvas
+    PointerLeave,
+    /// Scrolled by `delta` pixels, positive down
+    Wheel { delta: f32 },
+    /// A key was pressed or released, `key` is a `KeyboardEvent.key` value
+    Key { key: String, down: bool },
+    /// The canvas lost focus
+    Blur
 }
 
-lazy_static! {
-    static ref MAT_STACK: Mutex<Vec<Matrix4>> = Mutex::new(Vec::new());
+/// Something the user can do with a key
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    RotateLeft,
+    RotateRight,
+    RotateUp,
+    RotateDown,
+    ZoomIn,
+    ZoomOut,
+    /// Fly back to the initial view
+    Reset
 }
 
-lazy_static! {
-    static ref MOUSE_STATE: Mutex<Arc<MouseState>> = Mutex::new(Arc::new(MouseState::new()));
-}
+impl Action {
+    pub const ALL: [Action; 7] = [
+        Action::RotateLeft, Action::RotateRight, Action::RotateUp, Action::RotateDown,
+        Action::ZoomIn, Action::ZoomOut, Action::Reset
+    ];
+
+    /// Name of the action as used from JS, e.g. `"rotate_left"`
+    pub fn name(&self) -> &'static str {
+        match self {
+            Action::RotateLeft => "rotate_left",
+            Action::RotateRight => "rotate_right",
+            Action::RotateUp => "rotate_up",
+            Action::RotateDown => "rotate_down",
+            Action::ZoomIn => "zoom_in",
+            Action::ZoomOut => "zoom_out",
+            Action::Reset => "reset"
+        }
+    }
 
-lazy_static! {
-    static ref CAMERA: Mutex<Arc<Camera>> = Mutex::new(Arc::new(Camera::new()));
+    pub fn from_name(name: &str) -> Option<Action> {
+        Action::ALL.into_iter().find(|action| action.name() == name)
+    }
 }
 
-pub fn update_dynamic_data(canvas_height: f32, canvas_width: f32) {
-    let mut data = APP_STATE.lock().unwrap();
-    
-    *data = Arc::new( AppState::new(canvas_height, canvas_width));
+/// Table of keys and the action each one triggers
+/// 
+/// Keys are `KeyboardEvent.key` values, e.g. `"ArrowLeft"` or `"w"`, and
+/// are matched ignoring case. A key triggers at most one action, but an
+/// action can have any number of keys.
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyBindings {
+    bindings: Vec<(String, Action)>
 }
 
-pub fn get_canvas_height() -> f32 {
-    APP_STATE.lock().unwrap().canvas_height
+#[allow(dead_code)]
+impl KeyBindings {
+    /// Create a table without any bindings
+    pub fn empty() -> Self {
+        Self { bindings: Vec::new() }
+    }
+
+    /// Bind `key` to `action`, replacing its previous binding
+    pub fn bind(&mut self, key: &str, action: Action) {
+        let key = key.to_lowercase();
+
+        match self.bindings.iter_mut().find(|(k, _)| *k == key) {
+            Some(binding) => binding.1 = action,
+            None => self.bindings.push((key, action))
+        }
+    }
+
+    /// Remove the binding of `key` and return its action, if any
+    pub fn unbind(&mut self, key: &str) -> Option<Action> {
+        let key = key.to_lowercase();
+        let index = self.bindings.iter().position(|(k, _)| *k == key)?;
+
+        Some(self.bindings.remove(index).1)
+    }
+
+    pub fn get(&self, key: &str) -> Option<Action> {
+        let key = key.to_lowercase();
+        self.bindings.iter().find(|(k, _)| *k == key).map(|(_, action)| *action)
+    }
+
+    /// Iterate over the bound keys, in lower case, and their actions
+    pub fn iter(&self) -> impl Iterator<Item = (&str, Action)> {
+        self.bindings.iter().map(|(key, action)| (key.as_str(), *action))
+    }
 }
 
-pub fn get_canvas_width() -> f32 {
-    APP_STATE.lock().unwrap().canvas_width
+impl Default for KeyBindings {
+    /// Arrow keys and WASD rotate, +/- zoom and R or Home resets the view
+    fn default() -> Self {
+        let mut bindings = Self::empty();
+
+        for (key, action) in [
+            ("ArrowLeft", Action::RotateLeft), ("a", Action::RotateLeft),
+            ("ArrowRight", Action::RotateRight), ("d", Action::RotateRight),
+            ("ArrowUp", Action::RotateUp), ("w", Action::RotateUp),
+            ("ArrowDown", Action::RotateDown), ("s", Action::RotateDown),
+            ("+", Action::ZoomIn), ("=", Action::ZoomIn),
+            ("-", Action::ZoomOut), ("_", Action::ZoomOut),
+            ("r", Action::Reset), ("Home", Action::Reset),
+        ] {
+            bindings.bind(key, action);
+        }
+
+        bindings
+    }
 }
 
-pub fn set_mouse_pos(x: f32, y: f32) {
-    let mut mouse_state = MOUSE_STATE.lock().unwrap();
-
-    *mouse_state = Arc::new( MouseState {
-        x,
-        y,
-        delta_x: mouse_state.delta_x,
-        delta_y: mouse_state.delta_y,
-        down: mouse_state.down,
-        drag: mouse_state.drag
-    } );
+/// State of the pointers and keys of a single `App`
+///
+/// Fed by `process`, either from the event handlers registered on the
+/// canvas or directly in tests, and read by `App::update` once per frame.
+/// A single touch acts like the mouse with a button held down. Two touches
+/// pinch to zoom instead. Keys are turned into actions through `bindings`.
+pub struct InputState {
+    pos: Vector2,
+    /// Movement of the dragging pointer since the last frame, as the
+    /// previous minus the current position
+    delta: Vector2,
+    scroll: f32,
+    down: bool,
+    drag: bool,
+    over: bool,
+    click: Option<Vector2>,
+    /// Position the pointer was pressed at, until it starts dragging
+    press: Option<Vector2>,
+    /// Pointers currently pressed on the canvas by ID
+    pointers: Vec<(i32, Vector2)>,
+    /// Distance between the two pinching pointers
+    pinch_distance: Option<f32>,
+    /// Zoom factor accumulated from pinching since the last frame
+    pinch: f32,
+    /// Position and time of the last tap, waiting for a second tap
+    last_tap: Option<(Vector2, f64)>,
+    double_tap: Option<Vector2>,
+    pub bindings: KeyBindings,
+    /// Keys currently held down, in lower case
+    keys: Vec<String>,
+    /// Actions whose keys were pressed since the last frame
+    pressed: Vec<Action>
 }
 
-pub fn get_mouse_pos() -> Vector2 {
-    let mouse_state = MOUSE_STATE.lock().unwrap();
+#[allow(dead_code)]
+impl InputState {
+    pub fn new() -> Self {
+        Self { pos: Vector2::zero(), delta: Vector2::zero(), scroll: 0.0, down: false, drag: false, over: false,
+            click: None, press: None, pointers: Vec::new(), pinch_distance: None, pinch: 1.0, last_tap: None,
+            double_tap: None, bindings: KeyBindings::default(), keys: Vec::new(), pressed: Vec::new() }
+    }
 
-    Vector2::new(
-        mouse_state.x,
-        mouse_state.y
-    )
-}
+    /// Update the state with an event
+    /// 
+    /// Returns `true` if the event was used and the browser should not act
+    /// on it as well, i.e. for the wheel and bound keys.
+    pub fn process(&mut self, event: InputEvent) -> bool {
+        match event {
+            InputEvent::PointerDown { id, x, y } => self.pointer_down(id, Vector2::new(x, y)),
+            InputEvent::PointerMove { id, x, y } => self.pointer_move(id, Vector2::new(x, y)),
+            InputEvent::PointerUp { id, x, y, time, touch } => self.pointer_up(id, Vector2::new(x, y), time, touch),
+            InputEvent::PointerCancel => self.pointer_cancel(),
+            InputEvent::PointerLeave => self.over = false,
+            InputEvent::Wheel { delta } => {
+                self.scroll += delta;
+                return true;
+            },
+            InputEvent::Key { key, down: true } => return self.key_down(&key),
+            InputEvent::Key { key, down: false } => self.key_up(&key),
+            InputEvent::Blur => self.keys.clear()
+        }
 
-pub fn update_mouse_delta(delta_x: f32, delta_y: f32) {
-    // This should exclusively be called in update
-    let mut mouse_state = MOUSE_STATE.lock().unwrap();
+        false
+    }
 
-    let delta = mouse_state.down as i32 as f32;
+    /// Position of the last pointer that moved
+    pub fn pos(&self) -> Vector2 {
+        self.pos
+    }
 
-    *mouse_state = Arc::new( MouseState {
-        x: mouse_state.x,
-        y: mouse_state.y,
-        delta_x: delta_x * delta,
-        delta_y: delta_y * delta,
-        down: mouse_state.down,
-        drag: mouse_state.drag
-    });
-}
+    /// Return the movement of the pressed pointer since the last call and
+    /// reset it
+    /// 
+    /// Stays zero until the pointer moved past `DRAG_THRESHOLD`.
+    pub fn take_delta(&mut self) -> Vector2 {
+        std::mem::replace(&mut self.delta, Vector2::zero())
+    }
 
-pub fn get_mouse_delta() -> Vector2 {
-    
-    let mouse_state = MOUSE_STATE.lock().unwrap();
+    /// Returns `true` while a pointer is pressed
+    pub fn down(&self) -> bool {
+        self.down
+    }
 
-    Vector2::new(
-        mouse_state.delta_x,
-        mouse_state.delta_y
-    )
-}
+    /// Returns `true` once the pressed pointers moved past `DRAG_THRESHOLD`
+    /// or started pinching, so releasing them is no click
+    pub fn drag(&self) -> bool {
+        self.drag
+    }
 
-pub fn set_mouse_down(down: bool) {
-    let mut mouse_state = MOUSE_STATE.lock().unwrap();
-
-    *mouse_state = Arc::new( MouseState {
-        x: mouse_state.x,
-        y: mouse_state.y,
-        delta_x: mouse_state.delta_x,
-        delta_y: mouse_state.delta_y,
-        down,
-        drag: mouse_state.drag
-    });
-}
+    /// Returns `true` while the mouse is over the canvas
+    pub fn over(&self) -> bool {
+        self.over
+    }
 
-pub fn set_mouse_drag(drag: bool) {
-    let mut mouse_state = MOUSE_STATE.lock().unwrap();
+    /// Return the position of the last click, if any, and reset it
+    pub fn take_click(&mut self) -> Option<Vector2> {
+        self.click.take()
+    }
 
-    *mouse_state = Arc::new( MouseState {
-        drag: drag&&mouse_state.down,
-        ..*mouse_state.as_ref() // much cleaner
-    })
-}
+    /// Return the zoom factor pinched since the last frame and reset it
+    /// 
+    /// Above 1.0 when the pointers moved together, which should zoom out.
+    pub fn take_pinch(&mut self) -> f32 {
+        std::mem::replace(&mut self.pinch, 1.0)
+    }
 
-pub fn get_mouse_drag() -> bool {
-    let mouse_state = MOUSE_STATE.lock().unwrap();
+    /// Return the position of the last double tap, if any, and reset it
+    pub fn take_double_tap(&mut self) -> Option<Vector2> {
+        self.double_tap.take()
+    }
 
-    mouse_state.drag
-}
+    /// Return the accumulated scroll and reset it
+    pub fn take_scroll(&mut self) -> f32 {
+        std::mem::take(&mut self.scroll)
+    }
 
-pub fn get_projection_matrix() -> Matrix4 {
-    let cam = CAMERA.lock().unwrap();
+    /// Returns `true` while any key bound to `action` is held down
+    pub fn held(&self, action: Action) -> bool {
+        self.keys.iter().any(|key| self.bindings.get(key) == Some(action))
+    }
 
-    cam.proj_matrix
-}
+    /// -1.0, 0.0 or 1.0 depending on which of two opposing actions are held
+    pub fn axis(&self, negative: Action, positive: Action) -> f32 {
+        self.held(positive) as i32 as f32 - self.held(negative) as i32 as f32
+    }
 
-pub fn get_view_matrix() -> Matrix4 {
-    let cam = CAMERA.lock().unwrap();
+    /// Return the actions pressed since the last frame and reset them
+    pub fn take_pressed(&mut self) -> Vec<Action> {
+        std::mem::take(&mut self.pressed)
+    }
 
-    cam.view_matrix
-}
+    /// The first pointer presses like a mouse button. A second pointer
+    /// starts a pinch, and the gesture can no longer become a click.
+    fn pointer_down(&mut self, id: i32, pos: Vector2) {
+        self.pointers.retain(|(p, _)| *p != id);
+        self.pointers.push((id, pos));
+
+        match self.pointers.len() {
+            1 => {
+                self.pos = pos;
+                self.press = Some(pos);
+                self.down = true;
+                self.drag = false;
+            },
+            _ => {
+                self.drag = true;
+                self.press = None;
+                self.pinch_distance = self.pinch_span();
+            }
+        }
+    }
 
-pub fn update_projection_matrix() {
-    // configure the new projection matrix
-    let mut cam = CAMERA.lock().unwrap();
-    let aspect_ratio = get_canvas_width() / get_canvas_height();
-    let proj_mat = Matrix4::perspective(
-        cam.fov, aspect_ratio, cam.near, cam.far);
+    fn pointer_move(&mut self, id: i32, pos: Vector2) {
+        if let Some(pointer) = self.pointers.iter_mut().find(|(p, _)| *p == id) {
+            pointer.1 = pos;
+        } else if !self.pointers.is_empty() {
+            // Hovering pointer while another one is pressed
+            return;
+        }
 
-    *cam = Arc::new( Camera {
-        proj_matrix: proj_mat,
-        ..*cam.as_ref()
-    } );
+        match self.pointers.len() {
+            0 => {
+                self.pos = pos;
+                self.over = true;
+            },
+            1 => {
+                match self.press {
+                    // Jitter of a click does not rotate, passing the
+                    // threshold catches up with the whole movement
+                    Some(press) if (pos - press).magnitude() > DRAG_THRESHOLD => {
+                        self.delta += press - pos;
+                        self.drag = true;
+                        self.press = None;
+                    },
+                    Some(_) => (),
+                    None => self.delta += self.pos - pos
+                }
+
+                self.pos = pos;
+                self.over = true;
+            },
+            _ => {
+                if let (Some(old), Some(new)) = (self.pinch_distance, self.pinch_span()) {
+                    if new > 0.0 {
+                        self.pinch *= old / new;
+                    }
+                }
+
+                self.pinch_distance = self.pinch_span();
+            }
+        }
+    }
 
-}
+    /// Releasing the last pointer without dragging is a click. Two quick
+    /// touch clicks close to each other are a double tap instead, so the
+    /// second one and the first one, if not taken yet, do not click.
+    fn pointer_up(&mut self, id: i32, pos: Vector2, time: f64, touch: bool) {
+        if !self.pointers.iter().any(|(p, _)| *p == id) {
+            return;
+        }
 
-pub fn update_view_matrix() {
-    // configure the new view matrix
-    let mut cam = CAMERA.lock().unwrap();
-    let view_mat = Matrix4::view(
-        cam.position,
-        Vector3::up(),
-        Vector3::forward()
-    );
+        self.pointers.retain(|(p, _)| *p != id);
 
-    *cam = Arc::new( Camera {
-        view_matrix: view_mat,
-        ..*cam.as_ref()
-    });
-}
+        if let Some((_, remaining)) = self.pointers.first().copied() {
+            // Keep rotating with the remaining pointer without jumping
+            self.pos = remaining;
+            self.pinch_distance = None;
+            return;
+        }
 
-pub fn set_camera_position(pos: Vector3) {
-    let mut cam = CAMERA.lock().unwrap();
-    *cam = Arc::new( Camera {
-        position: pos,
-        ..*cam.as_ref()
-    });
-}
+        if self.down && !self.drag {
+            self.click = Some(pos);
 
-pub fn move_camera(delta: Vector3) {
-    let mut cam = CAMERA.lock().unwrap();
-    *cam = Arc::new( Camera {
-        position: cam.position + delta,
-        ..*cam.as_ref()
-    });
-}
+            if touch && self.tap(pos, time) {
+                self.click = None;
+            }
+        }
 
-/// Takes in a matrix and multiplies it onto the mat stack
-pub fn multiply_to_mat_stack(mat: Matrix4) {
-    let mut stack = MAT_STACK.lock().unwrap();
-    let top = *stack.last().unwrap_or(&Matrix4::identity());
+        self.pinch_distance = None;
+        self.press = None;
+        self.down = false;
+        self.drag = false;
+    }
 
-    stack.push(
-        mat * top
-    )
-}
+    /// Release every pointer without clicking
+    fn pointer_cancel(&mut self) {
+        self.pointers.clear();
+        self.pinch_distance = None;
+        self.press = None;
+        self.down = false;
+        self.drag = false;
+    }
 
-pub fn pop_from_mat_stack() {
-    MAT_STACK.lock().unwrap().pop();
+    /// Returns `true` if the tap completes a double tap
+    fn tap(&mut self, pos: Vector2, time: f64) -> bool {
+        match self.last_tap.take() {
+            Some((last, last_time)) if
+                time - last_time <= DOUBLE_TAP_MS &&
+                (pos - last).magnitude() <= DOUBLE_TAP_DISTANCE => {
+                self.double_tap = Some(pos);
+                true
+            },
+            _ => {
+                self.last_tap = Some((pos, time));
+                false
+            }
+        }
+    }
 
-}
+    fn pinch_span(&self) -> Option<f32> {
+        match self.pointers.as_slice() {
+            [(_, a), (_, b), ..] => Some((*a - *b).magnitude()),
+            _ => None
+        }
+    }
 
-pub fn peek_mat_stack() -> Matrix4 {
-    *MAT_STACK.lock().unwrap().last().unwrap_or(&Matrix4::identity())
-}
+    /// Press a key, returns `true` if it is bound to an action
+    /// 
+    /// Repeated presses of a held key are ignored.
+    fn key_down(&mut self, key: &str) -> bool {
+        let action = self.bindings.get(key);
+        let key = key.to_lowercase();
+
+        if !self.keys.contains(&key) {
+            self.keys.push(key);
+            self.pressed.extend(action);
+        }
+
+        action.is_some()
+    }
 
-pub struct AppState {
-    canvas_height: f32,
-    canvas_width: f32,
+    fn key_up(&mut self, key: &str) {
+        let key = key.to_lowercase();
+        self.keys.retain(|k| *k != key);
+    }
 }
 
-impl AppState {
-    pub fn new(canvas_height: f32, canvas_width: f32) -> Self {
-        Self {
-            canvas_height: canvas_height,
-            canvas_width: canvas_width,
-        }
+impl Default for InputState {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
-pub struct MouseState {
-    x: f32,
-    y: f32,
-    delta_x: f32,
-    delta_y: f32,
-    down: bool,
-    drag: bool
+/// Move `orbit` by the input since the last frame and advance it by
+/// `delta_time` seconds
+/// 
+/// Consumes the drag, scroll, pinch and key actions of `input`, but leaves
+/// clicks and double taps to the caller.
+pub fn control_orbit(input: &mut InputState, orbit: &mut OrbitCamera, delta_time: f32) {
+    let dragging = input.down();
+    let delta = input.take_delta();
+
+    if dragging {
+        orbit.drag(delta, delta_time);
+    }
+
+    orbit.zoom(input.take_scroll());
+    orbit.zoom_by(input.take_pinch());
+
+    let turn = Vector2::new(
+        input.axis(Action::RotateLeft, Action::RotateRight),
+        input.axis(Action::RotateUp, Action::RotateDown)
+    );
+    orbit.turn(turn, delta_time);
+    orbit.zoom_steady(input.axis(Action::ZoomIn, Action::ZoomOut), delta_time);
+
+    if input.take_pressed().contains(&Action::Reset) {
+        orbit.reset(RESET_DURATION);
+    }
+
+    orbit.update(delta_time, dragging);
 }
 
-impl MouseState {
-    pub fn new() -> Self {
-        Self { x: 0.0, y: 0.0, delta_x: 0.0, delta_y: 0.0, down: false, drag: false }
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn down(id: i32, x: f32, y: f32) -> InputEvent {
+        InputEvent::PointerDown { id, x, y }
+    }
+
+    fn move_to(id: i32, x: f32, y: f32) -> InputEvent {
+        InputEvent::PointerMove { id, x, y }
+    }
+
+    fn up(id: i32, x: f32, y: f32, time: f64, touch: bool) -> InputEvent {
+        InputEvent::PointerUp { id, x, y, time, touch }
+    }
+
+    fn key(key: &str, down: bool) -> InputEvent {
+        InputEvent::Key { key: String::from(key), down }
+    }
+
+    #[test]
+    fn test_drag_needs_button_down() {
+        let mut input = InputState::new();
+
+        input.process(move_to(1, 50.0, 50.0));
+        assert!(input.over());
+        assert!(!input.drag());
+
+        input.process(down(1, 50.0, 50.0));
+        input.process(move_to(1, 100.0, 50.0));
+        assert!(input.drag());
+    }
+
+    #[test]
+    fn test_drag_threshold() {
+        let mut input = InputState::new();
+
+        // Small jitters while pressing still click
+        input.process(down(1, 10.0, 10.0));
+        input.process(move_to(1, 13.0, 14.0));
+        assert!(!input.drag());
+        input.process(up(1, 13.0, 14.0, 0.0, false));
+        assert_eq!(input.take_click().unwrap().data, [13.0, 14.0]);
+
+        // Going past the threshold and back is a drag
+        input.process(down(1, 10.0, 10.0));
+        input.process(move_to(1, 16.0, 10.0));
+        input.process(move_to(1, 10.0, 10.0));
+        assert!(input.drag());
+        input.process(up(1, 10.0, 10.0, 0.0, false));
+        assert!(input.take_click().is_none());
+    }
+
+    #[test]
+    fn test_delta_only_while_down() {
+        let mut input = InputState::new();
+
+        input.process(move_to(1, 3.0, 4.0));
+        assert_eq!(input.take_delta().data, [0.0, 0.0]);
+
+        input.process(down(1, 3.0, 4.0));
+        input.process(move_to(1, 1.0, 2.0));
+        input.process(move_to(1, -3.0, -4.0));
+        assert_eq!(input.take_delta().data, [6.0, 8.0]);
+        assert_eq!(input.take_delta().data, [0.0, 0.0]);
+    }
+
+    #[test]
+    fn test_no_delta_before_drag_threshold() {
+        let mut input = InputState::new();
+
+        input.process(down(1, 10.0, 10.0));
+        input.process(move_to(1, 13.0, 14.0));
+        input.process(move_to(1, 12.0, 10.0));
+        assert_eq!(input.take_delta().data, [0.0, 0.0]);
+
+        input.process(move_to(1, 18.0, 10.0));
+        input.process(move_to(1, 20.0, 10.0));
+        assert_eq!(input.take_delta().data, [-10.0, 0.0]);
+    }
+
+    #[test]
+    fn test_scroll_is_consumed() {
+        let mut input = InputState::new();
+
+        assert!(input.process(InputEvent::Wheel { delta: 1.5 }));
+        input.process(InputEvent::Wheel { delta: -0.5 });
+        assert_eq!(input.take_scroll(), 1.0);
+        assert_eq!(input.take_scroll(), 0.0);
+    }
+
+    #[test]
+    fn test_pointer_click_and_drag() {
+        let mut input = InputState::new();
+
+        input.process(down(1, 10.0, 20.0));
+        assert!(input.down());
+        input.process(up(1, 10.0, 20.0, 0.0, false));
+        assert_eq!(input.take_click().unwrap().data, [10.0, 20.0]);
+
+        input.process(down(1, 10.0, 20.0));
+        input.process(move_to(1, 30.0, 20.0));
+        assert!(input.drag());
+        input.process(up(1, 30.0, 20.0, 0.0, false));
+        assert!(input.take_click().is_none());
+        assert!(!input.down());
+
+        // Cancelled pointers never click
+        input.process(down(1, 10.0, 20.0));
+        input.process(InputEvent::PointerCancel);
+        input.process(up(1, 10.0, 20.0, 0.0, false));
+        assert!(input.take_click().is_none());
+        assert!(!input.down());
+    }
+
+    #[test]
+    fn test_pinch_zoom() {
+        let mut input = InputState::new();
+
+        input.process(down(1, 0.0, 0.0));
+        input.process(down(2, 100.0, 0.0));
+        input.process(move_to(2, 200.0, 0.0));
+        input.process(move_to(1, -200.0, 0.0));
+
+        // Fingers moved from 100 to 400 pixels apart, zoom in four times
+        assert!((input.take_pinch() - 0.25).abs() < 1e-6);
+        assert_eq!(input.take_pinch(), 1.0);
+        assert_eq!(input.take_delta().data, [0.0, 0.0]);
+
+        // Lifting one finger keeps rotating from the other, never a click
+        input.process(up(2, 200.0, 0.0, 0.0, true));
+        assert_eq!(input.pos().data, [-200.0, 0.0]);
+        assert!(input.down());
+        input.process(up(1, -200.0, 0.0, 0.0, true));
+        assert!(input.take_click().is_none());
+    }
+
+    #[test]
+    fn test_double_tap() {
+        let mut input = InputState::new();
+
+        let mut tap = |x: f32, time: f64| {
+            input.process(down(1, x, 0.0));
+            input.process(up(1, x, 0.0, time, true));
+        };
+
+        tap(0.0, 0.0);
+        tap(10.0, 200.0);
+        // Too slow
+        tap(10.0, 1000.0);
+        // Too far
+        tap(100.0, 1100.0);
+
+        assert_eq!(input.take_double_tap().unwrap().data, [10.0, 0.0]);
+        assert!(input.take_double_tap().is_none());
+    }
+
+    #[test]
+    fn test_double_tap_clicks_once() {
+        let mut input = InputState::new();
+
+        // Taken between the taps, only the first tap clicks
+        input.process(down(1, 0.0, 0.0));
+        input.process(up(1, 0.0, 0.0, 0.0, true));
+        assert_eq!(input.take_click().unwrap().data, [0.0, 0.0]);
+        input.process(down(1, 5.0, 0.0));
+        input.process(up(1, 5.0, 0.0, 200.0, true));
+        assert!(input.take_click().is_none());
+        assert_eq!(input.take_double_tap().unwrap().data, [5.0, 0.0]);
+
+        // Within one frame, neither does
+        input.process(down(1, 0.0, 0.0));
+        input.process(up(1, 0.0, 0.0, 1000.0, true));
+        input.process(down(1, 5.0, 0.0));
+        input.process(up(1, 5.0, 0.0, 1200.0, true));
+        assert!(input.take_click().is_none());
+        assert!(input.take_double_tap().is_some());
+    }
+
+    #[test]
+    fn test_click_is_consumed() {
+        let mut input = InputState::new();
+        assert!(input.take_click().is_none());
+
+        input.process(down(1, 1.0, 2.0));
+        input.process(up(1, 1.0, 2.0, 0.0, false));
+        input.process(down(1, 3.0, 4.0));
+        input.process(up(1, 3.0, 4.0, 0.0, false));
+        assert_eq!(input.take_click().unwrap().data, [3.0, 4.0]);
+        assert!(input.take_click().is_none());
+    }
+
+    #[test]
+    fn test_key_actions() {
+        let mut input = InputState::new();
+
+        assert!(input.process(key("ArrowLeft", true)));
+        assert!(input.process(key("ArrowLeft", true)));
+        assert!(input.process(key("D", true)));
+        assert!(!input.process(key("q", true)));
+        assert_eq!(input.take_pressed(), vec![Action::RotateLeft, Action::RotateRight]);
+        assert!(input.take_pressed().is_empty());
+
+        // Opposite directions cancel out
+        assert_eq!(input.axis(Action::RotateLeft, Action::RotateRight), 0.0);
+        input.process(key("d", false));
+        assert_eq!(input.axis(Action::RotateLeft, Action::RotateRight), -1.0);
+
+        input.process(InputEvent::Blur);
+        assert!(!input.held(Action::RotateLeft));
+    }
+
+    #[test]
+    fn test_rebind_keys() {
+        let mut input = InputState::new();
+
+        input.bindings.bind("Q", Action::ZoomIn);
+        input.bindings.bind("w", Action::ZoomOut);
+        assert_eq!(input.bindings.unbind("ArrowUp"), Some(Action::RotateUp));
+        assert_eq!(input.bindings.unbind("ArrowUp"), None);
+
+        assert!(!input.process(key("ArrowUp", true)));
+        input.process(key("q", true));
+        input.process(key("W", true));
+        assert_eq!(input.take_pressed(), vec![Action::ZoomIn, Action::ZoomOut]);
+        assert!(!input.held(Action::RotateUp));
+
+        assert_eq!(Action::from_name("zoom_in"), Some(Action::ZoomIn));
+        assert_eq!(Action::from_name("fly"), None);
+    }
+
+    #[test]
+    fn test_control_orbit() {
+        let mut input = InputState::new();
+        let mut orbit = OrbitCamera::new(1.0, 0.0, 30.0);
+
+        // Dragging right turns the camera west, like the globe follows the mouse
+        input.process(down(1, 100.0, 100.0));
+        input.process(move_to(1, 150.0, 100.0));
+        control_orbit(&mut input, &mut orbit, 0.1);
+        assert!((orbit.yaw() - (1.0 + 50.0 * orbit.sensitivity)).abs() < 1e-5);
+
+        // Keeps turning a little after letting go
+        input.process(up(1, 150.0, 100.0, 0.0, false));
+        let yaw = orbit.yaw();
+        control_orbit(&mut input, &mut orbit, 0.1);
+        assert!(orbit.yaw() > yaw);
+        assert!(input.take_click().is_none());
+
+        input.process(InputEvent::Wheel { delta: -100.0 });
+        input.process(key("Home", true));
+        control_orbit(&mut input, &mut orbit, 0.0);
+        assert!(orbit.is_flying());
+
+        for _ in 0..20 {
+            control_orbit(&mut input, &mut orbit, 0.1);
+        }
+
+        assert!(!orbit.is_flying());
+        assert!((orbit.yaw() - 1.0).abs() < 1e-5);
+        assert!((orbit.distance() - 30.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_click_picks_globe() {
+        use crate::graphics::camera::Camera;
+        use crate::math::geo::EarthModel;
+        use crate::scene::{fireball::FireballRegistry, picking::{self, Pick}, scene_graph::SceneGraph, scene_node::Node};
+
+        let mut graph = SceneGraph::new();
+        let globe = graph.add_child(graph.root(), Node::named("globe"));
+        graph.update_transforms();
+
+        let mut input = InputState::new();
+        let orbit = OrbitCamera::new(0.5, 0.3, 5.0);
+        let mut camera = Camera::new();
+        camera.update_projection_matrix(800.0 / 600.0);
+        orbit.apply(&mut camera);
+
+        // Clicking the center of the canvas hits the point below the camera
+        input.process(down(1, 400.0, 300.0));
+        input.process(up(1, 402.0, 300.0, 0.0, false));
+        let ray = camera.canvas_ray(input.take_click().unwrap(), 800.0, 600.0).unwrap();
+
+        match picking::pick(&graph, globe, &FireballRegistry::new(), &ray, &EarthModel::default()) {
+            Some(Pick::Globe { lat, lon, .. }) => {
+                assert!((lat - 0.3_f32.to_degrees()).abs() < 0.1);
+                assert!((lon - 0.5_f32.to_degrees()).abs() < 0.5);
+            },
+            other => panic!("Expected a globe hit, got {:?}", other)
+        }
     }
 }
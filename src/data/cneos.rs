@@ -0,0 +1,496 @@
+//! Parsing for the NASA CNEOS fireball dataset
+//!
+//! Supports the JSON returned by the `fireball.api` endpoint as well as
+//! CSV exports, either using the API field names (`lat`, `lat-dir`, ...)
+//! or the column titles of the CNEOS website table.
+
+use js_sys::{Object, Reflect};
+use serde_json::Value;
+use wasm_bindgen::JsValue;
+
+use crate::math::geo::GeoCoord;
+
+/// A single fireball event
+///
+/// Latitude and longitude are signed degrees (north and east positive).
+/// Every other attribute is optional as the dataset leaves many of them
+/// blank.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct FireballRecord {
+    /// Date and time of peak brightness (UT)
+    pub date: Option<String>,
+    pub lat: f32,
+    pub lon: f32,
+    /// Altitude in km
+    pub alt: Option<f32>,
+    /// Velocity in km/s
+    pub vel: Option<f32>,
+    pub vx: Option<f32>,
+    pub vy: Option<f32>,
+    pub vz: Option<f32>,
+    /// Total radiated energy in units of 10^10 J
+    pub energy: Option<f32>,
+    /// Calculated total impact energy in kt
+    pub impact_e: Option<f32>
+}
+
+impl FireballRecord {
+    pub fn new(lat: f32, lon: f32, alt: Option<f32>) -> Self {
+        Self { lat, lon, alt, ..Default::default() }
+    }
+
+    /// Location of the event, at the surface if the altitude is unknown
+    pub fn geo(&self) -> GeoCoord {
+        GeoCoord::new(self.lat, self.lon, self.alt.unwrap_or(0.0))
+    }
+
+    /// Convert the record into a plain JavaScript object
+    ///
+    /// Missing attributes are set to `null`.
+    pub fn to_js(&self) -> JsValue {
+        let obj = Object::new();
+        let opt = |v: Option<f32>| v.map(|v| JsValue::from_f64(v as f64)).unwrap_or(JsValue::NULL);
+
+        let fields = [
+            ("date", self.date.as_deref().map(JsValue::from_str).unwrap_or(JsValue::NULL)),
+            ("lat", JsValue::from_f64(self.lat as f64)),
+            ("lon", JsValue::from_f64(self.lon as f64)),
+            ("alt", opt(self.alt)),
+            ("vel", opt(self.vel)),
+            ("vx", opt(self.vx)),
+            ("vy", opt(self.vy)),
+            ("vz", opt(self.vz)),
+            ("energy", opt(self.energy)),
+            ("impact_e", opt(self.impact_e)),
+        ];
+
+        for (key, value) in fields {
+            let _ = Reflect::set(&obj, &JsValue::from_str(key), &value);
+        }
+
+        obj.into()
+    }
+}
+
+/// A row that could not be imported
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImportError {
+    /// 1-based index of the record in the data
+    pub row: usize,
+    pub message: String
+}
+
+impl ImportError {
+    pub fn to_js(&self) -> JsValue {
+        let obj = Object::new();
+        let _ = Reflect::set(&obj, &JsValue::from_str("row"), &JsValue::from_f64(self.row as f64));
+        let _ = Reflect::set(&obj, &JsValue::from_str("message"), &JsValue::from_str(&self.message));
+
+        obj.into()
+    }
+}
+
+/// Result of parsing a dataset
+///
+/// Rows that fail to parse are reported in `errors` and do not stop the
+/// rest of the data from being imported.
+#[derive(Debug, Default)]
+pub struct Import {
+    pub records: Vec<FireballRecord>,
+    /// 1-based row of each record in `records`
+    pub rows: Vec<usize>,
+    pub errors: Vec<ImportError>
+}
+
+/// Columns of the dataset that are understood by the importer
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Field {
+    Date,
+    Lat,
+    LatDir,
+    Lon,
+    LonDir,
+    Alt,
+    Vel,
+    Vx,
+    Vy,
+    Vz,
+    Energy,
+    ImpactE,
+    /// Total radiated energy in J, as used by the website export
+    EnergyJoules
+}
+
+impl Field {
+    fn from_header(header: &str) -> Option<Self> {
+        let header = header.trim().to_lowercase();
+
+        let field = match header.as_str() {
+            "date" => Field::Date,
+            "lat" => Field::Lat,
+            "lat-dir" | "lat_dir" => Field::LatDir,
+            "lon" => Field::Lon,
+            "lon-dir" | "lon_dir" => Field::LonDir,
+            "alt" => Field::Alt,
+            "vel" => Field::Vel,
+            "vx" => Field::Vx,
+            "vy" => Field::Vy,
+            "vz" => Field::Vz,
+            "energy" => Field::Energy,
+            "impact-e" | "impact_e" => Field::ImpactE,
+            h if h.contains("impact energy") => Field::ImpactE,
+            h if h.contains("radiated energy") => Field::EnergyJoules,
+            h if h.contains("date") => Field::Date,
+            h if h.starts_with("latitude") => Field::Lat,
+            h if h.starts_with("longitude") => Field::Lon,
+            h if h.starts_with("altitude") => Field::Alt,
+            h if h.starts_with("velocity") => Field::Vel,
+            _ => return None
+        };
+
+        Some(field)
+    }
+}
+
+/// Parse the JSON returned by the CNEOS fireball API
+///
+/// Accepts either the API response (`fields` and `data` arrays) or an
+/// array of objects keyed by the API field names. Returns `Err` only if
+/// the document itself can not be read.
+pub fn parse_json(src: &str) -> Result<Import, String> {
+    let doc: Value = serde_json::from_str(src).map_err(|e| format!("Invalid JSON: {}", e))?;
+
+    let mut import = Import::default();
+
+    match &doc {
+        Value::Object(obj) => {
+            let fields = obj.get("fields")
+                .and_then(Value::as_array)
+                .ok_or_else(|| String::from("Missing \"fields\" array"))?
+                .iter()
+                .map(|f| f.as_str().and_then(Field::from_header))
+                .collect::<Vec<_>>();
+
+            let data = match obj.get("data") {
+                Some(Value::Array(data)) => data.as_slice(),
+                // The API leaves out "data" when there are no results
+                None => &[],
+                Some(_) => return Err(String::from("\"data\" is not an array"))
+            };
+
+            for (i, row) in data.iter().enumerate() {
+                let values = match row.as_array() {
+                    Some(values) => values,
+                    None => {
+                        import.errors.push(ImportError { row: i + 1, message: String::from("Row is not an array") });
+                        continue;
+                    }
+                };
+
+                let cells = fields.iter()
+                    .zip(values.iter())
+                    .filter_map(|(field, value)| field.map(|f| (f, json_cell(value))));
+
+                push_record(&mut import, i + 1, cells);
+            }
+        },
+        Value::Array(rows) => {
+            for (i, row) in rows.iter().enumerate() {
+                let values = match row.as_object() {
+                    Some(values) => values,
+                    None => {
+                        import.errors.push(ImportError { row: i + 1, message: String::from("Row is not an object") });
+                        continue;
+                    }
+                };
+
+                let cells = values.iter()
+                    .filter_map(|(key, value)| Field::from_header(key).map(|f| (f, json_cell(value))));
+
+                push_record(&mut import, i + 1, cells);
+            }
+        },
+        _ => return Err(String::from("Expected an object or an array"))
+    }
+
+    Ok(import)
+}
+
+/// Parse a CSV export of the CNEOS fireball data
+///
+/// The first line must be a header. Unknown columns are ignored.
+pub fn parse_csv(src: &str) -> Result<Import, String> {
+    let mut lines = src.lines().filter(|l| !l.trim().is_empty());
+
+    let fields = split_csv_line(
+        lines.next().ok_or_else(|| String::from("Missing header"))?
+    )
+        .iter()
+        .map(|h| Field::from_header(h))
+        .collect::<Vec<_>>();
+
+    if !fields.contains(&Some(Field::Lat)) || !fields.contains(&Some(Field::Lon)) {
+        return Err(String::from("Header needs latitude and longitude columns"));
+    }
+
+    let mut import = Import::default();
+
+    for (i, line) in lines.enumerate() {
+        let values = split_csv_line(line);
+
+        let cells = fields.iter()
+            .zip(values)
+            .filter_map(|(field, value)| field.map(|f| (f, Some(value))));
+
+        push_record(&mut import, i + 1, cells);
+    }
+
+    Ok(import)
+}
+
+fn json_cell(value: &Value) -> Option<String> {
+    match value {
+        Value::Null => None,
+        Value::String(s) => Some(s.clone()),
+        v => Some(v.to_string())
+    }
+}
+
+fn push_record(import: &mut Import, row: usize, cells: impl Iterator<Item = (Field, Option<String>)>) {
+    match parse_record(cells) {
+        Ok(record) => {
+            import.records.push(record);
+            import.rows.push(row);
+        }
+        Err(message) => import.errors.push(ImportError { row, message })
+    }
+}
+
+/// Build a record from `(field, value)` pairs
+fn parse_record(cells: impl Iterator<Item = (Field, Option<String>)>) -> Result<FireballRecord, String> {
+    let mut record = FireballRecord::default();
+    let mut lat: Option<f32> = None;
+    let mut lon: Option<f32> = None;
+    let mut lat_dir: Option<String> = None;
+    let mut lon_dir: Option<String> = None;
+
+    for (field, value) in cells {
+        let value = match value.as_deref().map(str::trim) {
+            Some(v) if !v.is_empty() => v.to_owned(),
+            _ => continue
+        };
+
+        match field {
+            Field::Date => record.date = Some(value),
+            Field::Lat => lat = Some(parse_coord(&value, 'N', 'S', "lat")?),
+            Field::Lon => lon = Some(parse_coord(&value, 'E', 'W', "lon")?),
+            Field::LatDir => lat_dir = Some(value),
+            Field::LonDir => lon_dir = Some(value),
+            Field::Alt => record.alt = Some(parse_number(&value, "alt")?),
+            Field::Vel => record.vel = Some(parse_number(&value, "vel")?),
+            Field::Vx => record.vx = Some(parse_number(&value, "vx")?),
+            Field::Vy => record.vy = Some(parse_number(&value, "vy")?),
+            Field::Vz => record.vz = Some(parse_number(&value, "vz")?),
+            Field::Energy => record.energy = Some(parse_number(&value, "energy")?),
+            Field::EnergyJoules => record.energy = Some(parse_number(&value, "energy")? / 1e10),
+            Field::ImpactE => record.impact_e = Some(parse_number(&value, "impact-e")?),
+        }
+    }
+
+    let mut lat = lat.ok_or_else(|| String::from("Missing lat"))?;
+    let mut lon = lon.ok_or_else(|| String::from("Missing lon"))?;
+
+    if let Some(dir) = lat_dir {
+        lat = apply_direction(lat, &dir, 'N', 'S', "lat-dir")?;
+    }
+
+    if let Some(dir) = lon_dir {
+        lon = apply_direction(lon, &dir, 'E', 'W', "lon-dir")?;
+    }
+
+    GeoCoord::new(lat, lon, 0.0).validate()?;
+
+    record.lat = lat;
+    record.lon = lon;
+
+    Ok(record)
+}
+
+fn parse_number(value: &str, name: &str) -> Result<f32, String> {
+    value.parse::<f32>()
+        .ok()
+        .filter(|v| v.is_finite())
+        .ok_or_else(|| format!("Invalid {} \"{}\"", name, value))
+}
+
+/// Parse a coordinate that may carry its direction as a suffix (`"12.5S"`)
+fn parse_coord(value: &str, pos: char, neg: char, name: &str) -> Result<f32, String> {
+    match value.chars().last().map(|c| c.to_ascii_uppercase()) {
+        Some(c) if c == pos || c == neg => {
+            let number = parse_number(value[..value.len() - 1].trim(), name)?;
+            apply_direction(number, &c.to_string(), pos, neg, name)
+        },
+        _ => parse_number(value, name)
+    }
+}
+
+/// Apply a direction field to a coordinate
+///
+/// The dataset reports magnitudes with a separate direction, a negative
+/// magnitude is rejected rather than flipped twice.
+fn apply_direction(value: f32, dir: &str, pos: char, neg: char, name: &str) -> Result<f32, String> {
+    if value < 0.0 {
+        return Err(format!("{} has both a sign and a direction", name));
+    }
+
+    match dir.trim().to_ascii_uppercase().as_str() {
+        d if d.len() == 1 && d.starts_with(pos) => Ok(value),
+        d if d.len() == 1 && d.starts_with(neg) => Ok(-value),
+        d => Err(format!("Invalid {} \"{}\", expected {} or {}", name, d, pos, neg))
+    }
+}
+
+/// Split a CSV line on commas, honouring double quoted values
+fn split_csv_line(line: &str) -> Vec<String> {
+    let mut values = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+    let mut chars = line.trim_end_matches('\r').chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                current.push('"');
+                chars.next();
+            },
+            '"' => quoted = !quoted,
+            ',' if !quoted => values.push(std::mem::take(&mut current)),
+            c => current.push(c)
+        }
+    }
+
+    values.push(current);
+    values
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_api_json() {
+        let src = r#"{
+            "signature": {"version": "1.0", "source": "NASA/JPL Fireball Data API"},
+            "count": "2",
+            "fields": ["date", "energy", "impact-e", "lat", "lat-dir", "lon", "lon-dir", "alt", "vel"],
+            "data": [
+                ["2022-03-11 21:22:46", "2.1", "0.077", "70.0", "N", "19.9", "W", "24.0", "18.4"],
+                ["2022-02-28 11:09:31", "4.4", "0.14", "4.1", "S", "150.3", "E", null, null]
+            ]
+        }"#;
+
+        let import = parse_json(src).unwrap();
+        assert!(import.errors.is_empty());
+        assert_eq!(import.records.len(), 2);
+
+        let a = &import.records[0];
+        assert_eq!(a.date.as_deref(), Some("2022-03-11 21:22:46"));
+        assert_eq!(a.lat, 70.0);
+        assert_eq!(a.lon, -19.9);
+        assert_eq!(a.alt, Some(24.0));
+        assert_eq!(a.energy, Some(2.1));
+
+        let b = &import.records[1];
+        assert_eq!(b.lat, -4.1);
+        assert_eq!(b.lon, 150.3);
+        assert_eq!(b.alt, None);
+        assert_eq!(b.vel, None);
+    }
+
+    #[test]
+    fn test_parse_json_reports_bad_rows() {
+        let src = r#"{
+            "fields": ["lat", "lat-dir", "lon", "lon-dir"],
+            "data": [
+                ["10.0", "N", "20.0", "E"],
+                ["10.0", "Q", "20.0", "E"],
+                ["abc", "N", "20.0", "E"],
+                [null, "N", "20.0", "E"],
+                "not a row"
+            ]
+        }"#;
+
+        let import = parse_json(src).unwrap();
+        assert_eq!(import.records.len(), 1);
+        assert_eq!(import.rows, vec![1]);
+
+        let rows: Vec<usize> = import.errors.iter().map(|e| e.row).collect();
+        assert_eq!(rows, vec![2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn test_parse_json_objects() {
+        let src = r#"[{"lat": 12.5, "lon": -40.0, "alt": 30, "impact-e": 0.3}]"#;
+
+        let import = parse_json(src).unwrap();
+        assert_eq!(import.records[0], FireballRecord {
+            lat: 12.5,
+            lon: -40.0,
+            alt: Some(30.0),
+            impact_e: Some(0.3),
+            ..Default::default()
+        });
+    }
+
+    #[test]
+    fn test_parse_json_invalid_document() {
+        assert!(parse_json("{").is_err());
+        assert!(parse_json("42").is_err());
+        assert!(parse_json(r#"{"data": []}"#).is_err());
+    }
+
+    #[test]
+    fn test_parse_csv_api_fields() {
+        let src = "date,lat,lat-dir,lon,lon-dir,alt,energy\n\
+                   2022-03-11 21:22:46,70.0,N,19.9,W,24.0,2.1\n\
+                   2022-02-28 11:09:31,4.1,S,150.3,E,,4.4\n\
+                   2022-02-28 11:09:31,4.1,S,,E,,4.4\n";
+
+        let import = parse_csv(src).unwrap();
+        assert_eq!(import.records.len(), 2);
+        assert_eq!(import.records[1].lat, -4.1);
+        assert_eq!(import.records[1].alt, None);
+        assert_eq!(import.errors, vec![ImportError { row: 3, message: String::from("Missing lon") }]);
+    }
+
+    #[test]
+    fn test_parse_csv_website_export() {
+        let src = "\"Peak Brightness Date/Time (UT)\",\"Latitude (deg.)\",\"Longitude (deg.)\",\"Altitude (km)\",\"Velocity (km/s)\",\"vx\",\"vy\",\"vz\",\"Total Radiated Energy (J)\",\"Calculated Total Impact Energy (kt)\"\r\n\
+                   \"2022-03-11 21:22:46\",\"70.0N\",\"19.9W\",\"24.0\",\"18.4\",\"\",\"\",\"\",\"21000000000\",\"0.077\"\r\n";
+
+        let import = parse_csv(src).unwrap();
+        assert!(import.errors.is_empty());
+
+        let a = &import.records[0];
+        assert_eq!(a.lat, 70.0);
+        assert_eq!(a.lon, -19.9);
+        assert_eq!(a.vel, Some(18.4));
+        assert_eq!(a.vx, None);
+        assert_eq!(a.energy, Some(2.1));
+        assert_eq!(a.impact_e, Some(0.077));
+    }
+
+    #[test]
+    fn test_parse_csv_missing_columns() {
+        assert!(parse_csv("").is_err());
+        assert!(parse_csv("date,alt\n2022-01-01,20").is_err());
+    }
+
+    #[test]
+    fn test_direction_with_sign_is_rejected() {
+        let src = "lat,lat-dir,lon\n-10,S,20";
+
+        let import = parse_csv(src).unwrap();
+        assert_eq!(import.errors.len(), 1);
+    }
+}
@@ -0,0 +1,74 @@
+use std::fmt;
+
+use wasm_bindgen::JsValue;
+
+/// Reason the renderer could not be set up
+///
+/// Returned by the constructors of the renderer and thrown to JavaScript as
+/// its message, so a failure names what went wrong instead of showing up as
+/// a panic.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RendererError {
+    /// The page has no window or document, e.g. in a web worker
+    NoDocument,
+    /// No element with this ID
+    MissingElement(String),
+    /// The element with this ID is not a canvas
+    NotACanvas(String),
+    /// The browser cannot provide a WebGL2 context
+    WebGl2Unsupported,
+    /// A shader failed to compile, with the info log of the compiler
+    ShaderCompile { name: String, log: String },
+    /// A program failed to link, with the info log of the linker
+    ProgramLink { name: String, log: String },
+    /// No shader was exposed under this name
+    UnknownShader(String),
+    /// No program was exposed under this name
+    UnknownProgram(String),
+    /// The backend could not create a buffer, vertex array or texture
+    Resource(String)
+}
+
+impl fmt::Display for RendererError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RendererError::NoDocument => write!(f, "No window or document to render in"),
+            RendererError::MissingElement(id) => write!(f, "No element with id '{}'", id),
+            RendererError::NotACanvas(id) => write!(f, "Element '{}' is not a canvas", id),
+            RendererError::WebGl2Unsupported => write!(f, "WebGL2 is not supported by this browser"),
+            RendererError::ShaderCompile { name, log } => write!(f, "Could not compile shader '{}': {}", name, log),
+            RendererError::ProgramLink { name, log } => write!(f, "Could not link program '{}': {}", name, log),
+            RendererError::UnknownShader(name) => write!(f, "No shader named '{}'", name),
+            RendererError::UnknownProgram(name) => write!(f, "No program named '{}'", name),
+            RendererError::Resource(message) => write!(f, "{}", message)
+        }
+    }
+}
+
+impl std::error::Error for RendererError {}
+
+impl From<RendererError> for JsValue {
+    fn from(error: RendererError) -> Self {
+        JsValue::from_str(&error.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_messages_name_the_cause() {
+        let error = RendererError::ShaderCompile {
+            name: String::from("frag_simple_unlit"),
+            log: String::from("ERROR: 0:4: 'vec5' : undeclared identifier")
+        };
+        assert_eq!(
+            error.to_string(),
+            "Could not compile shader 'frag_simple_unlit': ERROR: 0:4: 'vec5' : undeclared identifier"
+        );
+
+        assert_eq!(RendererError::MissingElement(String::from("glCanvas")).to_string(), "No element with id 'glCanvas'");
+        assert_eq!(RendererError::WebGl2Unsupported.to_string(), "WebGL2 is not supported by this browser");
+    }
+}
@@ -0,0 +1,246 @@
+/// Handle of a vertex or index buffer created by a `GraphicsBackend`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BufferId(pub u32);
+
+/// Handle of a vertex array, which binds buffers to attributes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct VertexArrayId(pub u32);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ShaderId(pub u32);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ProgramId(pub u32);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TextureId(pub u32);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShaderKind {
+    Vertex,
+    Fragment
+}
+
+/// Face of a cube map texture, in the order WebGL numbers them
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CubeFace {
+    PositiveX,
+    NegativeX,
+    PositiveY,
+    NegativeY,
+    PositiveZ,
+    NegativeZ
+}
+
+impl CubeFace {
+    pub const ALL: [CubeFace; 6] = [
+        CubeFace::PositiveX,
+        CubeFace::NegativeX,
+        CubeFace::PositiveY,
+        CubeFace::NegativeY,
+        CubeFace::PositiveZ,
+        CubeFace::NegativeZ
+    ];
+
+    /// Position of the face in `ALL`
+    pub fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// Progress of the images loading into a texture
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LoadState {
+    Pending,
+    /// Every image loaded, also used for textures that never loaded one
+    Loaded,
+    /// An image could not be loaded, the texture keeps its placeholder
+    Failed(String)
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Filter {
+    Nearest,
+    Linear
+}
+
+/// What a texture shows for coords outside 0 to 1
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Wrap {
+    Repeat,
+    MirroredRepeat,
+    ClampToEdge
+}
+
+/// How a texture is sampled, set once per texture with `set_sampler`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SamplerDesc {
+    /// Wrapping along u
+    pub wrap_s: Wrap,
+    /// Wrapping along v
+    pub wrap_t: Wrap,
+    pub min_filter: Filter,
+    pub mag_filter: Filter,
+    /// Whether to generate mipmaps, which the min filter then blends between
+    pub mipmaps: bool,
+    /// Most samples taken along the direction a texture is stretched in,
+    /// 1 to turn it off. Clamped to what the GPU supports and ignored
+    /// without `EXT_texture_filter_anisotropic`.
+    pub anisotropy: f32
+}
+
+impl Default for SamplerDesc {
+    /// Repeating and linear filtered with mipmaps
+    fn default() -> Self {
+        Self {
+            wrap_s: Wrap::Repeat,
+            wrap_t: Wrap::Repeat,
+            min_filter: Filter::Linear,
+            mag_filter: Filter::Linear,
+            mipmaps: true,
+            anisotropy: 1.0
+        }
+    }
+}
+
+/// Value of a shader uniform
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UniformValue {
+    /// An int, also used for the texture unit of a sampler
+    Int(i32),
+    Vec4([f32; 4]),
+    /// Column major, like `Matrix3`
+    Mat3([f32; 9]),
+    /// Column major, like `Matrix4`
+    Mat4([f32; 16])
+}
+
+/// Layout of a float attribute inside an interleaved vertex buffer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VertexAttribute {
+    /// Location of the attribute in the program
+    pub location: u32,
+    /// Number of floats
+    pub size: i32,
+    /// Bytes from the start of one vertex to the next
+    pub stride: i32,
+    /// Bytes from the start of the vertex to the attribute
+    pub offset: i32
+}
+
+/// The commands the renderer sends to the GPU
+///
+/// Resources are referred to by handles handed out by the backend, so the
+/// trait can be used as `dyn GraphicsBackend`. Methods take `&self` like
+/// the WebGL context they wrap. `WebGl2Backend` draws in the browser and
+/// `RecordingBackend` logs the commands for native tests.
+pub trait GraphicsBackend {
+    fn compile_shader(&self, kind: ShaderKind, source: &str) -> Result<ShaderId, String>;
+
+    fn link_program(&self, vert_shader: ShaderId, frag_shader: ShaderId) -> Result<ProgramId, String>;
+
+    /// Location of a vertex attribute, `None` if the program does not use it
+    fn attrib_location(&self, program: ProgramId, name: &str) -> Option<u32>;
+
+    fn use_program(&self, program: ProgramId);
+
+    /// Set a uniform of `program`, which has to be in use
+    ///
+    /// Uniforms the program does not use are ignored, as the shader compiler
+    /// may optimize them out.
+    fn set_uniform(&self, program: ProgramId, name: &str, value: UniformValue);
+
+    fn create_vertex_buffer(&self, data: &[f32]) -> Result<BufferId, String>;
+
+    fn create_index_buffer(&self, data: &[u32]) -> Result<BufferId, String>;
+
+    /// Delete a vertex or index buffer, the handle must not be used
+    /// afterwards
+    fn delete_buffer(&self, buffer: BufferId);
+
+    /// Create a vertex array reading `attributes` from `vertex_buffer` and
+    /// indices from `index_buffer`
+    fn create_vertex_array(
+        &self,
+        vertex_buffer: BufferId,
+        index_buffer: BufferId,
+        attributes: &[VertexAttribute]
+    ) -> Result<VertexArrayId, String>;
+
+    /// Delete a vertex array, the handle must not be used afterwards
+    fn delete_vertex_array(&self, vertex_array: VertexArrayId);
+
+    /// Draw `index_count` indices of a vertex array as triangles
+    fn draw_indexed(&self, vertex_array: VertexArrayId, index_count: i32);
+
+    fn create_texture(&self) -> Result<TextureId, String>;
+
+    /// Replace the image of a texture with RGBA pixels, row by row
+    fn upload_texture(&self, texture: TextureId, width: u32, height: u32, pixels: &[u8]) -> Result<(), String>;
+
+    /// Replace the image of a texture with the image at `url` once it loads
+    ///
+    /// The texture keeps its current image until then.
+    fn load_texture_image(&self, texture: TextureId, url: &str) -> Result<(), String>;
+
+    /// Create a cube map texture, which is bound and filtered like a 2D one
+    fn create_texture_cube(&self) -> Result<TextureId, String>;
+
+    /// Replace the image of one face of a cube map with RGBA pixels, row by row
+    fn upload_cube_face(
+        &self,
+        texture: TextureId,
+        face: CubeFace,
+        width: u32,
+        height: u32,
+        pixels: &[u8]
+    ) -> Result<(), String>;
+
+    /// Replace the image of one face of a cube map with the image at `url`
+    /// once it loads
+    ///
+    /// A cube map cannot be sampled while its faces differ in size, so it
+    /// shows black until the images of all faces are loaded.
+    fn load_cube_face_image(&self, texture: TextureId, face: CubeFace, url: &str) -> Result<(), String>;
+
+    /// Replace every face of a cube map with the faces of the cross shaped
+    /// net at `url` once it loads, see `split_cross_net`
+    fn load_cube_net_image(&self, texture: TextureId, url: &str) -> Result<(), String>;
+
+    /// Whether the images requested for a texture have loaded
+    fn load_state(&self, texture: TextureId) -> LoadState;
+
+    /// Delete a texture and cancel its loads, the handle must not be used
+    /// afterwards
+    fn delete_texture(&self, texture: TextureId);
+
+    /// Set how a texture is sampled
+    ///
+    /// Textures created without a call sample like `SamplerDesc::default()`.
+    /// Set it before uploading, as mipmaps are generated by every upload or
+    /// load after it, or call `generate_mipmaps` after turning them on. A
+    /// cube map has complete mipmaps once all its faces have the same size.
+    fn set_sampler(&self, texture: TextureId, sampler: &SamplerDesc);
+
+    /// Generate the mipmaps of the images a texture already has
+    ///
+    /// Does nothing if its sampler does not use mipmaps, if it has no image
+    /// yet or if the faces of a cube map differ in size.
+    fn generate_mipmaps(&self, texture: TextureId);
+
+    fn bind_texture(&self, unit: u32, texture: TextureId);
+
+    fn viewport(&self, x: i32, y: i32, width: i32, height: i32);
+
+    fn set_clear_color(&self, color: [f32; 4]);
+
+    /// Clear the color and depth buffers
+    fn clear(&self);
+
+    fn set_depth_test(&self, enabled: bool);
+
+    /// Enable or disable culling of back faces
+    fn set_cull_back_faces(&self, enabled: bool);
+}
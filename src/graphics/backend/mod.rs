@@ -0,0 +1,4 @@
+pub mod graphics_backend;
+pub mod recording;
+pub mod software;
+pub mod webgl;
\ No newline at end of file
@@ -0,0 +1,252 @@
+use std::{cell::{Cell, RefCell}, collections::HashMap};
+
+use super::graphics_backend::{
+    BufferId, CubeFace, GraphicsBackend, LoadState, ProgramId, SamplerDesc, ShaderId, ShaderKind, TextureId, UniformValue,
+    VertexArrayId, VertexAttribute
+};
+
+/// A call made on a `RecordingBackend`
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    CompileShader { shader: ShaderId, kind: ShaderKind },
+    LinkProgram { program: ProgramId, vert_shader: ShaderId, frag_shader: ShaderId },
+    UseProgram(ProgramId),
+    SetUniform { program: ProgramId, name: String, value: UniformValue },
+    CreateVertexBuffer { buffer: BufferId, len: usize },
+    CreateIndexBuffer { buffer: BufferId, len: usize },
+    DeleteBuffer(BufferId),
+    CreateVertexArray { vertex_array: VertexArrayId, attributes: Vec<VertexAttribute> },
+    DeleteVertexArray(VertexArrayId),
+    DrawIndexed { vertex_array: VertexArrayId, index_count: i32 },
+    CreateTexture(TextureId),
+    UploadTexture { texture: TextureId, width: u32, height: u32 },
+    LoadTextureImage { texture: TextureId, url: String },
+    CreateTextureCube(TextureId),
+    UploadCubeFace { texture: TextureId, face: CubeFace, width: u32, height: u32 },
+    LoadCubeFaceImage { texture: TextureId, face: CubeFace, url: String },
+    LoadCubeNetImage { texture: TextureId, url: String },
+    DeleteTexture(TextureId),
+    SetSampler { texture: TextureId, sampler: SamplerDesc },
+    GenerateMipmaps(TextureId),
+    BindTexture { unit: u32, texture: TextureId },
+    Viewport { x: i32, y: i32, width: i32, height: i32 },
+    SetClearColor([f32; 4]),
+    Clear,
+    SetDepthTest(bool),
+    SetCullBackFaces(bool)
+}
+
+/// `GraphicsBackend` that draws nothing and logs every command instead
+///
+/// Used by native tests to check what a frame sends to the GPU. Shaders
+/// always compile, images load at once and attributes get locations in the
+/// order they are first asked for.
+pub struct RecordingBackend {
+    commands: RefCell<Vec<Command>>,
+    next_id: Cell<u32>,
+    attributes: RefCell<HashMap<String, u32>>
+}
+
+#[allow(dead_code)]
+impl RecordingBackend {
+    pub fn new() -> Self {
+        Self {
+            commands: RefCell::new(Vec::new()),
+            next_id: Cell::new(0),
+            attributes: RefCell::new(HashMap::new())
+        }
+    }
+
+    /// Every command recorded since the last `take_commands`
+    pub fn commands(&self) -> Vec<Command> {
+        self.commands.borrow().clone()
+    }
+
+    /// Return the recorded commands and start a new recording, e.g. to
+    /// only look at the commands of the next frame
+    pub fn take_commands(&self) -> Vec<Command> {
+        self.commands.take()
+    }
+
+    /// Number of draw calls recorded
+    pub fn draw_count(&self) -> usize {
+        self.commands.borrow().iter()
+            .filter(|command| matches!(command, Command::DrawIndexed { .. }))
+            .count()
+    }
+
+    /// Every value set for the uniform `name`, in order
+    pub fn uniform_values(&self, name: &str) -> Vec<UniformValue> {
+        self.commands.borrow().iter()
+            .filter_map(|command| match command {
+                Command::SetUniform { name: n, value, .. } if n == name => Some(*value),
+                _ => None
+            })
+            .collect()
+    }
+
+    fn record(&self, command: Command) {
+        self.commands.borrow_mut().push(command);
+    }
+
+    /// Unique ID for a new resource, shared by every kind of resource
+    fn next_id(&self) -> u32 {
+        let id = self.next_id.get();
+        self.next_id.set(id + 1);
+        id
+    }
+}
+
+impl Default for RecordingBackend {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl GraphicsBackend for RecordingBackend {
+    fn compile_shader(&self, kind: ShaderKind, _source: &str) -> Result<ShaderId, String> {
+        let shader = ShaderId(self.next_id());
+        self.record(Command::CompileShader { shader, kind });
+        Ok(shader)
+    }
+
+    fn link_program(&self, vert_shader: ShaderId, frag_shader: ShaderId) -> Result<ProgramId, String> {
+        let program = ProgramId(self.next_id());
+        self.record(Command::LinkProgram { program, vert_shader, frag_shader });
+        Ok(program)
+    }
+
+    fn attrib_location(&self, _program: ProgramId, name: &str) -> Option<u32> {
+        let mut attributes = self.attributes.borrow_mut();
+        let next = attributes.len() as u32;
+
+        Some(*attributes.entry(String::from(name)).or_insert(next))
+    }
+
+    fn use_program(&self, program: ProgramId) {
+        self.record(Command::UseProgram(program));
+    }
+
+    fn set_uniform(&self, program: ProgramId, name: &str, value: UniformValue) {
+        self.record(Command::SetUniform { program, name: String::from(name), value });
+    }
+
+    fn create_vertex_buffer(&self, data: &[f32]) -> Result<BufferId, String> {
+        let buffer = BufferId(self.next_id());
+        self.record(Command::CreateVertexBuffer { buffer, len: data.len() });
+        Ok(buffer)
+    }
+
+    fn create_index_buffer(&self, data: &[u32]) -> Result<BufferId, String> {
+        let buffer = BufferId(self.next_id());
+        self.record(Command::CreateIndexBuffer { buffer, len: data.len() });
+        Ok(buffer)
+    }
+
+    fn delete_buffer(&self, buffer: BufferId) {
+        self.record(Command::DeleteBuffer(buffer));
+    }
+
+    fn create_vertex_array(
+        &self,
+        _vertex_buffer: BufferId,
+        _index_buffer: BufferId,
+        attributes: &[VertexAttribute]
+    ) -> Result<VertexArrayId, String> {
+        let vertex_array = VertexArrayId(self.next_id());
+        self.record(Command::CreateVertexArray { vertex_array, attributes: attributes.to_vec() });
+        Ok(vertex_array)
+    }
+
+    fn delete_vertex_array(&self, vertex_array: VertexArrayId) {
+        self.record(Command::DeleteVertexArray(vertex_array));
+    }
+
+    fn draw_indexed(&self, vertex_array: VertexArrayId, index_count: i32) {
+        self.record(Command::DrawIndexed { vertex_array, index_count });
+    }
+
+    fn create_texture(&self) -> Result<TextureId, String> {
+        let texture = TextureId(self.next_id());
+        self.record(Command::CreateTexture(texture));
+        Ok(texture)
+    }
+
+    fn upload_texture(&self, texture: TextureId, width: u32, height: u32, _pixels: &[u8]) -> Result<(), String> {
+        self.record(Command::UploadTexture { texture, width, height });
+        Ok(())
+    }
+
+    fn load_texture_image(&self, texture: TextureId, url: &str) -> Result<(), String> {
+        self.record(Command::LoadTextureImage { texture, url: String::from(url) });
+        Ok(())
+    }
+
+    fn create_texture_cube(&self) -> Result<TextureId, String> {
+        let texture = TextureId(self.next_id());
+        self.record(Command::CreateTextureCube(texture));
+        Ok(texture)
+    }
+
+    fn upload_cube_face(
+        &self,
+        texture: TextureId,
+        face: CubeFace,
+        width: u32,
+        height: u32,
+        _pixels: &[u8]
+    ) -> Result<(), String> {
+        self.record(Command::UploadCubeFace { texture, face, width, height });
+        Ok(())
+    }
+
+    fn load_cube_face_image(&self, texture: TextureId, face: CubeFace, url: &str) -> Result<(), String> {
+        self.record(Command::LoadCubeFaceImage { texture, face, url: String::from(url) });
+        Ok(())
+    }
+
+    fn load_cube_net_image(&self, texture: TextureId, url: &str) -> Result<(), String> {
+        self.record(Command::LoadCubeNetImage { texture, url: String::from(url) });
+        Ok(())
+    }
+
+    fn load_state(&self, _texture: TextureId) -> LoadState {
+        LoadState::Loaded
+    }
+
+    fn delete_texture(&self, texture: TextureId) {
+        self.record(Command::DeleteTexture(texture));
+    }
+
+    fn set_sampler(&self, texture: TextureId, sampler: &SamplerDesc) {
+        self.record(Command::SetSampler { texture, sampler: *sampler });
+    }
+
+    fn generate_mipmaps(&self, texture: TextureId) {
+        self.record(Command::GenerateMipmaps(texture));
+    }
+
+    fn bind_texture(&self, unit: u32, texture: TextureId) {
+        self.record(Command::BindTexture { unit, texture });
+    }
+
+    fn viewport(&self, x: i32, y: i32, width: i32, height: i32) {
+        self.record(Command::Viewport { x, y, width, height });
+    }
+
+    fn set_clear_color(&self, color: [f32; 4]) {
+        self.record(Command::SetClearColor(color));
+    }
+
+    fn clear(&self) {
+        self.record(Command::Clear);
+    }
+
+    fn set_depth_test(&self, enabled: bool) {
+        self.record(Command::SetDepthTest(enabled));
+    }
+
+    fn set_cull_back_faces(&self, enabled: bool) {
+        self.record(Command::SetCullBackFaces(enabled));
+    }
+}
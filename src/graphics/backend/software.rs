@@ -0,0 +1,989 @@
+use std::{cell::RefCell, collections::HashMap};
+
+use crate::graphics::{frag_shaders, texture_cube::split_cross_net, vert_shaders};
+
+use super::graphics_backend::{
+    BufferId, CubeFace, Filter, GraphicsBackend, LoadState, ProgramId, SamplerDesc, ShaderId, ShaderKind, TextureId,
+    UniformValue, VertexArrayId, VertexAttribute, Wrap
+};
+
+/// Attribute locations of the standard 3D vertex shader
+const POSITION_LOCATION: u32 = 0;
+const NORMAL_LOCATION: u32 = 1;
+const UV_LOCATION: u32 = 2;
+
+const IDENTITY_4: [f32; 16] = [
+    1.0, 0.0, 0.0, 0.0,
+    0.0, 1.0, 0.0, 0.0,
+    0.0, 0.0, 1.0, 0.0,
+    0.0, 0.0, 0.0, 1.0
+];
+
+const IDENTITY_3: [f32; 9] = [
+    1.0, 0.0, 0.0,
+    0.0, 1.0, 0.0,
+    0.0, 0.0, 1.0
+];
+
+/// Fragment shading of a program, reimplemented from its GLSL source
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Shading {
+    /// `simple_unlit`, a flat color
+    Unlit,
+    /// `simple_unlit_shaded`, a texture lit from a fixed direction with a
+    /// rim of atmosphere
+    UnlitShaded,
+    /// `cube_map`, a cube map sampled by the normal in object space, lit
+    /// like `UnlitShaded`
+    CubeMap,
+    /// `equirectangular`, a lat/lon image mapped by the normal in object
+    /// space, lit like `UnlitShaded`
+    Equirectangular
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Shader {
+    Vertex3D,
+    Fragment(Shading)
+}
+
+enum Buffer {
+    Vertex(Vec<f32>),
+    Index(Vec<u32>)
+}
+
+struct VertexArray {
+    vertex_buffer: BufferId,
+    index_buffer: BufferId,
+    attributes: Vec<VertexAttribute>
+}
+
+struct Program {
+    shading: Shading,
+    uniforms: HashMap<String, UniformValue>
+}
+
+#[derive(Clone)]
+struct Image {
+    width: u32,
+    height: u32,
+    /// RGBA, row by row
+    pixels: Vec<u8>
+}
+
+enum Texture {
+    Flat(Image),
+    /// One image per face, in the order of `CubeFace::ALL`
+    Cube(Vec<Image>)
+}
+
+/// Where a requested image goes once it is available
+#[derive(Debug, Clone, Copy)]
+enum ImageTarget {
+    Flat,
+    Face(CubeFace),
+    Net
+}
+
+/// An image requested before it was added with `add_image`
+struct ImageLoad {
+    texture: TextureId,
+    target: ImageTarget,
+    url: String
+}
+
+/// Output of the vertex shader
+#[derive(Debug, Clone, Copy)]
+struct ClipVertex {
+    position: [f32; 4],
+    normal: [f32; 3],
+    uv: [f32; 2],
+    /// Normal in object space
+    local_normal: [f32; 3]
+}
+
+impl ClipVertex {
+    fn lerp(&self, other: &ClipVertex, t: f32) -> ClipVertex {
+        let lerp = |a: f32, b: f32| a + (b - a) * t;
+
+        ClipVertex {
+            position: [0, 1, 2, 3].map(|i| lerp(self.position[i], other.position[i])),
+            normal: [0, 1, 2].map(|i| lerp(self.normal[i], other.normal[i])),
+            uv: [0, 1].map(|i| lerp(self.uv[i], other.uv[i])),
+            local_normal: [0, 1, 2].map(|i| lerp(self.local_normal[i], other.local_normal[i]))
+        }
+    }
+}
+
+/// A vertex after the perspective divide and viewport transform
+#[derive(Debug, Clone, Copy)]
+struct ScreenVertex {
+    /// Pixels from the top left of the framebuffer
+    x: f32,
+    y: f32,
+    /// Depth in `[0, 1]`
+    z: f32,
+    inv_w: f32,
+    /// Varyings divided by w, for perspective correct interpolation
+    normal: [f32; 3],
+    uv: [f32; 2],
+    local_normal: [f32; 3]
+}
+
+struct State {
+    color: Vec<[f32; 4]>,
+    depth: Vec<f32>,
+    buffers: Vec<Buffer>,
+    vertex_arrays: Vec<VertexArray>,
+    shaders: Vec<Shader>,
+    programs: Vec<Program>,
+    textures: Vec<Texture>,
+    /// Texture bound to each texture unit
+    units: HashMap<u32, TextureId>,
+    /// Images `load_texture_image` can load, by URL
+    images: HashMap<String, Image>,
+    /// Loads waiting for `add_image` or `fail_image`
+    loads: Vec<ImageLoad>,
+    /// Error of every URL passed to `fail_image`
+    broken: HashMap<String, String>,
+    /// Error of every texture with a failed load
+    failed: HashMap<TextureId, String>,
+    /// Samplers set with `set_sampler`, the others use the default
+    samplers: HashMap<TextureId, SamplerDesc>,
+    program: Option<ProgramId>,
+    viewport: (i32, i32, i32, i32),
+    clear_color: [f32; 4],
+    depth_test: bool,
+    cull_back_faces: bool
+}
+
+/// `GraphicsBackend` rasterizing on the CPU into an RGBA framebuffer
+///
+/// Deterministic and without any dependency on a GPU, so frames can be
+/// compared to reference images in native tests. Only runs the programs
+/// linked from the standard 3D vertex shader and the `simple_unlit`,
+/// `simple_unlit_shaded`, `cube_map` or `equirectangular` fragment shaders,
+/// which are reimplemented in Rust.
+/// Supports indexed triangles, the depth test and back-face culling with
+/// counter clockwise front faces, like WebGL. Textures are sampled without
+/// mipmaps, with the wrapping and mag filter of their sampler. The faces of
+/// cube maps are always clamped to their edges.
+pub struct SoftwareBackend {
+    width: u32,
+    height: u32,
+    state: RefCell<State>
+}
+
+#[allow(dead_code)]
+impl SoftwareBackend {
+    /// Create a backend with a transparent framebuffer of `width` by
+    /// `height` pixels and the viewport covering all of it
+    pub fn new(width: u32, height: u32) -> Self {
+        let size = (width * height) as usize;
+
+        Self {
+            width,
+            height,
+            state: RefCell::new(State {
+                color: vec![[0.0, 0.0, 0.0, 0.0]; size],
+                depth: vec![1.0; size],
+                buffers: Vec::new(),
+                vertex_arrays: Vec::new(),
+                shaders: Vec::new(),
+                programs: Vec::new(),
+                textures: Vec::new(),
+                units: HashMap::new(),
+                images: HashMap::new(),
+                loads: Vec::new(),
+                broken: HashMap::new(),
+                failed: HashMap::new(),
+                samplers: HashMap::new(),
+                program: None,
+                viewport: (0, 0, width as i32, height as i32),
+                clear_color: [0.0, 0.0, 0.0, 0.0],
+                depth_test: false,
+                cull_back_faces: false
+            })
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Make the image at `url` load from RGBA `pixels`
+    ///
+    /// Loads of `url` requested before finish now. Loads of a URL without
+    /// an image stay pending until it is added or `fail_image` is called.
+    pub fn add_image(&self, url: &str, width: u32, height: u32, pixels: Vec<u8>) {
+        let image = Image { width, height, pixels };
+        let loads = {
+            let mut state = self.state.borrow_mut();
+            state.images.insert(String::from(url), image.clone());
+            state.take_loads(url)
+        };
+
+        for load in loads {
+            self.apply_image(load.texture, load.target, url, &image);
+        }
+    }
+
+    /// Make loading the image at `url` fail, like a missing file
+    pub fn fail_image(&self, url: &str) {
+        let error = format!("Could not load image {}", url);
+        let mut state = self.state.borrow_mut();
+
+        for load in state.take_loads(url) {
+            state.failed.insert(load.texture, error.clone());
+        }
+
+        state.broken.insert(String::from(url), error);
+    }
+
+    /// The framebuffer as RGBA bytes, starting with the top row
+    pub fn pixels(&self) -> Vec<u8> {
+        self.state.borrow().color.iter()
+            .flat_map(|color| color.map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8))
+            .collect()
+    }
+
+    /// Color of the pixel `x` from the left and `y` from the top
+    pub fn pixel(&self, x: u32, y: u32) -> [u8; 4] {
+        let color = self.state.borrow().color[(y * self.width + x) as usize];
+        color.map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8)
+    }
+}
+
+impl State {
+    fn add_buffer(&mut self, buffer: Buffer) -> BufferId {
+        self.buffers.push(buffer);
+        BufferId(self.buffers.len() as u32 - 1)
+    }
+
+    /// Remove the pending loads of `url`
+    fn take_loads(&mut self, url: &str) -> Vec<ImageLoad> {
+        let (taken, kept) = std::mem::take(&mut self.loads).into_iter().partition(|load| load.url == url);
+        self.loads = kept;
+        taken
+    }
+
+    /// Run the vertex shader on vertex `index` of a vertex array
+    fn fetch_vertex(&self, vertex_array: &VertexArray, index: u32, mvp: &[f32; 16], normal_matrix: &[f32; 9]) -> ClipVertex {
+        let data = match &self.buffers[vertex_array.vertex_buffer.0 as usize] {
+            Buffer::Vertex(data) => data,
+            Buffer::Index(_) => panic!("Vertex array reads vertices from an index buffer")
+        };
+
+        // Attributes that are not enabled read as 0, except w
+        let mut attributes = [[0.0, 0.0, 0.0, 1.0]; 3];
+
+        for attribute in &vertex_array.attributes {
+            let start = (index as usize * attribute.stride as usize + attribute.offset as usize) / 4;
+
+            if let Some(value) = attributes.get_mut(attribute.location as usize) {
+                value[..attribute.size as usize].copy_from_slice(&data[start..start + attribute.size as usize]);
+            }
+        }
+
+        let [position, normal, uv] = attributes;
+
+        ClipVertex {
+            position: mul_mat4(mvp, [position[0], position[1], position[2], 1.0]),
+            normal: mul_mat3(normal_matrix, [normal[0], normal[1], normal[2]]),
+            uv: [uv[0], uv[1]],
+            local_normal: [normal[0], normal[1], normal[2]]
+        }
+    }
+
+    fn to_screen(&self, vertex: &ClipVertex, height: u32) -> ScreenVertex {
+        let (vx, vy, vw, vh) = self.viewport;
+        let inv_w = 1.0 / vertex.position[3];
+        let ndc = [0, 1, 2].map(|i| vertex.position[i] * inv_w);
+
+        // Window coordinates start at the bottom left, the framebuffer at the top left
+        let x = (ndc[0] + 1.0) * 0.5 * vw as f32 + vx as f32;
+        let y = height as f32 - ((ndc[1] + 1.0) * 0.5 * vh as f32 + vy as f32);
+
+        ScreenVertex {
+            x,
+            y,
+            z: ndc[2] * 0.5 + 0.5,
+            inv_w,
+            normal: vertex.normal.map(|n| n * inv_w),
+            uv: vertex.uv.map(|n| n * inv_w),
+            local_normal: vertex.local_normal.map(|n| n * inv_w)
+        }
+    }
+
+    fn shade(
+        &self,
+        shading: Shading,
+        uniforms: &HashMap<String, UniformValue>,
+        normal: [f32; 3],
+        uv: [f32; 2],
+        local_normal: [f32; 3]
+    ) -> [f32; 4] {
+        match shading {
+            Shading::Unlit => match uniforms.get("color") {
+                Some(UniformValue::Vec4(color)) => *color,
+                _ => [0.0, 0.0, 0.0, 0.0]
+            },
+            Shading::UnlitShaded => lit(self.sample_unit(uniforms, "tex", [-uv[0], -uv[1]]), normal),
+            Shading::CubeMap => {
+                // The cube map has longitude 0 at +z and 90E at +x, the globe at -z and -x
+                let [x, y, z] = local_normal;
+                lit(self.sample_unit(uniforms, "tex", [-x, y, -z]), normal)
+            }
+            Shading::Equirectangular => {
+                let [x, y, z] = normalize(local_normal);
+                let lon = (-x).atan2(-z);
+                let lat = y.clamp(-1.0, 1.0).asin();
+                let u = lon / (2.0 * std::f32::consts::PI) + 0.5;
+                let v = 0.5 - lat / std::f32::consts::PI;
+
+                lit(self.sample_unit(uniforms, "tex", [u, v]), normal)
+            }
+        }
+    }
+
+    /// Sample the texture bound to the unit in the sampler uniform `name`
+    ///
+    /// A 2D texture is sampled at the first two coords, a cube map in the
+    /// direction of all three.
+    fn sample_unit<const N: usize>(&self, uniforms: &HashMap<String, UniformValue>, name: &str, coords: [f32; N]) -> [f32; 4] {
+        let unit = match uniforms.get(name) {
+            Some(UniformValue::Int(unit)) => *unit as u32,
+            _ => 0
+        };
+
+        let (texture, sampler) = match self.units.get(&unit) {
+            Some(texture) => (&self.textures[texture.0 as usize], self.samplers.get(texture).copied().unwrap_or_default()),
+            None => return [0.0, 0.0, 0.0, 1.0]
+        };
+
+        match (texture, coords.len()) {
+            (Texture::Flat(image), 2) => sample(image, [coords[0], coords[1]], &sampler),
+            (Texture::Cube(faces), 3) => sample_cube(faces, [coords[0], coords[1], coords[2]], &sampler),
+            // The sampler does not match the texture
+            _ => [0.0, 0.0, 0.0, 1.0]
+        }
+    }
+}
+
+impl SoftwareBackend {
+    /// Load the image at `url` into a texture, now if it was added and
+    /// once it is added otherwise
+    fn request_image(&self, texture: TextureId, target: ImageTarget, url: &str) {
+        let image = self.state.borrow().images.get(url).cloned();
+
+        if let Some(image) = image {
+            self.apply_image(texture, target, url, &image);
+            return;
+        }
+
+        let mut state = self.state.borrow_mut();
+        match state.broken.get(url).cloned() {
+            Some(error) => {
+                state.failed.insert(texture, error);
+            }
+            None => state.loads.push(ImageLoad { texture, target, url: String::from(url) })
+        }
+    }
+
+    /// Upload a loaded image, failing the texture if it does not fit
+    fn apply_image(&self, texture: TextureId, target: ImageTarget, url: &str, image: &Image) {
+        let result = match target {
+            ImageTarget::Flat => self.upload_texture(texture, image.width, image.height, &image.pixels),
+            ImageTarget::Face(face) => self.upload_cube_face(texture, face, image.width, image.height, &image.pixels),
+            ImageTarget::Net => split_cross_net(image.width, image.height, &image.pixels).and_then(|(size, faces)| {
+                CubeFace::ALL.into_iter().zip(faces)
+                    .try_for_each(|(face, pixels)| self.upload_cube_face(texture, face, size, size, &pixels))
+            })
+        };
+
+        if let Err(error) = result {
+            self.state.borrow_mut().failed.insert(texture, format!("Could not use image {}: {}", url, error));
+        }
+    }
+
+    fn draw_triangle(&self, state: &mut State, program: ProgramId, vertices: [ScreenVertex; 3]) {
+        let [a, b, c] = vertices;
+
+        // Twice the signed area, negative for counter clockwise front faces
+        // as the framebuffer is flipped upside down
+        let area = edge(&a, &b, c.x, c.y);
+
+        if area == 0.0 || (state.cull_back_faces && area > 0.0) {
+            return;
+        }
+
+        let (vx, vy, vw, vh) = state.viewport;
+        let top = self.height as i32 - (vy + vh);
+
+        let min_x = a.x.min(b.x).min(c.x).floor().max(vx.max(0) as f32) as i32;
+        let max_x = a.x.max(b.x).max(c.x).ceil().min((vx + vw).min(self.width as i32) as f32) as i32;
+        let min_y = a.y.min(b.y).min(c.y).floor().max(top.max(0) as f32) as i32;
+        let max_y = a.y.max(b.y).max(c.y).ceil().min((top + vh).min(self.height as i32) as f32) as i32;
+
+        let shading = state.programs[program.0 as usize].shading;
+        let uniforms = state.programs[program.0 as usize].uniforms.clone();
+
+        for y in min_y..max_y {
+            for x in min_x..max_x {
+                let (px, py) = (x as f32 + 0.5, y as f32 + 0.5);
+
+                let l0 = edge(&b, &c, px, py) / area;
+                let l1 = edge(&c, &a, px, py) / area;
+                let l2 = edge(&a, &b, px, py) / area;
+
+                if l0 < 0.0 || l1 < 0.0 || l2 < 0.0 {
+                    continue;
+                }
+
+                let z = l0 * a.z + l1 * b.z + l2 * c.z;
+                if !(0.0..=1.0).contains(&z) {
+                    continue;
+                }
+
+                let index = (y as u32 * self.width + x as u32) as usize;
+
+                if state.depth_test {
+                    if z >= state.depth[index] {
+                        continue;
+                    }
+
+                    state.depth[index] = z;
+                }
+
+                let inv_w = l0 * a.inv_w + l1 * b.inv_w + l2 * c.inv_w;
+                let normal = [0, 1, 2].map(|i| (l0 * a.normal[i] + l1 * b.normal[i] + l2 * c.normal[i]) / inv_w);
+                let uv = [0, 1].map(|i| (l0 * a.uv[i] + l1 * b.uv[i] + l2 * c.uv[i]) / inv_w);
+                let local_normal = [0, 1, 2].map(|i| {
+                    (l0 * a.local_normal[i] + l1 * b.local_normal[i] + l2 * c.local_normal[i]) / inv_w
+                });
+
+                state.color[index] = state.shade(shading, &uniforms, normal, uv, local_normal);
+            }
+        }
+    }
+}
+
+impl GraphicsBackend for SoftwareBackend {
+    fn compile_shader(&self, kind: ShaderKind, source: &str) -> Result<ShaderId, String> {
+        let shader = match kind {
+            ShaderKind::Vertex if source == vert_shaders::vert_shader_3d::SHADER => Shader::Vertex3D,
+            ShaderKind::Fragment if source == frag_shaders::simple_unlit::SHADER => Shader::Fragment(Shading::Unlit),
+            ShaderKind::Fragment if source == frag_shaders::simple_unlit_shaded::SHADER => Shader::Fragment(Shading::UnlitShaded),
+            ShaderKind::Fragment if source == frag_shaders::cube_map::SHADER => Shader::Fragment(Shading::CubeMap),
+            ShaderKind::Fragment if source == frag_shaders::equirectangular::SHADER => {
+                Shader::Fragment(Shading::Equirectangular)
+            }
+            _ => return Err(String::from("Shader is not supported by the software backend"))
+        };
+
+        let mut state = self.state.borrow_mut();
+        state.shaders.push(shader);
+        Ok(ShaderId(state.shaders.len() as u32 - 1))
+    }
+
+    fn link_program(&self, vert_shader: ShaderId, frag_shader: ShaderId) -> Result<ProgramId, String> {
+        let mut state = self.state.borrow_mut();
+
+        let shading = match (state.shaders[vert_shader.0 as usize], state.shaders[frag_shader.0 as usize]) {
+            (Shader::Vertex3D, Shader::Fragment(shading)) => shading,
+            _ => return Err(String::from("Program needs a vertex and a fragment shader"))
+        };
+
+        state.programs.push(Program { shading, uniforms: HashMap::new() });
+        Ok(ProgramId(state.programs.len() as u32 - 1))
+    }
+
+    fn attrib_location(&self, _program: ProgramId, name: &str) -> Option<u32> {
+        match name {
+            "vertex_position" => Some(POSITION_LOCATION),
+            "vertex_normal" => Some(NORMAL_LOCATION),
+            "vertex_uv_coords" => Some(UV_LOCATION),
+            _ => None
+        }
+    }
+
+    fn use_program(&self, program: ProgramId) {
+        self.state.borrow_mut().program = Some(program);
+    }
+
+    fn set_uniform(&self, program: ProgramId, name: &str, value: UniformValue) {
+        self.state.borrow_mut().programs[program.0 as usize].uniforms.insert(String::from(name), value);
+    }
+
+    fn create_vertex_buffer(&self, data: &[f32]) -> Result<BufferId, String> {
+        Ok(self.state.borrow_mut().add_buffer(Buffer::Vertex(data.to_vec())))
+    }
+
+    fn create_index_buffer(&self, data: &[u32]) -> Result<BufferId, String> {
+        Ok(self.state.borrow_mut().add_buffer(Buffer::Index(data.to_vec())))
+    }
+
+    fn delete_buffer(&self, buffer: BufferId) {
+        // Keep the slot so the other handles stay valid
+        match &mut self.state.borrow_mut().buffers[buffer.0 as usize] {
+            Buffer::Vertex(data) => *data = Vec::new(),
+            Buffer::Index(data) => *data = Vec::new()
+        }
+    }
+
+    fn create_vertex_array(
+        &self,
+        vertex_buffer: BufferId,
+        index_buffer: BufferId,
+        attributes: &[VertexAttribute]
+    ) -> Result<VertexArrayId, String> {
+        let mut state = self.state.borrow_mut();
+
+        state.vertex_arrays.push(VertexArray { vertex_buffer, index_buffer, attributes: attributes.to_vec() });
+        Ok(VertexArrayId(state.vertex_arrays.len() as u32 - 1))
+    }
+
+    fn delete_vertex_array(&self, vertex_array: VertexArrayId) {
+        // Keep the slot so the other handles stay valid
+        self.state.borrow_mut().vertex_arrays[vertex_array.0 as usize].attributes.clear();
+    }
+
+    fn draw_indexed(&self, vertex_array: VertexArrayId, index_count: i32) {
+        let mut state = self.state.borrow_mut();
+
+        let program = match state.program {
+            Some(program) => program,
+            None => return
+        };
+
+        let uniforms = &state.programs[program.0 as usize].uniforms;
+        let mat4 = |name: &str| match uniforms.get(name) {
+            Some(UniformValue::Mat4(m)) => *m,
+            _ => IDENTITY_4
+        };
+
+        // projection * view * transform, like the vertex shader
+        let mvp = mul_mat4_mat4(&mul_mat4_mat4(&mat4("projection_matrix"), &mat4("view_matrix")), &mat4("transform_matrix"));
+        let normal_matrix = match uniforms.get("normal_matrix") {
+            Some(UniformValue::Mat3(m)) => *m,
+            _ => IDENTITY_3
+        };
+
+        let vao = &state.vertex_arrays[vertex_array.0 as usize];
+        let indices = match &state.buffers[vao.index_buffer.0 as usize] {
+            Buffer::Index(indices) => &indices[..(index_count as usize).min(indices.len())],
+            Buffer::Vertex(_) => panic!("Vertex array reads indices from a vertex buffer")
+        };
+
+        let triangles: Vec<[ClipVertex; 3]> = indices.chunks_exact(3)
+            .map(|triangle| [0, 1, 2].map(|i| state.fetch_vertex(vao, triangle[i], &mvp, &normal_matrix)))
+            .collect();
+
+        for triangle in triangles {
+            let polygon = clip_near(&triangle);
+
+            for i in 1..polygon.len().saturating_sub(1) {
+                let vertices = [polygon[0], polygon[i], polygon[i + 1]].map(|v| state.to_screen(&v, self.height));
+                self.draw_triangle(&mut state, program, vertices);
+            }
+        }
+    }
+
+    fn create_texture(&self) -> Result<TextureId, String> {
+        let mut state = self.state.borrow_mut();
+
+        state.textures.push(Texture::Flat(Image { width: 1, height: 1, pixels: vec![0, 0, 0, 255] }));
+        Ok(TextureId(state.textures.len() as u32 - 1))
+    }
+
+    fn upload_texture(&self, texture: TextureId, width: u32, height: u32, pixels: &[u8]) -> Result<(), String> {
+        if pixels.len() != (width * height * 4) as usize {
+            return Err(String::from("Texture data does not match its size"));
+        }
+
+        self.state.borrow_mut().textures[texture.0 as usize] = Texture::Flat(Image { width, height, pixels: pixels.to_vec() });
+        Ok(())
+    }
+
+    fn load_texture_image(&self, texture: TextureId, url: &str) -> Result<(), String> {
+        self.request_image(texture, ImageTarget::Flat, url);
+        Ok(())
+    }
+
+    fn create_texture_cube(&self) -> Result<TextureId, String> {
+        let mut state = self.state.borrow_mut();
+        let black = Image { width: 1, height: 1, pixels: vec![0, 0, 0, 255] };
+
+        state.textures.push(Texture::Cube(vec![black; 6]));
+        Ok(TextureId(state.textures.len() as u32 - 1))
+    }
+
+    fn upload_cube_face(
+        &self,
+        texture: TextureId,
+        face: CubeFace,
+        width: u32,
+        height: u32,
+        pixels: &[u8]
+    ) -> Result<(), String> {
+        if pixels.len() != (width * height * 4) as usize {
+            return Err(String::from("Texture data does not match its size"));
+        }
+
+        match &mut self.state.borrow_mut().textures[texture.0 as usize] {
+            Texture::Cube(faces) => faces[face.index()] = Image { width, height, pixels: pixels.to_vec() },
+            Texture::Flat(_) => return Err(String::from("Texture is not a cube map"))
+        }
+
+        Ok(())
+    }
+
+    fn load_cube_face_image(&self, texture: TextureId, face: CubeFace, url: &str) -> Result<(), String> {
+        self.request_image(texture, ImageTarget::Face(face), url);
+        Ok(())
+    }
+
+    fn load_cube_net_image(&self, texture: TextureId, url: &str) -> Result<(), String> {
+        self.request_image(texture, ImageTarget::Net, url);
+        Ok(())
+    }
+
+    fn load_state(&self, texture: TextureId) -> LoadState {
+        let state = self.state.borrow();
+
+        if let Some(error) = state.failed.get(&texture) {
+            LoadState::Failed(error.clone())
+        } else if state.loads.iter().any(|load| load.texture == texture) {
+            LoadState::Pending
+        } else {
+            LoadState::Loaded
+        }
+    }
+
+    fn delete_texture(&self, texture: TextureId) {
+        let mut state = self.state.borrow_mut();
+
+        state.loads.retain(|load| load.texture != texture);
+        state.failed.remove(&texture);
+        state.units.retain(|_, bound| *bound != texture);
+        state.samplers.remove(&texture);
+        state.textures[texture.0 as usize] = Texture::Flat(Image { width: 1, height: 1, pixels: vec![0, 0, 0, 255] });
+    }
+
+    fn set_sampler(&self, texture: TextureId, sampler: &SamplerDesc) {
+        self.state.borrow_mut().samplers.insert(texture, *sampler);
+    }
+
+    fn generate_mipmaps(&self, _texture: TextureId) {
+        // Textures are sampled without mipmaps
+    }
+
+    fn bind_texture(&self, unit: u32, texture: TextureId) {
+        self.state.borrow_mut().units.insert(unit, texture);
+    }
+
+    fn viewport(&self, x: i32, y: i32, width: i32, height: i32) {
+        self.state.borrow_mut().viewport = (x, y, width, height);
+    }
+
+    fn set_clear_color(&self, color: [f32; 4]) {
+        self.state.borrow_mut().clear_color = color;
+    }
+
+    fn clear(&self) {
+        let mut state = self.state.borrow_mut();
+        let clear_color = state.clear_color;
+
+        state.color.fill(clear_color);
+        state.depth.fill(1.0);
+    }
+
+    fn set_depth_test(&self, enabled: bool) {
+        self.state.borrow_mut().depth_test = enabled;
+    }
+
+    fn set_cull_back_faces(&self, enabled: bool) {
+        self.state.borrow_mut().cull_back_faces = enabled;
+    }
+}
+
+/// Clip a triangle against the near plane, where z = -w
+///
+/// Returns a convex polygon of up to four vertices, or none if the
+/// triangle is behind the near plane.
+fn clip_near(triangle: &[ClipVertex; 3]) -> Vec<ClipVertex> {
+    let distance = |v: &ClipVertex| v.position[2] + v.position[3];
+    let mut out = Vec::with_capacity(4);
+
+    for i in 0..3 {
+        let (a, b) = (&triangle[i], &triangle[(i + 1) % 3]);
+        let (da, db) = (distance(a), distance(b));
+
+        if da >= 0.0 {
+            out.push(*a);
+        }
+
+        if (da >= 0.0) != (db >= 0.0) {
+            out.push(a.lerp(b, da / (da - db)));
+        }
+    }
+
+    out
+}
+
+/// Twice the signed area of the triangle `a`, `b`, `(x, y)`
+fn edge(a: &ScreenVertex, b: &ScreenVertex, x: f32, y: f32) -> f32 {
+    (b.x - a.x) * (y - a.y) - (b.y - a.y) * (x - a.x)
+}
+
+/// Sample a cube map in `direction`, like the WebGL `samplerCube`
+///
+/// Faces are sampled with the filter of `sampler` and clamped to their edges.
+fn sample_cube(faces: &[Image], direction: [f32; 3], sampler: &SamplerDesc) -> [f32; 4] {
+    let [x, y, z] = direction;
+    let a = direction.map(f32::abs);
+
+    // Face and its coords in the major axis, as in the OpenGL ES spec
+    let (face, sc, tc, ma) = if a[0] >= a[1] && a[0] >= a[2] {
+        if x > 0.0 { (CubeFace::PositiveX, -z, -y, a[0]) } else { (CubeFace::NegativeX, z, -y, a[0]) }
+    } else if a[1] >= a[2] {
+        if y > 0.0 { (CubeFace::PositiveY, x, z, a[1]) } else { (CubeFace::NegativeY, x, -z, a[1]) }
+    } else if z > 0.0 {
+        (CubeFace::PositiveZ, x, -y, a[2])
+    } else {
+        (CubeFace::NegativeZ, -x, -y, a[2])
+    };
+
+    let sampler = SamplerDesc { wrap_s: Wrap::ClampToEdge, wrap_t: Wrap::ClampToEdge, ..*sampler };
+
+    sample(&faces[face.index()], [(sc / ma + 1.0) * 0.5, (tc / ma + 1.0) * 0.5], &sampler)
+}
+
+/// Sample an image with the wrapping and mag filter of `sampler`
+fn sample(texture: &Image, uv: [f32; 2], sampler: &SamplerDesc) -> [f32; 4] {
+    let (width, height) = (texture.width as i64, texture.height as i64);
+    let s = uv[0] * width as f32;
+    let t = uv[1] * height as f32;
+
+    let texel = |x: i64, y: i64| {
+        let index = (wrap(y, height, sampler.wrap_t) * width + wrap(x, width, sampler.wrap_s)) as usize * 4;
+        [0, 1, 2, 3].map(|i| texture.pixels[index + i] as f32 / 255.0)
+    };
+
+    if sampler.mag_filter == Filter::Nearest {
+        return texel(s.floor() as i64, t.floor() as i64);
+    }
+
+    let (s, t) = (s - 0.5, t - 0.5);
+    let (x0, y0) = (s.floor(), t.floor());
+    let (fx, fy) = (s - x0, t - y0);
+    let (x0, y0) = (x0 as i64, y0 as i64);
+    let (c00, c10, c01, c11) = (texel(x0, y0), texel(x0 + 1, y0), texel(x0, y0 + 1), texel(x0 + 1, y0 + 1));
+
+    [0, 1, 2, 3].map(|i| {
+        let top = c00[i] + (c10[i] - c00[i]) * fx;
+        let bottom = c01[i] + (c11[i] - c01[i]) * fx;
+        top + (bottom - top) * fy
+    })
+}
+
+/// Texel coord `i` wrapped into `0..size`
+fn wrap(i: i64, size: i64, wrap: Wrap) -> i64 {
+    match wrap {
+        Wrap::Repeat => i.rem_euclid(size),
+        Wrap::MirroredRepeat => {
+            let i = i.rem_euclid(2 * size);
+            if i < size { i } else { 2 * size - 1 - i }
+        }
+        Wrap::ClampToEdge => i.clamp(0, size - 1)
+    }
+}
+
+/// Light a texture color from a fixed direction with a rim of atmosphere,
+/// like the shaded fragment shaders
+fn lit(col: [f32; 4], normal: [f32; 3]) -> [f32; 4] {
+    let light_dir = normalize([1.0, 1.0, 1.0]);
+    let fres = 1.0 - dot(normal, [0.0, 0.0, 1.0]);
+    let atmosphere = fres * fres * fres * fres * 1.85;
+    let shadow = (dot(normal, light_dir) + 0.25) * 0.85;
+    let intensity = (shadow + atmosphere).min(1.0);
+
+    [col[0] * intensity, col[1] * intensity, col[2] * intensity, 1.0]
+}
+
+/// Column major matrix times a column vector
+fn mul_mat4(m: &[f32; 16], v: [f32; 4]) -> [f32; 4] {
+    [0, 1, 2, 3].map(|row| (0..4).map(|col| m[row + 4 * col] * v[col]).sum())
+}
+
+fn mul_mat3(m: &[f32; 9], v: [f32; 3]) -> [f32; 3] {
+    [0, 1, 2].map(|row| (0..3).map(|col| m[row + 3 * col] * v[col]).sum())
+}
+
+/// Product `a·b` of two column major matrices, applying `b` first
+fn mul_mat4_mat4(a: &[f32; 16], b: &[f32; 16]) -> [f32; 16] {
+    let mut out = [0.0; 16];
+
+    for row in 0..4 {
+        for col in 0..4 {
+            out[row + 4 * col] = (0..4).map(|k| a[row + 4 * k] * b[k + 4 * col]).sum();
+        }
+    }
+
+    out
+}
+
+fn dot(a: [f32; 3], b: [f32; 3]) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn normalize(v: [f32; 3]) -> [f32; 3] {
+    let length = dot(v, v).sqrt();
+    v.map(|c| c / length)
+}
+
+/// Compare the framebuffer of `backend` to the reference PNG `golden/<name>.png`
+///
+/// Writes the reference instead if the `UPDATE_GOLDEN` environment variable
+/// is set. If the reference is missing or does not match, the frame is
+/// written to `target/golden/<name>.png` for inspection.
+#[cfg(test)]
+pub fn assert_golden(backend: &SoftwareBackend, name: &str) {
+    use std::{fs::{self, File}, io::BufWriter, path::{Path, PathBuf}};
+
+    fn write_png(path: &Path, width: u32, height: u32, pixels: &[u8]) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        let mut encoder = png::Encoder::new(BufWriter::new(File::create(path).unwrap()), width, height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.write_header().unwrap().write_image_data(pixels).unwrap();
+    }
+
+    let root = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let path = root.join("golden").join(format!("{}.png", name));
+    let actual = root.join("target").join("golden").join(format!("{}.png", name));
+    let pixels = backend.pixels();
+
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        write_png(&path, backend.width(), backend.height(), &pixels);
+        return;
+    }
+
+    if !path.exists() {
+        write_png(&actual, backend.width(), backend.height(), &pixels);
+        panic!(
+            "No reference image {}, see {} and run `UPDATE_GOLDEN=1 cargo test` to accept it",
+            path.display(),
+            actual.display()
+        );
+    }
+
+    let decoder = png::Decoder::new(File::open(&path).unwrap());
+    let mut reader = decoder.read_info().unwrap();
+    let mut expected = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut expected).unwrap();
+
+    // Allow for rounding differences between platforms
+    let matches = (info.width, info.height) == (backend.width(), backend.height()) &&
+        info.color_type == png::ColorType::Rgba &&
+        expected.iter().zip(&pixels).all(|(a, b)| a.abs_diff(*b) <= 2);
+
+    if !matches {
+        write_png(&actual, backend.width(), backend.height(), &pixels);
+        panic!("Frame does not match {}, see {}", path.display(), actual.display());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Backend with the `simple_unlit` program in use, drawing in clip space
+    fn unlit(color: [f32; 4]) -> (SoftwareBackend, ProgramId) {
+        let backend = SoftwareBackend::new(8, 8);
+        let vert = backend.compile_shader(ShaderKind::Vertex, vert_shaders::vert_shader_3d::SHADER).unwrap();
+        let frag = backend.compile_shader(ShaderKind::Fragment, frag_shaders::simple_unlit::SHADER).unwrap();
+        let program = backend.link_program(vert, frag).unwrap();
+
+        backend.use_program(program);
+        backend.set_uniform(program, "color", UniformValue::Vec4(color));
+        (backend, program)
+    }
+
+    fn triangle(backend: &SoftwareBackend, positions: [[f32; 3]; 3]) -> VertexArrayId {
+        let verts: Vec<f32> = positions.iter().flat_map(|p| [p[0], p[1], p[2], 0.0, 0.0, 1.0, 0.0, 0.0]).collect();
+        let vertex_buffer = backend.create_vertex_buffer(&verts).unwrap();
+        let index_buffer = backend.create_index_buffer(&[0, 1, 2]).unwrap();
+
+        backend.create_vertex_array(vertex_buffer, index_buffer, &[
+            VertexAttribute { location: POSITION_LOCATION, size: 3, stride: 32, offset: 0 }
+        ]).unwrap()
+    }
+
+    #[test]
+    fn test_triangle_coverage_and_culling() {
+        let (backend, _) = unlit([1.0, 0.0, 0.0, 1.0]);
+        backend.set_cull_back_faces(true);
+
+        // Counter clockwise, covering the bottom left half
+        let front = triangle(&backend, [[-1.0, -1.0, 0.0], [1.0, -1.0, 0.0], [-1.0, 1.0, 0.0]]);
+        backend.draw_indexed(front, 3);
+
+        assert_eq!(backend.pixel(0, 7), [255, 0, 0, 255]);
+        assert_eq!(backend.pixel(7, 0), [0, 0, 0, 0]);
+
+        // Clockwise, covering the top right half
+        let back = triangle(&backend, [[1.0, 1.0, 0.0], [1.0, -1.0, 0.0], [-1.0, 1.0, 0.0]]);
+        backend.draw_indexed(back, 3);
+        assert_eq!(backend.pixel(7, 0), [0, 0, 0, 0]);
+
+        backend.set_cull_back_faces(false);
+        backend.draw_indexed(back, 3);
+        assert_eq!(backend.pixel(7, 0), [255, 0, 0, 255]);
+    }
+
+    #[test]
+    fn test_depth_test() {
+        let (backend, program) = unlit([1.0, 0.0, 0.0, 1.0]);
+        backend.set_depth_test(true);
+        backend.clear();
+
+        let near = triangle(&backend, [[-3.0, -1.0, -0.5], [1.0, -1.0, -0.5], [1.0, 3.0, -0.5]]);
+        let far = triangle(&backend, [[-3.0, -1.0, 0.5], [1.0, -1.0, 0.5], [1.0, 3.0, 0.5]]);
+        backend.draw_indexed(near, 3);
+
+        backend.set_uniform(program, "color", UniformValue::Vec4([0.0, 1.0, 0.0, 1.0]));
+        backend.draw_indexed(far, 3);
+        assert_eq!(backend.pixel(4, 4), [255, 0, 0, 255]);
+
+        backend.set_depth_test(false);
+        backend.draw_indexed(far, 3);
+        assert_eq!(backend.pixel(4, 4), [0, 255, 0, 255]);
+    }
+
+    #[test]
+    fn test_sampler_wrap_and_filter() {
+        // Black on the left, white on the right
+        let image = Image { width: 2, height: 1, pixels: vec![0, 0, 0, 255, 255, 255, 255, 255] };
+        let red = |sampler: SamplerDesc, u: f32| sample(&image, [u, 0.5], &sampler)[0];
+
+        let repeat = SamplerDesc::default();
+        let clamped = SamplerDesc { wrap_s: Wrap::ClampToEdge, ..repeat };
+        let mirrored = SamplerDesc { wrap_s: Wrap::MirroredRepeat, ..repeat };
+        let nearest = SamplerDesc { mag_filter: Filter::Nearest, ..repeat };
+
+        // At the left edge, halfway between the last and the first texel
+        assert_eq!(red(repeat, 0.0), 0.5);
+        assert_eq!(red(clamped, 0.0), 0.0);
+        assert_eq!(red(mirrored, 0.0), 0.0);
+        assert_eq!(red(mirrored, -0.25), 0.0);
+        assert_eq!(red(mirrored, -0.75), 1.0);
+
+        assert_eq!(red(repeat, 0.4), 0.3);
+        assert_eq!(red(nearest, 0.4), 0.0);
+    }
+}
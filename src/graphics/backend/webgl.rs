@@ -0,0 +1,784 @@
+use std::{cell::{Cell, RefCell}, collections::HashMap, rc::Rc};
+
+use wasm_bindgen::{prelude::Closure, JsCast, JsValue};
+use web_sys::{
+    CanvasRenderingContext2d, HtmlCanvasElement, HtmlImageElement, WebGl2RenderingContext as GL, WebGlBuffer,
+    WebGlProgram, WebGlShader, WebGlTexture, WebGlUniformLocation, WebGlVertexArrayObject
+};
+
+use crate::graphics::texture_cube::split_cross_net;
+
+use super::graphics_backend::{
+    BufferId, CubeFace, Filter, GraphicsBackend, LoadState, ProgramId, SamplerDesc, ShaderId, ShaderKind, TextureId,
+    UniformValue, VertexArrayId, VertexAttribute, Wrap
+};
+
+/// Parameter and limit of `EXT_texture_filter_anisotropic`
+const TEXTURE_MAX_ANISOTROPY_EXT: u32 = 0x84FE;
+const MAX_TEXTURE_MAX_ANISOTROPY_EXT: u32 = 0x84FF;
+
+/// `GraphicsBackend` drawing with a `WebGl2RenderingContext`
+///
+/// Keeps the WebGL objects in tables indexed by their handles. Uniform
+/// locations are looked up once per program and name.
+pub struct WebGl2Backend {
+    gl: GL,
+    buffers: RefCell<Slots<WebGlBuffer>>,
+    vertex_arrays: RefCell<Slots<WebGlVertexArrayObject>>,
+    shaders: RefCell<Vec<WebGlShader>>,
+    programs: RefCell<Vec<WebGlProgram>>,
+    textures: RefCell<Slots<GlTexture>>,
+    uniforms: RefCell<HashMap<(ProgramId, String), Option<WebGlUniformLocation>>>,
+    /// Images loading into textures, dropped by `load_state` once finished
+    loads: RefCell<Vec<ImageLoad>>,
+    /// Error of every texture with a failed load
+    failed: RefCell<HashMap<TextureId, String>>,
+    /// Highest anisotropy, if `EXT_texture_filter_anisotropic` is supported
+    max_anisotropy: Option<f32>
+}
+
+/// Table of WebGL objects, reusing the slots of deleted ones
+///
+/// Deleting an object drops it from its slot, so the table does not keep
+/// the JavaScript object alive.
+struct Slots<T> {
+    slots: Vec<Option<T>>,
+    free: Vec<u32>
+}
+
+impl<T> Slots<T> {
+    fn new() -> Self {
+        Self { slots: Vec::new(), free: Vec::new() }
+    }
+
+    /// Store an object, returning the index of its slot
+    fn insert(&mut self, object: T) -> u32 {
+        match self.free.pop() {
+            Some(index) => {
+                self.slots[index as usize] = Some(object);
+                index
+            },
+            None => {
+                self.slots.push(Some(object));
+                self.slots.len() as u32 - 1
+            }
+        }
+    }
+
+    /// Object in a slot, panics if it was deleted like an out of range
+    /// index would
+    fn get(&self, index: u32) -> &T {
+        self.slots[index as usize].as_ref().expect("Handle of a deleted object")
+    }
+
+    /// Take the object out of its slot, `None` if it was already deleted
+    fn remove(&mut self, index: u32) -> Option<T> {
+        let object = self.slots.get_mut(index as usize)?.take();
+        if object.is_some() {
+            self.free.push(index);
+        }
+        object
+    }
+}
+
+/// A texture in the table of a `WebGl2Backend`
+struct GlTexture {
+    /// `TEXTURE_2D` or `TEXTURE_CUBE_MAP`
+    target: u32,
+    texture: Rc<WebGlTexture>,
+    /// Shared with the pending loads
+    mipmaps: Rc<Mipmaps>
+}
+
+/// When to generate the mipmaps of a texture
+struct Mipmaps {
+    /// Whether uploads generate mipmaps
+    enabled: Cell<bool>,
+    /// Width and height of the image of a 2D texture, or of each face of a
+    /// cube map, 0 until uploaded
+    sizes: Cell<[(u32, u32); 6]>
+}
+
+impl Mipmaps {
+    /// Generate the mipmaps of the texture bound to `target` if it uses them
+    ///
+    /// WebGL fails to generate them without an image or while the faces of
+    /// a cube map differ in size, so that waits until the last face of the
+    /// new size is uploaded.
+    fn generate(&self, gl: &GL, target: u32) {
+        let sizes = self.sizes.get();
+        let complete = sizes[0] != (0, 0) && (target != GL::TEXTURE_CUBE_MAP || sizes.iter().all(|size| *size == sizes[0]));
+
+        if self.enabled.get() && complete {
+            gl.generate_mipmap(target);
+        }
+    }
+
+    /// Note the new size of the bound 2D texture and generate its mipmaps if
+    /// it uses them
+    fn generate_2d(&self, gl: &GL, width: u32, height: u32) {
+        self.set_size(0, width, height);
+        self.generate(gl, GL::TEXTURE_2D);
+    }
+
+    /// Note the new size of a face of the bound cube map and generate its
+    /// mipmaps if it uses them
+    fn generate_cube(&self, gl: &GL, face: CubeFace, width: u32, height: u32) {
+        self.set_size(face.index(), width, height);
+        self.generate(gl, GL::TEXTURE_CUBE_MAP);
+    }
+
+    fn set_size(&self, index: usize, width: u32, height: u32) {
+        let mut sizes = self.sizes.get();
+        sizes[index] = (width, height);
+        self.sizes.set(sizes);
+    }
+}
+
+/// An image loading into a texture
+///
+/// Owns the handlers of the image, so they are freed with the load instead
+/// of leaking.
+struct ImageLoad {
+    texture: TextureId,
+    image: HtmlImageElement,
+    state: Rc<RefCell<LoadState>>,
+    _on_load: Closure<dyn FnMut()>,
+    _on_error: Closure<dyn FnMut()>
+}
+
+impl Drop for ImageLoad {
+    fn drop(&mut self) {
+        // The handlers are freed now, so the image must not call them anymore
+        self.image.set_onload(None);
+        self.image.set_onerror(None);
+    }
+}
+
+#[allow(dead_code)]
+impl WebGl2Backend {
+    pub fn new(gl: GL) -> Self {
+        let max_anisotropy = gl.get_extension("EXT_texture_filter_anisotropic").ok().flatten()
+            .and_then(|_| gl.get_parameter(MAX_TEXTURE_MAX_ANISOTROPY_EXT).ok())
+            .and_then(|max| max.as_f64())
+            .map(|max| max as f32);
+
+        Self {
+            gl,
+            buffers: RefCell::new(Slots::new()),
+            vertex_arrays: RefCell::new(Slots::new()),
+            shaders: RefCell::new(Vec::new()),
+            programs: RefCell::new(Vec::new()),
+            textures: RefCell::new(Slots::new()),
+            uniforms: RefCell::new(HashMap::new()),
+            loads: RefCell::new(Vec::new()),
+            failed: RefCell::new(HashMap::new()),
+            max_anisotropy
+        }
+    }
+
+    /// The wrapped context
+    pub fn gl(&self) -> &GL {
+        &self.gl
+    }
+
+    fn program(&self, program: ProgramId) -> WebGlProgram {
+        self.programs.borrow()[program.0 as usize].clone()
+    }
+
+    fn texture(&self, texture: TextureId) -> Rc<WebGlTexture> {
+        self.textures.borrow().get(texture.0).texture.clone()
+    }
+
+    fn target(&self, texture: TextureId) -> u32 {
+        self.textures.borrow().get(texture.0).target
+    }
+
+    fn mipmaps(&self, texture: TextureId) -> Rc<Mipmaps> {
+        self.textures.borrow().get(texture.0).mipmaps.clone()
+    }
+
+    fn add_texture(&self, target: u32) -> Result<TextureId, String> {
+        let texture = self.gl.create_texture().ok_or_else(|| String::from("Cannot create gl texture"))?;
+        let sampler = SamplerDesc::default();
+
+        // Replace the defaults of WebGL, which need mipmaps before sampling
+        self.gl.bind_texture(target, Some(&texture));
+        self.apply_sampler(target, &sampler);
+
+        let mipmaps = Mipmaps { enabled: Cell::new(sampler.mipmaps), sizes: Cell::new([(0, 0); 6]) };
+        let texture = GlTexture { target, texture: Rc::new(texture), mipmaps: Rc::new(mipmaps) };
+        Ok(TextureId(self.textures.borrow_mut().insert(texture)))
+    }
+
+    /// Set the parameters of the texture bound to `target`
+    fn apply_sampler(&self, target: u32, sampler: &SamplerDesc) {
+        let min_filter = match (sampler.min_filter, sampler.mipmaps) {
+            (Filter::Nearest, false) => GL::NEAREST,
+            (Filter::Linear, false) => GL::LINEAR,
+            (Filter::Nearest, true) => GL::NEAREST_MIPMAP_NEAREST,
+            (Filter::Linear, true) => GL::LINEAR_MIPMAP_LINEAR
+        };
+
+        let mag_filter = match sampler.mag_filter {
+            Filter::Nearest => GL::NEAREST,
+            Filter::Linear => GL::LINEAR
+        };
+
+        let wrap = |wrap: Wrap| match wrap {
+            Wrap::Repeat => GL::REPEAT,
+            Wrap::MirroredRepeat => GL::MIRRORED_REPEAT,
+            Wrap::ClampToEdge => GL::CLAMP_TO_EDGE
+        } as i32;
+
+        self.gl.tex_parameteri(target, GL::TEXTURE_MIN_FILTER, min_filter as i32);
+        self.gl.tex_parameteri(target, GL::TEXTURE_MAG_FILTER, mag_filter as i32);
+        self.gl.tex_parameteri(target, GL::TEXTURE_WRAP_S, wrap(sampler.wrap_s));
+        self.gl.tex_parameteri(target, GL::TEXTURE_WRAP_T, wrap(sampler.wrap_t));
+
+        if let Some(max) = self.max_anisotropy {
+            self.gl.tex_parameterf(target, TEXTURE_MAX_ANISOTROPY_EXT, sampler.anisotropy.clamp(1.0, max));
+        }
+    }
+
+    /// Load the image at `url` into `texture`, see `load_image`
+    fn load(
+        &self,
+        texture: TextureId,
+        url: &str,
+        on_load: impl FnOnce(&HtmlImageElement) -> Result<(), String> + 'static
+    ) -> Result<(), String> {
+        let load = load_image(texture, url, on_load)?;
+        self.loads.borrow_mut().push(load);
+        Ok(())
+    }
+
+    fn uniform_location(&self, program: ProgramId, name: &str) -> Option<WebGlUniformLocation> {
+        let key = (program, String::from(name));
+
+        if let Some(location) = self.uniforms.borrow().get(&key) {
+            return location.clone();
+        }
+
+        let location = self.gl.get_uniform_location(&self.program(program), name);
+        self.uniforms.borrow_mut().insert(key, location.clone());
+        location
+    }
+
+    fn create_buffer(&self, target: u32, data: &js_sys::Object) -> Result<BufferId, String> {
+        let buffer = self.gl.create_buffer().ok_or_else(|| String::from("Unable to create buffer"))?;
+
+        self.gl.bind_buffer(target, Some(&buffer));
+        self.gl.buffer_data_with_array_buffer_view(target, data, GL::STATIC_DRAW);
+
+        Ok(BufferId(self.buffers.borrow_mut().insert(buffer)))
+    }
+}
+
+impl GraphicsBackend for WebGl2Backend {
+    fn compile_shader(&self, kind: ShaderKind, source: &str) -> Result<ShaderId, String> {
+        let shader_type = match kind {
+            ShaderKind::Vertex => GL::VERTEX_SHADER,
+            ShaderKind::Fragment => GL::FRAGMENT_SHADER
+        };
+
+        let shader = compile_shader(&self.gl, shader_type, source)?;
+
+        let mut shaders = self.shaders.borrow_mut();
+        shaders.push(shader);
+        Ok(ShaderId(shaders.len() as u32 - 1))
+    }
+
+    fn link_program(&self, vert_shader: ShaderId, frag_shader: ShaderId) -> Result<ProgramId, String> {
+        let program = {
+            let shaders = self.shaders.borrow();
+            link_program(&self.gl, &shaders[vert_shader.0 as usize], &shaders[frag_shader.0 as usize])?
+        };
+
+        let mut programs = self.programs.borrow_mut();
+        programs.push(program);
+        Ok(ProgramId(programs.len() as u32 - 1))
+    }
+
+    fn attrib_location(&self, program: ProgramId, name: &str) -> Option<u32> {
+        let location = self.gl.get_attrib_location(&self.program(program), name);
+
+        // -1 if the attribute is not used by the program
+        u32::try_from(location).ok()
+    }
+
+    fn use_program(&self, program: ProgramId) {
+        self.gl.use_program(Some(&self.program(program)));
+    }
+
+    fn set_uniform(&self, program: ProgramId, name: &str, value: UniformValue) {
+        let location = match self.uniform_location(program, name) {
+            Some(location) => location,
+            None => return
+        };
+
+        match value {
+            UniformValue::Int(value) => self.gl.uniform1i(Some(&location), value),
+            UniformValue::Vec4(value) => self.gl.uniform4fv_with_f32_array(Some(&location), &value),
+            UniformValue::Mat3(value) => self.gl.uniform_matrix3fv_with_f32_array(Some(&location), false, &value),
+            UniformValue::Mat4(value) => self.gl.uniform_matrix4fv_with_f32_array(Some(&location), false, &value)
+        }
+    }
+
+    fn create_vertex_buffer(&self, data: &[f32]) -> Result<BufferId, String> {
+        // The view is only valid until the next allocation in wasm memory
+        unsafe {
+            let view = js_sys::Float32Array::view(data);
+            self.create_buffer(GL::ARRAY_BUFFER, &view)
+        }
+    }
+
+    fn create_index_buffer(&self, data: &[u32]) -> Result<BufferId, String> {
+        // Unbind any vertex array, so it does not take the index buffer
+        self.gl.bind_vertex_array(None);
+
+        unsafe {
+            let view = js_sys::Uint32Array::view(data);
+            self.create_buffer(GL::ELEMENT_ARRAY_BUFFER, &view)
+        }
+    }
+
+    fn delete_buffer(&self, buffer: BufferId) {
+        if let Some(buffer) = self.buffers.borrow_mut().remove(buffer.0) {
+            self.gl.delete_buffer(Some(&buffer));
+        }
+    }
+
+    fn create_vertex_array(
+        &self,
+        vertex_buffer: BufferId,
+        index_buffer: BufferId,
+        attributes: &[VertexAttribute]
+    ) -> Result<VertexArrayId, String> {
+        let vao = self.gl.create_vertex_array()
+            .ok_or_else(|| String::from("Could not create vertex array"))?;
+
+        self.gl.bind_vertex_array(Some(&vao));
+
+        {
+            let buffers = self.buffers.borrow();
+            self.gl.bind_buffer(GL::ARRAY_BUFFER, Some(buffers.get(vertex_buffer.0)));
+            self.gl.bind_buffer(GL::ELEMENT_ARRAY_BUFFER, Some(buffers.get(index_buffer.0)));
+        }
+
+        for attribute in attributes {
+            self.gl.vertex_attrib_pointer_with_i32(
+                attribute.location,
+                attribute.size,
+                GL::FLOAT,
+                false,
+                attribute.stride,
+                attribute.offset
+            );
+
+            self.gl.enable_vertex_attrib_array(attribute.location);
+        }
+
+        self.gl.bind_vertex_array(None);
+
+        Ok(VertexArrayId(self.vertex_arrays.borrow_mut().insert(vao)))
+    }
+
+    fn delete_vertex_array(&self, vertex_array: VertexArrayId) {
+        if let Some(vao) = self.vertex_arrays.borrow_mut().remove(vertex_array.0) {
+            self.gl.delete_vertex_array(Some(&vao));
+        }
+    }
+
+    fn draw_indexed(&self, vertex_array: VertexArrayId, index_count: i32) {
+        self.gl.bind_vertex_array(Some(self.vertex_arrays.borrow().get(vertex_array.0)));
+        self.gl.draw_elements_with_i32(GL::TRIANGLES, index_count, GL::UNSIGNED_INT, 0);
+    }
+
+    fn create_texture(&self) -> Result<TextureId, String> {
+        self.add_texture(GL::TEXTURE_2D)
+    }
+
+    fn upload_texture(&self, texture: TextureId, width: u32, height: u32, pixels: &[u8]) -> Result<(), String> {
+        self.gl.bind_texture(GL::TEXTURE_2D, Some(&self.texture(texture)));
+        upload_pixels(&self.gl, GL::TEXTURE_2D, width, height, pixels)?;
+        self.mipmaps(texture).generate_2d(&self.gl, width, height);
+
+        Ok(())
+    }
+
+    fn load_texture_image(&self, texture: TextureId, url: &str) -> Result<(), String> {
+        let gl_texture = self.texture(texture);
+        let mipmaps = self.mipmaps(texture);
+        let gl = self.gl.clone();
+
+        self.load(texture, url, move |img| {
+            gl.bind_texture(GL::TEXTURE_2D, Some(&gl_texture));
+            upload_image(&gl, GL::TEXTURE_2D, img)?;
+            mipmaps.generate_2d(&gl, img.natural_width(), img.natural_height());
+
+            Ok(())
+        })
+    }
+
+    fn create_texture_cube(&self) -> Result<TextureId, String> {
+        self.add_texture(GL::TEXTURE_CUBE_MAP)
+    }
+
+    fn upload_cube_face(
+        &self,
+        texture: TextureId,
+        face: CubeFace,
+        width: u32,
+        height: u32,
+        pixels: &[u8]
+    ) -> Result<(), String> {
+        self.gl.bind_texture(GL::TEXTURE_CUBE_MAP, Some(&self.texture(texture)));
+        upload_pixels(&self.gl, face_target(face), width, height, pixels)?;
+        self.mipmaps(texture).generate_cube(&self.gl, face, width, height);
+
+        Ok(())
+    }
+
+    fn load_cube_face_image(&self, texture: TextureId, face: CubeFace, url: &str) -> Result<(), String> {
+        let gl_texture = self.texture(texture);
+        let mipmaps = self.mipmaps(texture);
+        let gl = self.gl.clone();
+
+        self.load(texture, url, move |img| {
+            gl.bind_texture(GL::TEXTURE_CUBE_MAP, Some(&gl_texture));
+            upload_image(&gl, face_target(face), img)?;
+            mipmaps.generate_cube(&gl, face, img.natural_width(), img.natural_height());
+
+            Ok(())
+        })
+    }
+
+    fn load_cube_net_image(&self, texture: TextureId, url: &str) -> Result<(), String> {
+        let gl_texture = self.texture(texture);
+        let mipmaps = self.mipmaps(texture);
+        let gl = self.gl.clone();
+
+        self.load(texture, url, move |img| {
+            let pixels = image_pixels(img)
+                .map_err(|e| e.as_string().unwrap_or_else(|| String::from("Unable to read image")))?;
+            let (size, faces) = split_cross_net(img.natural_width(), img.natural_height(), &pixels)?;
+
+            gl.bind_texture(GL::TEXTURE_CUBE_MAP, Some(&gl_texture));
+
+            for (face, pixels) in CubeFace::ALL.into_iter().zip(faces) {
+                upload_pixels(&gl, face_target(face), size, size, &pixels)?;
+                mipmaps.generate_cube(&gl, face, size, size);
+            }
+
+            Ok(())
+        })
+    }
+
+    fn load_state(&self, texture: TextureId) -> LoadState {
+        let mut failed = self.failed.borrow_mut();
+        let mut pending = false;
+
+        // Finished loads are dropped here rather than in their handlers,
+        // which cannot free themselves while running
+        self.loads.borrow_mut().retain(|load| {
+            if load.texture != texture {
+                return true;
+            }
+
+            match load.state.borrow().clone() {
+                LoadState::Pending => pending = true,
+                LoadState::Loaded => (),
+                LoadState::Failed(error) => {
+                    failed.insert(texture, error);
+                }
+            }
+
+            *load.state.borrow() == LoadState::Pending
+        });
+
+        match failed.get(&texture) {
+            Some(error) => LoadState::Failed(error.clone()),
+            None if pending => LoadState::Pending,
+            None => LoadState::Loaded
+        }
+    }
+
+    fn delete_texture(&self, texture: TextureId) {
+        self.loads.borrow_mut().retain(|load| load.texture != texture);
+        self.failed.borrow_mut().remove(&texture);
+
+        if let Some(gl_texture) = self.textures.borrow_mut().remove(texture.0) {
+            self.gl.delete_texture(Some(&gl_texture.texture));
+        }
+    }
+
+    fn set_sampler(&self, texture: TextureId, sampler: &SamplerDesc) {
+        let target = self.target(texture);
+
+        self.gl.bind_texture(target, Some(&self.texture(texture)));
+        self.apply_sampler(target, sampler);
+        self.mipmaps(texture).enabled.set(sampler.mipmaps);
+    }
+
+    fn generate_mipmaps(&self, texture: TextureId) {
+        let target = self.target(texture);
+
+        self.gl.bind_texture(target, Some(&self.texture(texture)));
+        self.mipmaps(texture).generate(&self.gl, target);
+    }
+
+    fn bind_texture(&self, unit: u32, texture: TextureId) {
+        self.gl.active_texture(GL::TEXTURE0 + unit);
+        self.gl.bind_texture(self.target(texture), Some(&self.texture(texture)));
+    }
+
+    fn viewport(&self, x: i32, y: i32, width: i32, height: i32) {
+        self.gl.viewport(x, y, width, height);
+    }
+
+    fn set_clear_color(&self, color: [f32; 4]) {
+        self.gl.clear_color(color[0], color[1], color[2], color[3]);
+    }
+
+    fn clear(&self) {
+        self.gl.clear(GL::COLOR_BUFFER_BIT | GL::DEPTH_BUFFER_BIT);
+    }
+
+    fn set_depth_test(&self, enabled: bool) {
+        if enabled {
+            self.gl.enable(GL::DEPTH_TEST);
+        } else {
+            self.gl.disable(GL::DEPTH_TEST);
+        }
+    }
+
+    fn set_cull_back_faces(&self, enabled: bool) {
+        if enabled {
+            self.gl.enable(GL::CULL_FACE);
+            self.gl.cull_face(GL::BACK);
+        } else {
+            self.gl.disable(GL::CULL_FACE);
+        }
+    }
+}
+
+/// Target of one face of a cube map
+fn face_target(face: CubeFace) -> u32 {
+    GL::TEXTURE_CUBE_MAP_POSITIVE_X + face.index() as u32
+}
+
+/// Upload RGBA pixels to `target` of the bound texture
+fn upload_pixels(gl: &GL, target: u32, width: u32, height: u32, pixels: &[u8]) -> Result<(), String> {
+    // This is the worst method signature I have ever seen
+    gl.tex_image_2d_with_i32_and_i32_and_i32_and_format_and_type_and_opt_u8_array(
+        target,
+        0,
+        GL::RGBA as i32,
+        width as i32,
+        height as i32,
+        0,
+        GL::RGBA,
+        GL::UNSIGNED_BYTE,
+        Some(pixels),
+    ).map_err(|e| e.as_string().unwrap_or_else(|| String::from("Unable to upload texture")))
+}
+
+/// Upload a loaded image to `target` of the bound texture
+fn upload_image(gl: &GL, target: u32, img: &HtmlImageElement) -> Result<(), String> {
+    gl.tex_image_2d_with_u32_and_u32_and_html_image_element(
+        target,
+        0,
+        GL::RGBA as i32,
+        GL::RGBA,
+        GL::UNSIGNED_BYTE,
+        img
+    ).map_err(|e| e.as_string().unwrap_or_else(|| String::from("Unable to upload image")))
+}
+
+/// Start loading the image at `url` into `texture`
+///
+/// `on_load` is called with the image once it loaded. The state of the load
+/// becomes `Loaded` if it succeeds and `Failed` if it or the request fails.
+fn load_image(
+    texture: TextureId,
+    url: &str,
+    on_load: impl FnOnce(&HtmlImageElement) -> Result<(), String> + 'static
+) -> Result<ImageLoad, String> {
+    let image = HtmlImageElement::new().map_err(|_| String::from("Unable to create image element"))?;
+    image.set_cross_origin(Some(""));
+
+    let state = Rc::new(RefCell::new(LoadState::Pending));
+
+    let on_load = {
+        let (image, state) = (image.clone(), state.clone());
+        let url = String::from(url);
+        let mut on_load = Some(on_load);
+
+        Closure::wrap(Box::new(move || {
+            if let Some(on_load) = on_load.take() {
+                *state.borrow_mut() = match on_load(&image) {
+                    Ok(()) => LoadState::Loaded,
+                    Err(error) => LoadState::Failed(format!("Could not use image {}: {}", url, error))
+                };
+            }
+        }) as Box<dyn FnMut()>)
+    };
+
+    let on_error = {
+        let state = state.clone();
+        let error = format!("Could not load image {}", url);
+
+        Closure::wrap(Box::new(move || {
+            *state.borrow_mut() = LoadState::Failed(error.clone());
+        }) as Box<dyn FnMut()>)
+    };
+
+    image.set_onload(Some(on_load.as_ref().unchecked_ref()));
+    image.set_onerror(Some(on_error.as_ref().unchecked_ref()));
+    image.set_src(url);
+
+    Ok(ImageLoad { texture, image, state, _on_load: on_load, _on_error: on_error })
+}
+
+/// Read the RGBA pixels of a loaded image by drawing it on a canvas
+fn image_pixels(img: &HtmlImageElement) -> Result<Vec<u8>, JsValue> {
+    let document = web_sys::window()
+        .and_then(|window| window.document())
+        .ok_or_else(|| JsValue::from_str("No document to draw the image in"))?;
+
+    let canvas = document.create_element("canvas")?.dyn_into::<HtmlCanvasElement>()?;
+    canvas.set_width(img.natural_width());
+    canvas.set_height(img.natural_height());
+
+    let context = canvas.get_context("2d")?
+        .ok_or_else(|| JsValue::from_str("Unable to get a 2d context"))?
+        .dyn_into::<CanvasRenderingContext2d>()?;
+
+    context.draw_image_with_html_image_element(img, 0.0, 0.0)?;
+    let data = context.get_image_data(0.0, 0.0, img.natural_width() as f64, img.natural_height() as f64)?;
+
+    Ok(data.data().0)
+}
+
+/// Compile a shader from a source string
+/// 
+/// Takes in a reference to the `WebGl2RenderingContext` and a source string
+/// to compile a shader of the specified type (typically either 
+/// `FRAGMENT_SHADER` or `VERT_SHADER`)
+/// 
+/// Returns a `WebGlShader` if compilation was successful, or a string 
+/// containing an error if the compilation failed.
+/// 
+/// # Examples
+/// ```
+/// let shader_source: str& = r##"
+///     #version 330 es
+/// 
+///     precision mediump float;
+///     out vec4 outColor;
+/// 
+///     void main() {
+///         outColor = vec4(1.0, 1.0, 1.0, 1.0);
+///     }
+/// "##;
+/// 
+/// let frag_shader = compile_shader(
+///     &gl,
+///     WebGl2RenderingContext::FRAGMENT_SHADER,
+///     shader_source
+/// )?;
+/// ```
+fn compile_shader(
+    gl: &GL,
+    shader_type: u32,
+    source: &str
+) -> Result<WebGlShader, String> {
+    // Return a shader
+
+    let shader = gl
+        .create_shader(shader_type)
+        .ok_or_else(|| String::from("Unable to create shader object"))?;
+    gl.shader_source(&shader, source);
+    gl.compile_shader(&shader);
+
+    if gl
+        .get_shader_parameter(&shader, GL::COMPILE_STATUS)
+        .as_bool()
+        .unwrap_or(false)
+    {
+        Ok(shader)
+    } else {
+        Err(gl
+            .get_shader_info_log(&shader)
+            .unwrap_or_else(|| String::from("Unknown error creating shader"))
+        )
+    }
+
+}
+
+/// Creates a new shader program and links an existing vertex and 
+/// fragment shader.
+/// 
+/// Takes in a reference to a `WebGl2RenderingContext` and compiled
+/// vertex and fragment shaders. Returns a result containing either 
+/// a `WebGlProgram` if the linking was successful or a `String` 
+/// containing the error message if the program failed to link
+/// successfully.
+/// 
+/// # Examples
+/// 
+/// ```
+/// let frag_shader_source = r##"
+///     // fragment shader source
+/// "##;
+/// 
+/// let vert_shader_source = r##"
+///     // vertex shader source
+/// "##;
+/// 
+/// let frag_shader = compile_shader(
+///     &gl,
+///     WebGl2RenderingContext::FRAGMENT_SHADER,
+///     frag_shader_source
+/// )?;
+/// 
+/// let vert_shader = compile_shader(
+///     &gl,
+///     WebGl2RenderingContext::VERTEX_SHADER,
+///     vert_shader_source
+/// )?;
+/// 
+/// let program = link_program(
+///     &gl
+///     &vert_shader,
+///     &frag_shader
+/// )?;
+/// ```
+fn link_program(
+    gl: &GL,
+    vert_shader: &WebGlShader,
+    frag_shader: &WebGlShader
+) -> Result<WebGlProgram, String> {
+    
+    let program = gl
+        .create_program()
+        .ok_or_else(|| String::from("Unable to create program ojbect"))?;
+    
+    gl.attach_shader(&program, vert_shader);
+    gl.attach_shader(&program, frag_shader);
+    gl.link_program(&program);
+
+    if gl
+        .get_program_parameter(&program, GL::LINK_STATUS)
+        .as_bool()
+        .unwrap_or(false)
+    {
+        Ok(program)
+    } else {
+        Err(gl
+            .get_program_info_log(&program)
+            .unwrap_or_else(|| String::from("Unknown error creating program object"))
+        )
+    }
+}
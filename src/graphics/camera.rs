@@ -1,7 +1,9 @@
-use crate::math::{vec3::Vector3, mat4::Matrix4};
+use crate::math::{vec2::Vector2, vec3::Vector3, mat4::Matrix4, quaternion::Quaternion, ray::Ray};
 
 pub struct Camera {
     pub position: Vector3,
+    /// Orientation of the camera, which looks down its -z axis
+    pub rotation: Quaternion,
     pub fov: f32,
     pub near: f32,
     pub far: f32,
@@ -13,6 +15,7 @@ impl Camera {
     pub fn new() -> Self {
         Self { 
             position: Vector3::zero(), 
+            rotation: Quaternion::identity(),
             fov: 0.25, 
             near: 0.01,
             far: 1000.0,
@@ -20,4 +23,425 @@ impl Camera {
             proj_matrix: Matrix4::identity()
         }
     }
-}
\ No newline at end of file
+
+    /// Configure the projection matrix for a new aspect ratio
+    pub fn update_projection_matrix(&mut self, aspect_ratio: f32) {
+        self.proj_matrix = Matrix4::perspective(
+            self.fov, aspect_ratio, self.near, self.far);
+    }
+
+    /// Configure the view matrix from the camera position and rotation
+    pub fn update_view_matrix(&mut self) {
+        self.view_matrix = Matrix4::view(
+            self.position,
+            self.rotation * Vector3::up(),
+            self.rotation * Vector3::forward()
+        );
+    }
+
+    /// Ray from the camera through a point on a canvas
+    /// 
+    /// `pos` is in pixels from the top left of a canvas of `width` by
+    /// `height` pixels. Returns `None` for an empty canvas.
+    pub fn canvas_ray(&self, pos: Vector2, width: f32, height: f32) -> Option<Ray> {
+        if width <= 0.0 || height <= 0.0 {
+            return None;
+        }
+
+        self.screen_ray(Vector2::new(
+            2.0 * pos[0] / width - 1.0,
+            1.0 - 2.0 * pos[1] / height
+        ))
+    }
+
+    /// Ray from the camera through a point on the screen
+    /// 
+    /// `ndc` is in normalized device coordinates, where `(-1, -1)` is the
+    /// bottom left and `(1, 1)` the top right of the canvas. Unprojects the
+    /// point on the near and far plane with the inverse of the projection
+    /// and view matrices. Returns `None` if they can not be inverted.
+    pub fn screen_ray(&self, ndc: Vector2) -> Option<Ray> {
+        let inverse = (self.view_matrix * self.proj_matrix).inverse()?;
+
+        let near = inverse.transform_point(Vector3::new(ndc[0], ndc[1], -1.0));
+        let far = inverse.transform_point(Vector3::new(ndc[0], ndc[1], 1.0));
+
+        Some(Ray::new(near, far - near))
+    }
+}
+
+/// Camera controller orbiting a target point
+///
+/// The camera sits `distance` away from the target in the direction given
+/// by `yaw` and `pitch`. Around the globe these are the longitude and
+/// latitude, in radians, of the point the camera is above. Drags keep
+/// rotating with their last velocity after the mouse is released, slowing
+/// down over time.
+pub struct OrbitCamera {
+    pub target: Vector3,
+    yaw: f32,
+    pitch: f32,
+    distance: f32,
+    /// Velocity of yaw and pitch in radians per second
+    velocity: Vector2,
+    flight: Option<Flight>,
+    /// Yaw, pitch and distance the camera was created with
+    home: (f32, f32, f32),
+    pub min_distance: f32,
+    pub max_distance: f32,
+    /// Largest pitch in either direction, keeps the camera off the poles
+    pub max_pitch: f32,
+    /// Radians rotated per pixel dragged at the largest distance
+    pub sensitivity: f32,
+    /// Relative change of the distance per pixel scrolled
+    pub zoom_speed: f32,
+    /// Rate at which the velocity decays after a drag, per second
+    pub damping: f32,
+    /// Radians rotated per second by `turn` at the largest distance
+    pub turn_speed: f32,
+    /// Relative change of the distance per second by `zoom_steady`
+    pub zoom_rate: f32
+}
+
+/// An animated move of an `OrbitCamera`
+struct Flight {
+    from: (f32, f32, f32),
+    to: (f32, f32, f32),
+    elapsed: f32,
+    duration: f32
+}
+
+#[allow(dead_code)]
+impl OrbitCamera {
+    pub fn new(yaw: f32, pitch: f32, distance: f32) -> Self {
+        let mut orbit = Self {
+            target: Vector3::zero(),
+            yaw,
+            pitch,
+            distance,
+            velocity: Vector2::new(0.0, 0.0),
+            flight: None,
+            home: (yaw, pitch, distance),
+            min_distance: 1.2,
+            max_distance: 30.0,
+            max_pitch: 85.0_f32.to_radians(),
+            sensitivity: 0.01,
+            zoom_speed: 0.001,
+            damping: 5.0,
+            turn_speed: 2.0,
+            zoom_rate: 1.5
+        };
+
+        orbit.clamp();
+        orbit
+    }
+
+    pub fn yaw(&self) -> f32 {
+        self.yaw
+    }
+
+    pub fn pitch(&self) -> f32 {
+        self.pitch
+    }
+
+    pub fn distance(&self) -> f32 {
+        self.distance
+    }
+
+    /// Returns `true` while a `fly_to` animation is running
+    pub fn is_flying(&self) -> bool {
+        self.flight.is_some()
+    }
+
+    /// Rotate by a mouse drag `delta` in pixels over `delta_time` seconds
+    /// 
+    /// Should be called every frame while the mouse is held down, even if
+    /// it did not move. `delta` is the previous minus the current mouse
+    /// position. The globe follows the mouse, and rotates slower the closer
+    /// the camera is to it. Moving the mouse cancels any running `fly_to`.
+    pub fn drag(&mut self, delta: Vector2, delta_time: f32) {
+        let speed = self.sensitivity * self.distance / self.max_distance;
+        let rotation = Vector2::new(-delta[0] * speed, -delta[1] * speed);
+
+        if rotation[0] != 0.0 || rotation[1] != 0.0 {
+            self.flight = None;
+            self.yaw += rotation[0];
+            self.pitch += rotation[1];
+        }
+
+        if delta_time > 0.0 {
+            self.velocity = rotation * (1.0 / delta_time);
+        }
+
+        self.clamp();
+    }
+
+    /// Move towards or away from the target by a scroll `amount` in pixels
+    /// 
+    /// Scrolling down moves away. Cancels any running `fly_to`.
+    pub fn zoom(&mut self, amount: f32) {
+        self.zoom_by((amount * self.zoom_speed).exp());
+    }
+
+    /// Multiply the distance to the target by `factor`
+    /// 
+    /// Factors above 1.0 move away. Cancels any running `fly_to`.
+    pub fn zoom_by(&mut self, factor: f32) {
+        if factor == 1.0 {
+            return;
+        }
+
+        self.flight = None;
+        self.distance *= factor;
+        self.clamp();
+    }
+
+    /// Rotate steadily in a screen `direction` for `delta_time` seconds
+    /// 
+    /// Rotates the same way as dragging the mouse in `direction`, where +x
+    /// is right and +y is down, e.g. while a key is held. Stops inertia and
+    /// cancels any running `fly_to` if `direction` is not zero.
+    pub fn turn(&mut self, direction: Vector2, delta_time: f32) {
+        if direction[0] == 0.0 && direction[1] == 0.0 {
+            return;
+        }
+
+        let speed = self.turn_speed * delta_time * self.distance / self.max_distance;
+
+        self.flight = None;
+        self.velocity = Vector2::new(0.0, 0.0);
+        self.yaw += direction[0] * speed;
+        self.pitch += direction[1] * speed;
+        self.clamp();
+    }
+
+    /// Zoom steadily for `delta_time` seconds, out for a positive `direction`
+    pub fn zoom_steady(&mut self, direction: f32, delta_time: f32) {
+        self.zoom_by((direction * self.zoom_rate * delta_time).exp());
+    }
+
+    /// Animate the camera back to where it was created over `duration` seconds
+    pub fn reset(&mut self, duration: f32) {
+        let (yaw, pitch, distance) = self.home;
+        self.fly_to(pitch.to_degrees(), yaw.to_degrees(), distance, duration);
+    }
+
+    /// Animate the camera to look down on a latitude and longitude
+    /// 
+    /// Angles are in degrees, `distance` is measured from the target and
+    /// `duration` is in seconds. Turns the short way around the globe.
+    pub fn fly_to(&mut self, lat: f32, lon: f32, distance: f32, duration: f32) {
+        let lon = lon.to_radians();
+        let pitch = lat.to_radians().clamp(-self.max_pitch, self.max_pitch);
+        let distance = distance.clamp(self.min_distance, self.max_distance);
+
+        // Wrap the current yaw so the flight never turns more than half way round
+        let tau = std::f32::consts::TAU;
+        let yaw = lon + (self.yaw - lon + std::f32::consts::PI).rem_euclid(tau) - std::f32::consts::PI;
+
+        self.velocity = Vector2::new(0.0, 0.0);
+        self.flight = Some(Flight {
+            from: (yaw, self.pitch, self.distance),
+            to: (lon, pitch, distance),
+            elapsed: 0.0,
+            duration: duration.max(0.0)
+        });
+
+        self.yaw = yaw;
+    }
+
+    /// Advance inertia and any running `fly_to` by `delta_time` seconds
+    /// 
+    /// Inertia is paused while `dragging`.
+    pub fn update(&mut self, delta_time: f32, dragging: bool) {
+        if let Some(flight) = &mut self.flight {
+            flight.elapsed += delta_time;
+
+            let t = if flight.duration > 0.0 { (flight.elapsed / flight.duration).min(1.0) } else { 1.0 };
+            let t = t * t * (3.0 - 2.0 * t);
+            let lerp = |a: f32, b: f32| a + (b - a) * t;
+
+            self.yaw = lerp(flight.from.0, flight.to.0);
+            self.pitch = lerp(flight.from.1, flight.to.1);
+            self.distance = lerp(flight.from.2, flight.to.2);
+
+            if flight.elapsed >= flight.duration {
+                self.flight = None;
+            }
+
+            self.clamp();
+            return;
+        }
+
+        if dragging {
+            return;
+        }
+
+        self.yaw += self.velocity[0] * delta_time;
+        self.pitch += self.velocity[1] * delta_time;
+        self.velocity *= (-self.damping * delta_time).exp();
+        self.clamp();
+    }
+
+    /// Position of the camera
+    pub fn position(&self) -> Vector3 {
+        let direction = Vector3::new(
+            -self.pitch.cos() * self.yaw.sin(),
+            self.pitch.sin(),
+            -self.pitch.cos() * self.yaw.cos()
+        );
+
+        self.target + direction * self.distance
+    }
+
+    /// Move `camera` into position, looking at the target with north up
+    pub fn apply(&self, camera: &mut Camera) {
+        let position = self.position();
+
+        camera.position = position;
+        camera.rotation = Quaternion::look_rotation(position - self.target, Vector3::up());
+        camera.update_view_matrix();
+    }
+
+    fn clamp(&mut self) {
+        self.distance = self.distance.clamp(self.min_distance, self.max_distance);
+        self.yaw = self.yaw.rem_euclid(std::f32::consts::TAU);
+
+        if self.pitch.abs() > self.max_pitch {
+            self.pitch = self.pitch.clamp(-self.max_pitch, self.max_pitch);
+            self.velocity = Vector2::new(self.velocity[0], 0.0);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_screen_ray_through_center() {
+        let mut camera = Camera::new();
+        camera.position = Vector3::new(0.0, 0.0, 15.0);
+        camera.update_projection_matrix(1.5);
+        camera.update_view_matrix();
+
+        let ray = camera.screen_ray(Vector2::new(0.0, 0.0)).unwrap();
+        assert!((ray.origin - Vector3::new(0.0, 0.0, 15.0 - camera.near)).magnitude() < 1e-3);
+        assert!((ray.direction - Vector3::new(0.0, 0.0, -1.0)).magnitude() < 1e-5);
+
+        // Top right of the screen is up and to the right of the camera
+        let ray = camera.screen_ray(Vector2::new(1.0, 1.0)).unwrap();
+        assert!(ray.direction.data[0] > 0.0);
+        assert!(ray.direction.data[1] > 0.0);
+    }
+
+    #[test]
+    fn test_orbit_looks_at_target() {
+        let mut camera = Camera::new();
+        camera.update_projection_matrix(1.0);
+
+        let mut orbit = OrbitCamera::new(1.0, 0.5, 10.0);
+        orbit.target = Vector3::new(1.0, 2.0, 3.0);
+        orbit.apply(&mut camera);
+
+        assert!(((camera.position - orbit.target).magnitude() - 10.0).abs() < 1e-4);
+
+        let ray = camera.screen_ray(Vector2::new(0.0, 0.0)).unwrap();
+        let to_target = (orbit.target - camera.position).normalize();
+        assert!((ray.direction - to_target).magnitude() < 1e-4);
+
+        // North stays up on the screen
+        let up = camera.view_matrix.transform_vector(Vector3::up());
+        assert!(up.data[1] > 0.0 && up.data[0].abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_orbit_position_matches_geo() {
+        use crate::math::geo::{EarthModel, GeoCoord};
+
+        let orbit = OrbitCamera::new(0.7, -0.3, 4.0);
+        let geo = GeoCoord::from_globe(orbit.position(), &EarthModel::default());
+
+        assert!((geo.lat - (-0.3_f32).to_degrees()).abs() < 1e-3);
+        assert!((geo.lon - 0.7_f32.to_degrees()).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_orbit_turn_and_reset() {
+        let mut orbit = OrbitCamera::new(1.0, 0.2, 30.0);
+
+        // Like dragging right and down
+        orbit.turn(Vector2::new(1.0, 1.0), 0.5);
+        assert!((orbit.yaw() - 2.0).abs() < 1e-5);
+        assert!((orbit.pitch() - 1.2).abs() < 1e-5);
+
+        orbit.zoom_steady(-1.0, 1.0);
+        assert!((orbit.distance() - 30.0 * (-1.5_f32).exp()).abs() < 1e-4);
+
+        orbit.reset(1.0);
+        orbit.update(1.0, false);
+        assert!((orbit.yaw() - 1.0).abs() < 1e-5);
+        assert!((orbit.pitch() - 0.2).abs() < 1e-5);
+        assert!((orbit.distance() - 30.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_orbit_clamps() {
+        let mut orbit = OrbitCamera::new(0.0, 0.0, 10.0);
+
+        orbit.zoom(-1e6);
+        assert_eq!(orbit.distance(), orbit.min_distance);
+        orbit.zoom(1e6);
+        assert_eq!(orbit.distance(), orbit.max_distance);
+
+        orbit.drag(Vector2::new(0.0, -1e6), 0.016);
+        assert_eq!(orbit.pitch(), orbit.max_pitch);
+    }
+
+    #[test]
+    fn test_orbit_inertia() {
+        let mut orbit = OrbitCamera::new(0.0, 0.0, 10.0);
+
+        orbit.drag(Vector2::new(-10.0, 0.0), 0.1);
+        let yaw = orbit.yaw();
+        assert!(yaw > 0.0);
+
+        // Keeps turning while held still, but slower over time
+        orbit.update(0.1, false);
+        let first = orbit.yaw() - yaw;
+        orbit.update(0.1, false);
+        let second = orbit.yaw() - yaw - first;
+
+        assert!(first > 0.0);
+        assert!(second > 0.0 && second < first);
+
+        // Holding the mouse down stops the inertia
+        let held = orbit.yaw();
+        orbit.update(0.1, true);
+        assert_eq!(orbit.yaw(), held);
+    }
+
+    #[test]
+    fn test_fly_to() {
+        let mut orbit = OrbitCamera::new(350.0_f32.to_radians(), 0.0, 10.0);
+
+        orbit.fly_to(45.0, 10.0, 5.0, 2.0);
+        orbit.update(1.0, false);
+        assert!(orbit.is_flying());
+
+        // Crosses 0 instead of turning the long way around
+        let yaw = orbit.yaw().to_degrees();
+        assert!(!(10.0..=350.0).contains(&yaw), "{}", yaw);
+
+        orbit.update(1.5, false);
+        assert!(!orbit.is_flying());
+        assert!((orbit.pitch() - 45.0_f32.to_radians()).abs() < 1e-5);
+        assert!((orbit.yaw() - 10.0_f32.to_radians()).abs() < 1e-5);
+        assert_eq!(orbit.distance(), 5.0);
+
+        // Dragging cancels the flight
+        orbit.fly_to(0.0, 0.0, 10.0, 1.0);
+        orbit.drag(Vector2::new(1.0, 0.0), 0.016);
+        assert!(!orbit.is_flying());
+    }
+}
@@ -0,0 +1,28 @@
+/// Fragment shader sampling a cube map by the normal in object space
+///
+/// Shaded like `simple_unlit_shaded`. The cube map has longitude 0 at +z
+/// and 90E at +x, see `TextureCube`.
+pub const SHADER: &str = r##"#version 300 es
+
+precision mediump float;
+
+uniform samplerCube tex;
+
+out vec4 outColor;
+
+in vec3 normal;
+in vec3 local_normal;
+
+vec3 lightDir = normalize(vec3(1.0, 1.0, 1.0)); // TODO: Make this a uniform
+
+void main() {
+    // The globe has longitude 0 at -z and 90E at -x
+    vec4 col = texture(tex, vec3(-local_normal.x, local_normal.y, -local_normal.z));
+    float fres = 1.0 - dot(normal, vec3(0.0, 0.0, 1.0)); // TODO: make relative to the inverse of the camera forward
+    float atmosphere = fres * fres * fres * fres * 1.85;
+    float shadow = (dot(normal, lightDir) + .25) * 0.85;
+    float intensity = min(shadow + atmosphere, 1.0);
+
+    outColor = vec4(col.xyz * intensity, 1.0);
+}
+"##;
@@ -0,0 +1,50 @@
+/// Fragment shader for the globe with an equirectangular image
+///
+/// Computes the uv coords from the normal in object space. The image spans
+/// longitude -180 to 180 from left to right and latitude 90 to -90 from top
+/// to bottom. The texture has to clamp v to its edges, so the poles do not
+/// blend into each other. Shaded like `simple_unlit_shaded`.
+pub const SHADER: &str = r##"#version 300 es
+
+precision highp float;
+
+uniform sampler2D tex;
+
+out vec4 outColor;
+
+in vec3 normal;
+in vec3 local_normal;
+
+const float PI = 3.14159265359;
+
+vec3 lightDir = normalize(vec3(1.0, 1.0, 1.0)); // TODO: Make this a uniform
+
+void main() {
+    vec3 n = normalize(local_normal);
+
+    // Longitude 0 is at -z and 90E at -x, like `GeoCoord::to_globe`
+    float lon = atan(-n.x, -n.z);
+    float lat = asin(clamp(n.y, -1.0, 1.0));
+    vec2 uv = vec2(lon / (2.0 * PI) + 0.5, 0.5 - lat / PI);
+
+    // u jumps from 1 to 0 at the antimeridian, which would make the
+    // derivatives pick the smallest mipmap along the seam. Take them from
+    // whichever of u and u moved by half a turn is continuous here.
+    float seam_u = fract(uv.x + 0.5);
+    bool at_seam = fwidth(uv.x) > fwidth(seam_u);
+    vec2 du = at_seam ? vec2(dFdx(seam_u), dFdy(seam_u)) : vec2(dFdx(uv.x), dFdy(uv.x));
+
+    // Columns crowd together towards the poles, where a pixel covers many
+    // of them but only a few rows
+    du *= cos(lat);
+    vec2 dv = vec2(dFdx(uv.y), dFdy(uv.y));
+
+    vec4 col = textureGrad(tex, uv, vec2(du.x, dv.x), vec2(du.y, dv.y));
+    float fres = 1.0 - dot(normal, vec3(0.0, 0.0, 1.0)); // TODO: make relative to the inverse of the camera forward
+    float atmosphere = fres * fres * fres * fres * 1.85;
+    float shadow = (dot(normal, lightDir) + .25) * 0.85;
+    float intensity = min(shadow + atmosphere, 1.0);
+
+    outColor = vec4(col.xyz * intensity, 1.0);
+}
+"##;
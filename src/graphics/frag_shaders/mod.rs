@@ -1,3 +1,5 @@
 pub mod simple_unlit;
 pub mod output_test;
-pub mod simple_unlit_shaded;
\ No newline at end of file
+pub mod simple_unlit_shaded;
+pub mod cube_map;
+pub mod equirectangular;
\ No newline at end of file
@@ -3,6 +3,8 @@ pub const SHADER: &str = r##"#version 300 es
 
 precision mediump float;
 
+uniform vec4 color;
+
 out vec4 outColor;
 
 in vec4 position;
@@ -10,7 +12,7 @@ in vec3 normal;
 in vec2 texture_coords;
 
 void main() {
-    outColor = vec4(0.9, 0.15, 0.05, 1.0);
+    outColor = color;
     // outColor = vec4(1.0, 1.0, 1.0, 1.0);
 }
 "##;
\ No newline at end of file
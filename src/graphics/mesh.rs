@@ -1,4 +1,6 @@
 // Hamilton Rice
+use std::collections::HashMap;
+
 use crate::math::vec3::Vector3;
 use crate::math::vec2::Vector2;
 
@@ -24,11 +26,28 @@ impl Vertex {
     }
 }
 
+/// Faces of the cube a quad sphere is projected from, as the direction of
+/// their center followed by the directions of their u and v axes
+///
+/// On the globe the face at -z is centered on longitude 0, the next three
+/// follow it eastwards and the last two are the poles.
+pub const CUBE_FACES: [[[f32; 3]; 3]; 6] = [
+    [[0.0, 0.0, -1.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]],
+    [[1.0, 0.0, 0.0], [0.0, 0.0, 1.0], [0.0, 1.0, 0.0]],
+    [[0.0, 0.0, 1.0], [-1.0, 0.0, 0.0], [0.0, 1.0, 0.0]],
+    [[-1.0, 0.0, 0.0], [0.0, 0.0, -1.0], [0.0, 1.0, 0.0]],
+    [[0.0, 1.0, 0.0], [1.0, 0.0, 0.0], [0.0, 0.0, 1.0]],
+    [[0.0, -1.0, 0.0], [1.0, 0.0, 0.0], [0.0, 0.0, -1.0]],
+];
+
 pub struct Mesh {
     pub verts: Vec<f32>,
     pub inds: Vec<u32>,
     pub index_size: usize,
-
+    /// Index into `CUBE_FACES` of the face each vertex belongs to, only
+    /// filled in by `quad_sphere`
+    #[allow(dead_code)]
+    pub faces: Vec<u8>,
 }
 
 #[allow(dead_code)]
@@ -39,7 +58,8 @@ impl Mesh {
         Self {
             verts: Vec::new(),
             inds: Vec::new(),
-            index_size: 0
+            index_size: 0,
+            faces: Vec::new()
         }
     }
     
@@ -85,7 +105,7 @@ impl Mesh {
 
         ];
         
-        Self { index_size: indices.len(), verts: verts, inds: indices, }
+        Self { index_size: indices.len(), verts, inds: indices, faces: Vec::new() }
     }
 
     pub fn texture_quad() -> Self {
@@ -102,7 +122,7 @@ impl Mesh {
             3, 2, 0,
         ];
 
-        Self { index_size: indices.len(), verts: verts, inds: indices }
+        Self { index_size: indices.len(), verts, inds: indices, faces: Vec::new() }
     }
 
     pub fn normal_cube_unit_sphere_face(resolution: u32) -> Self {
@@ -151,11 +171,81 @@ impl Mesh {
         Self { 
             verts: vec_vertex_to_vec_f32(points),
             index_size: indices.len(), 
-            inds: indices 
+            inds: indices,
+            faces: Vec::new()
         }
         // todo!()
     }
 
+    /// Unit sphere made of the six faces of a cube, each a grid of
+    /// `resolution` by `resolution` vertices pushed out onto the sphere
+    ///
+    /// Vertices on the edges and corners shared by faces are welded, so the
+    /// sphere is one closed mesh without seams. Every vertex is tagged in
+    /// `faces` with the first face of `CUBE_FACES` it is on, and its uv
+    /// coords are its position on that face. The uv coords are wrong along
+    /// the seams of the other faces, so the mesh is only meant for materials
+    /// sampling by the normal, like `UnlitCubeMap3D` and
+    /// `UnlitEquirectangular3D`, not for 2D textures read by uv.
+    pub fn quad_sphere(resolution: u32) -> Self {
+        assert!(resolution >= 2, "A quad sphere needs at least 2 vertices per edge");
+
+        let steps = resolution - 1;
+        let mut points = Vec::new();
+        let mut faces = Vec::new();
+        let mut indices = Vec::with_capacity((steps * steps * 36) as usize);
+
+        // Vertices by their integer coordinates on the cube, to find shared ones
+        let mut welded: HashMap<[i32; 3], u32> = HashMap::new();
+
+        for (face, [center, right, up]) in CUBE_FACES.iter().enumerate() {
+            let mut grid = Vec::with_capacity((resolution * resolution) as usize);
+
+            for y in 0..resolution {
+                for x in 0..resolution {
+                    let lattice = [0, 1, 2].map(|i| {
+                        center[i] as i32 * steps as i32 +
+                        right[i] as i32 * (2 * x as i32 - steps as i32) +
+                        up[i] as i32 * (2 * y as i32 - steps as i32)
+                    });
+
+                    let index = *welded.entry(lattice).or_insert_with(|| {
+                        let percent = Vector2::new(x as f32, y as f32) / steps as f32;
+                        let point = Vector3::new(
+                            lattice[0] as f32,
+                            lattice[1] as f32,
+                            lattice[2] as f32
+                        ).normalize();
+
+                        points.push(Vertex::new(point, point, percent));
+                        faces.push(face as u8);
+                        points.len() as u32 - 1
+                    });
+
+                    grid.push(index);
+                }
+            }
+
+            for y in 0..steps {
+                for x in 0..steps {
+                    let i = (x + resolution * y) as usize;
+                    let (next, above, diagonal) = (i + 1, i + resolution as usize, i + resolution as usize + 1);
+
+                    // Counter clockwise seen from outside the sphere
+                    indices.extend_from_slice(&[grid[i], grid[above], grid[diagonal]]);
+                    indices.extend_from_slice(&[grid[i], grid[diagonal], grid[next]]);
+                }
+            }
+        }
+
+        Self {
+            verts: vec_vertex_to_vec_f32(points),
+            index_size: indices.len(),
+            inds: indices,
+            faces
+        }
+    }
+
     pub fn fireball() -> Self {
         let sqrt_3 = (3.0 as f32).sqrt();
         let verts = vec![
@@ -199,7 +289,7 @@ impl Mesh {
 
         ];
         
-        Self { index_size: indices.len(), verts: verts, inds: indices, }
+        Self { index_size: indices.len(), verts, inds: indices, faces: Vec::new() }
     }
 
 }
@@ -219,4 +309,55 @@ fn vec_vertex_to_vec_f32(vert_vec: Vec<Vertex>) -> Vec<f32> {
     }
 
     f32_vec
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn position(mesh: &Mesh, index: u32) -> Vector3 {
+        let start = index as usize * 8;
+        Vector3::new(mesh.verts[start], mesh.verts[start + 1], mesh.verts[start + 2])
+    }
+
+    #[test]
+    fn test_quad_sphere_welds_edges() {
+        let mesh = Mesh::quad_sphere(4);
+
+        // 2 by 2 inner vertices per face, 2 per edge and the 8 corners
+        assert_eq!(mesh.verts.len() / 8, 6 * 4 + 12 * 2 + 8);
+        assert_eq!(mesh.faces.len(), mesh.verts.len() / 8);
+        assert_eq!(mesh.index_size, 6 * 3 * 3 * 6);
+
+        // Closed: every edge is shared by exactly two triangles, in opposite directions
+        let mut edges = HashMap::new();
+        for triangle in mesh.inds.chunks_exact(3) {
+            for i in 0..3 {
+                *edges.entry((triangle[i], triangle[(i + 1) % 3])).or_insert(0) += 1;
+            }
+        }
+
+        assert!(edges.iter().all(|(&(a, b), &count)| count == 1 && edges.get(&(b, a)) == Some(&1)));
+    }
+
+    #[test]
+    fn test_quad_sphere_faces_outwards() {
+        let mesh = Mesh::quad_sphere(5);
+
+        for triangle in mesh.inds.chunks_exact(3) {
+            let [a, b, c] = [0, 1, 2].map(|i| position(&mesh, triangle[i]));
+            let normal = Vector3::cross(b - a, c - a);
+
+            assert!(Vector3::dot(normal, a + b + c) > 0.0);
+        }
+
+        for index in 0..(mesh.verts.len() / 8) as u32 {
+            assert!((position(&mesh, index).magnitude() - 1.0).abs() < 1e-5);
+        }
+
+        // The first face is never welded, its center is at -z, longitude 0
+        assert_eq!(mesh.faces[12], 0);
+        assert!(position(&mesh, 12)[2] < -0.999_f32);
+        assert_eq!(mesh.faces.last(), Some(&5));
+    }
+}
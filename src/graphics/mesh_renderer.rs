@@ -1,114 +1,146 @@
-use web_sys::{WebGlBuffer, WebGl2RenderingContext as GL, WebGlVertexArrayObject};
+use crate::math::mat4::Matrix4;
+use super::backend::graphics_backend::{BufferId, GraphicsBackend, VertexArrayId, VertexAttribute};
+use super::camera::Camera;
+use crate::error::RendererError;
+
 use super::{mesh::Mesh, programs::material::Material};
 
+/// Bytes per vertex: position and normal, then uv coords
+const VERTEX_STRIDE: i32 = 32;
+
 pub struct MeshRenderer {
-    vertex_buffer: WebGlBuffer,
-    index_buffer: WebGlBuffer,
+    vertex_buffer: BufferId,
+    index_buffer: BufferId,
     // program: WebGlProgram,
     index_count: i32,
-    vao: WebGlVertexArrayObject,
+    vao: VertexArrayId,
     mat: Box<dyn Material> // TODO Enable this later
 }
 
+#[allow(dead_code)]
 impl MeshRenderer {
-    pub fn new(gl: &GL, mesh: Mesh, mat: Box<dyn Material>) -> Self {
-        
+    pub fn new(backend: &dyn GraphicsBackend, mesh: Mesh, mat: Box<dyn Material>) -> Result<Self, RendererError> {
+
         // Create buffers and attributes
-        let program = mat.get_program();
-        let vbuffer = gl.create_buffer().unwrap();
-        let ibuffer = gl.create_buffer().unwrap();
-        let position_attrib_location = gl.get_attrib_location(&program, "vertex_position");
-        let normal_attrib_location = gl.get_attrib_location(&program, "vertex_normal");
-        let uv_attrib_location = gl.get_attrib_location(&program, "vertex_uv_coords");
-        gl.bind_buffer(GL::ARRAY_BUFFER, Some(&vbuffer));
-        gl.bind_buffer(GL::ELEMENT_ARRAY_BUFFER, Some(&ibuffer));
-
-        unsafe {
-            // let vert_array_buf_view = js_sys::Float32Array::view(&mesh.verts);
-            let vertex_array_buf_view = js_sys::Float32Array::view(&mesh.verts);
-            gl.buffer_data_with_array_buffer_view(
-                GL::ARRAY_BUFFER, 
-                &vertex_array_buf_view,
-                GL::STATIC_DRAW
-            );
-
-            let index_array_buf_view = js_sys::Uint32Array::view(&mesh.inds);
-            gl.buffer_data_with_array_buffer_view(
-                GL::ELEMENT_ARRAY_BUFFER, 
-                &index_array_buf_view,
-                GL::STATIC_DRAW
-            );
-        }
-
-        let vao = gl.create_vertex_array()
-            .ok_or("Could not create vertex array").unwrap();
-        gl.bind_vertex_array(Some(&vao));
-
-        gl.vertex_attrib_pointer_with_i32(
-            position_attrib_location as u32,
-            3,
-            GL::FLOAT,
-            false,
-            32,
-            0
-        );
-
-        gl.vertex_attrib_pointer_with_i32(
-            normal_attrib_location as u32, 
-            3, 
-            GL::FLOAT, 
-            false, 
-            32, 
-            12
-        );
-        
-        gl.vertex_attrib_pointer_with_i32(
-            uv_attrib_location as u32,
-            2,
-            GL::FLOAT,
-            false,
-            32,
-            24
-        );
-
-        gl.enable_vertex_attrib_array(position_attrib_location as u32);
-        gl.enable_vertex_attrib_array(normal_attrib_location as u32);
-        gl.enable_vertex_attrib_array(uv_attrib_location as u32);
-        gl.bind_vertex_array(Some(&vao));
-
-        Self {
+        let vbuffer = backend.create_vertex_buffer(&mesh.verts).map_err(RendererError::Resource)?;
+        let ibuffer = backend.create_index_buffer(&mesh.inds).map_err(RendererError::Resource)?;
+        let vao = vertex_array(backend, vbuffer, ibuffer, mat.as_ref())?;
+
+        Ok(Self {
             index_count: mesh.index_size as i32,
             vertex_buffer: vbuffer,
             index_buffer: ibuffer,
-            vao: vao,
-            mat: mat
-        }
+            vao,
+            mat
+        })
     }
 
-    pub fn render(&self, gl: &GL) {
+    /// Draw the mesh with another material, e.g. to change the texture of
+    /// the globe, keeping its buffers
+    pub fn set_material(&mut self, backend: &dyn GraphicsBackend, mat: Box<dyn Material>) -> Result<(), RendererError> {
+        // Attribute locations can differ between programs
+        let vao = vertex_array(backend, self.vertex_buffer, self.index_buffer, mat.as_ref())?;
+        backend.delete_vertex_array(std::mem::replace(&mut self.vao, vao));
+        self.mat = mat;
 
-        self.mat.use_material(gl);
+        Ok(())
+    }
 
-        gl.bind_vertex_array(Some(&self.vao));
+    pub fn material(&self) -> &dyn Material {
+        self.mat.as_ref()
+    }
 
-        // Bind the vertext buffer for the mesh
-        gl.bind_buffer(
-            GL::ARRAY_BUFFER,
-            Some(&self.vertex_buffer)
-        );
+    pub fn vertex_buffer(&self) -> BufferId {
+        self.vertex_buffer
+    }
 
-        // Bind the index buffer for the mesh
-        gl.bind_buffer(
-            GL::ELEMENT_ARRAY_BUFFER,
-            Some(&self.index_buffer)
-        );
+    pub fn index_buffer(&self) -> BufferId {
+        self.index_buffer
+    }
 
+    /// Delete the buffers and vertex array of the mesh, the renderer must
+    /// not be drawn afterwards
+    pub fn release(&self, backend: &dyn GraphicsBackend) {
+        backend.delete_vertex_array(self.vao);
+        backend.delete_buffer(self.vertex_buffer);
+        backend.delete_buffer(self.index_buffer);
+    }
 
-        gl.draw_elements_with_i32(
-            GL::TRIANGLES, 
-            self.index_count, 
-            GL::UNSIGNED_INT,
-            0
-        );
+    /// Draw the mesh with `world` as its transform
+    pub fn render(&self, backend: &dyn GraphicsBackend, camera: &Camera, world: &Matrix4) {
+
+        self.mat.use_material(backend, camera, world);
+        backend.draw_indexed(self.vao, self.index_count);
     }
-}
\ No newline at end of file
+}
+
+/// Create a vertex array reading the buffers of a mesh with the attributes
+/// the program of `mat` uses
+fn vertex_array(
+    backend: &dyn GraphicsBackend,
+    vertex_buffer: BufferId,
+    index_buffer: BufferId,
+    mat: &dyn Material
+) -> Result<VertexArrayId, RendererError> {
+    let program = mat.get_program();
+
+    // Attributes the program does not use are skipped
+    let attributes: Vec<VertexAttribute> = [
+        ("vertex_position", 3, 0),
+        ("vertex_normal", 3, 12),
+        ("vertex_uv_coords", 2, 24),
+    ].into_iter()
+        .filter_map(|(name, size, offset)| {
+            let location = backend.attrib_location(program, name)?;
+            Some(VertexAttribute { location, size, stride: VERTEX_STRIDE, offset })
+        })
+        .collect();
+
+    backend.create_vertex_array(vertex_buffer, index_buffer, &attributes)
+        .map_err(RendererError::Resource)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::backend::graphics_backend::ProgramId;
+    use crate::graphics::backend::recording::{Command, RecordingBackend};
+    use crate::graphics::programs::unlit_3d::Unlit3D;
+    use crate::graphics::shader_manager::ShaderProgramManager;
+
+    #[test]
+    fn test_set_material_deletes_old_vertex_array() {
+        let backend = RecordingBackend::new();
+        let mut programs = ShaderProgramManager::new();
+        programs.expose_program(ProgramId(1), "simple_unlit");
+
+        let mut renderer = MeshRenderer::new(&backend, Mesh::fireball(), Box::new(Unlit3D::new(&programs).unwrap())).unwrap();
+        let old = renderer.vao;
+        backend.take_commands();
+
+        renderer.set_material(&backend, Box::new(Unlit3D::new(&programs).unwrap())).unwrap();
+        let commands = backend.take_commands();
+
+        assert_ne!(renderer.vao, old);
+        assert!(commands.contains(&Command::DeleteVertexArray(old)));
+        assert!(!commands.contains(&Command::DeleteVertexArray(renderer.vao)));
+    }
+
+    #[test]
+    fn test_release_deletes_buffers_and_vertex_array() {
+        let backend = RecordingBackend::new();
+        let mut programs = ShaderProgramManager::new();
+        programs.expose_program(ProgramId(1), "simple_unlit");
+
+        let renderer = MeshRenderer::new(&backend, Mesh::fireball(), Box::new(Unlit3D::new(&programs).unwrap())).unwrap();
+        backend.take_commands();
+
+        renderer.release(&backend);
+
+        assert_eq!(backend.take_commands(), vec![
+            Command::DeleteVertexArray(renderer.vao),
+            Command::DeleteBuffer(renderer.vertex_buffer),
+            Command::DeleteBuffer(renderer.index_buffer)
+        ]);
+    }
+}
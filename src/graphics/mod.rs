@@ -4,4 +4,7 @@ pub mod frag_shaders;
 pub mod programs;
 pub mod mesh_renderer;
 pub mod shader_manager;
-pub mod camera;
\ No newline at end of file
+pub mod camera;
+pub mod backend;
+pub mod texture_cube;
+pub mod texture_manager;
@@ -1,11 +1,40 @@
-use web_sys::{WebGl2RenderingContext, WebGlProgram};
+use crate::{
+    graphics::{backend::graphics_backend::{GraphicsBackend, ProgramId, UniformValue}, camera::Camera, texture_manager::TextureHandle},
+    math::{mat3::Matrix3, mat4::Matrix4}
+};
 
 pub trait Material {
-    // use the program
-    fn use_material(&self, gl: &WebGl2RenderingContext);
+    // use the program and set all its uniforms, world is the transform of
+    // the object being drawn
+    fn use_material(&self, backend: &dyn GraphicsBackend, camera: &Camera, world: &Matrix4) {
+        backend.use_program(self.get_program());
+        self.set_transform_uniforms(backend, camera, world);
+        self.init_uniforms(backend);
+    }
 
-    // set uniform values
-    fn init_uniforms(&self, gl: &WebGl2RenderingContext);
+    // set the matrices of the standard 3D vertex shader
+    fn set_transform_uniforms(&self, backend: &dyn GraphicsBackend, camera: &Camera, world: &Matrix4) {
+        let program = self.get_program();
 
-    fn get_program(&self) -> WebGlProgram;
-}
\ No newline at end of file
+        backend.set_uniform(program, "transform_matrix", UniformValue::Mat4(world.data));
+        backend.set_uniform(program, "projection_matrix", UniformValue::Mat4(camera.proj_matrix.data));
+        backend.set_uniform(program, "view_matrix", UniformValue::Mat4(camera.view_matrix.data));
+
+        // Only used by shaders that read the normal, so it may be optimized out
+        let normal_matrix = Matrix3::normal_matrix(&(*world * camera.view_matrix)).unwrap_or_default();
+        backend.set_uniform(program, "normal_matrix", UniformValue::Mat3(normal_matrix.data));
+    }
+
+    // set the uniforms of the material itself, like its color or texture
+    fn init_uniforms(&self, backend: &dyn GraphicsBackend);
+
+    fn get_program(&self) -> ProgramId;
+
+    // set the base color, ignored by materials without one
+    fn set_color(&self, _color: [f32; 4]) {}
+
+    // the texture the material samples, if any
+    fn texture(&self) -> Option<&TextureHandle> {
+        None
+    }
+}
@@ -1,232 +1,227 @@
-use std::{rc::Rc};
-use wasm_bindgen::{JsValue, prelude::Closure, JsCast};
-use web_sys::{WebGlProgram, WebGlTexture, HtmlImageElement, WebGl2RenderingContext as GL};
+use std::cell::Cell;
 
-use crate::{js_log, graphics::shader_manager::ShaderProgramManager, app_state::{peek_mat_stack, get_projection_matrix, get_view_matrix}};
+use crate::{
+    graphics::backend::graphics_backend::{GraphicsBackend, ProgramId, SamplerDesc, UniformValue, Wrap},
+    graphics::texture_cube::TextureCube,
+    graphics::texture_manager::{TextureHandle, TextureManager},
+    graphics::shader_manager::ShaderProgramManager,
+    error::RendererError
+};
 
 use super::material::Material;
 
 pub struct Unlit3D {
-    program: WebGlProgram,
+    program: ProgramId,
+    color: Cell<[f32; 4]>,
 }
 
 impl Unlit3D {
-    pub fn new(_gl: &GL, program_manager: &ShaderProgramManager) -> Self {
-        let prgm = program_manager.get_program("simple_unlit").to_owned();
-    
-        Self {
-            program: prgm
-        }
-    }
-    
-}
+    pub fn new(program_manager: &ShaderProgramManager) -> Result<Self, RendererError> {
+        let prgm = program_manager.get_program("simple_unlit")?;
 
-impl Material for Unlit3D {
-    fn use_material(&self, gl: &GL) {
-        gl.use_program(Some(&self.program));
-        self.init_uniforms(&gl);
+        Ok(Self {
+            program: prgm,
+            color: Cell::new([0.9, 0.15, 0.05, 1.0])
+        })
     }
 
-    fn init_uniforms(&self, gl: &GL) {
-
-        // js_log("init proj");
-        let proj_mat_location = gl.get_uniform_location(
-            &self.program, 
-            "projection_matrix"
-        ).unwrap();
-
-        let view_mat_location = gl.get_uniform_location(
-            &self.program,
-            "view_matrix"
-        ).unwrap();
-
-        let transform_mat_location = gl.get_uniform_location(
-            &self.program, 
-            "transform_matrix"
-        ).unwrap();
-        
-        let proj_mat = get_projection_matrix();
-
-        let view_mat = get_view_matrix();
-
-        let world_mat = peek_mat_stack();
-
-        gl.uniform_matrix4fv_with_f32_array(
-            Some(&transform_mat_location), 
-            false, 
-            &world_mat.data
-        );
+}
 
-        gl.uniform_matrix4fv_with_f32_array(
-            Some(&proj_mat_location), 
-            false, 
-            &proj_mat.data
+impl Material for Unlit3D {
+    fn init_uniforms(&self, backend: &dyn GraphicsBackend) {
+        backend.set_uniform(
+            self.program,
+            "color",
+            UniformValue::Vec4(self.color.get())
         );
+    }
 
-        gl.uniform_matrix4fv_with_f32_array(
-            Some(&view_mat_location), 
-            false, 
-            &view_mat.data
-        );
+    fn get_program(&self) -> ProgramId {
+        self.program
     }
 
-    fn get_program(&self) -> WebGlProgram {
-        self.program.clone()
+    fn set_color(&self, color: [f32; 4]) {
+        self.color.set(color);
     }
 }
 
+#[allow(dead_code)]
 pub struct UnlitTextured3D {
-    pub program: WebGlProgram,
-    pub texture: Rc<WebGlTexture>,
+    pub program: ProgramId,
+    pub texture: TextureHandle,
 
 }
 
+#[allow(dead_code)]
 impl UnlitTextured3D {
-    pub fn new(gl: &GL, img_src: &str, program_manager: &ShaderProgramManager) -> Self {
-        let tex = load_texture(gl, img_src).unwrap();
-        let prgm = program_manager.get_program("textured_lit").to_owned();
-
-        Self {
+    pub fn new(
+        backend: &dyn GraphicsBackend,
+        textures: &mut TextureManager,
+        img_src: &str,
+        program_manager: &ShaderProgramManager
+    ) -> Result<Self, RendererError> {
+        let prgm = program_manager.get_program("textured_lit")?;
+        let tex = textures.load(backend, img_src, SamplerDesc::default())?;
+
+        Ok(Self {
             program: prgm,
             texture: tex,
-        }
+        })
     }
 }
 
 impl Material for UnlitTextured3D {
-    fn use_material(&self, gl: &GL) {
-        gl.use_program(Some(&self.program));
-        self.init_uniforms(gl);
+    fn init_uniforms(&self, backend: &dyn GraphicsBackend) {
+        backend.bind_texture(0, self.texture.id());
+        backend.set_uniform(self.program, "tex", UniformValue::Int(0));
     }
 
-    fn init_uniforms(&self, gl: &GL) {
-
-        // js_log("init sampler");
-        let sampler_location = gl.get_uniform_location(
-            &self.program,
-            "tex"
-        ).unwrap();
+    fn get_program(&self) -> ProgramId {
+        self.program
+    }
 
-        let proj_mat_location = gl.get_uniform_location(
-            &self.program, 
-            "projection_matrix"
-        ).unwrap();
+    fn texture(&self) -> Option<&TextureHandle> {
+        Some(&self.texture)
+    }
+}
 
-        let view_mat_location = gl.get_uniform_location(
-            &self.program,
-            "view_matrix"
-        ).unwrap();
+/// Material sampling a cube map by the surface normal, for the globe
+pub struct UnlitCubeMap3D {
+    pub program: ProgramId,
+    pub cube: TextureCube,
+}
 
-        let transform_mat_location = gl.get_uniform_location(
-            &self.program, 
-            "transform_matrix"
-        ).unwrap();
-        
-        let proj_mat = get_projection_matrix();
+impl UnlitCubeMap3D {
+    pub fn new(cube: TextureCube, program_manager: &ShaderProgramManager) -> Result<Self, RendererError> {
+        let prgm = program_manager.get_program("cube_mapped")?;
 
-        let view_mat = get_view_matrix();
+        Ok(Self {
+            program: prgm,
+            cube,
+        })
+    }
+}
 
-        let world_mat = peek_mat_stack();
+impl Material for UnlitCubeMap3D {
+    fn init_uniforms(&self, backend: &dyn GraphicsBackend) {
+        backend.bind_texture(0, self.cube.texture());
+        backend.set_uniform(self.program, "tex", UniformValue::Int(0));
+    }
 
-        gl.uniform_matrix4fv_with_f32_array(
-            Some(&transform_mat_location), 
-            false, 
-            &world_mat.data
-        );
+    fn get_program(&self) -> ProgramId {
+        self.program
+    }
 
-        gl.uniform_matrix4fv_with_f32_array(
-            Some(&proj_mat_location), 
-            false, 
-            &proj_mat.data
-        );
+    fn texture(&self) -> Option<&TextureHandle> {
+        Some(self.cube.handle())
+    }
+}
 
-        gl.uniform_matrix4fv_with_f32_array(
-            Some(&view_mat_location), 
-            false, 
-            &view_mat.data
-        );
+/// Material mapping an equirectangular image onto the globe, e.g. the
+/// Blue Marble
+///
+/// The uv coords are computed from the surface normal, so the mesh needs
+/// none and the image has no seam along the antimeridian.
+pub struct UnlitEquirectangular3D {
+    pub program: ProgramId,
+    pub texture: TextureHandle,
+}
 
-        gl.tex_parameteri(
-            GL::TEXTURE_2D, 
-            GL::TEXTURE_MIN_FILTER, 
-            GL::LINEAR as i32
-        ); // Set filtering mode to linear (default is nearest)
+impl UnlitEquirectangular3D {
+    pub fn new(
+        backend: &dyn GraphicsBackend,
+        textures: &mut TextureManager,
+        img_src: &str,
+        program_manager: &ShaderProgramManager
+    ) -> Result<Self, RendererError> {
+        let prgm = program_manager.get_program("equirectangular")?;
+
+        // Repeating across the antimeridian but not over the poles, and
+        // anisotropic for the squashed view towards the limb
+        let sampler = SamplerDesc { wrap_t: Wrap::ClampToEdge, anisotropy: 8.0, ..Default::default() };
+        let tex = textures.load(backend, img_src, sampler)?;
+
+        Ok(Self {
+            program: prgm,
+            texture: tex,
+        })
+    }
+}
 
-        gl.active_texture(GL::TEXTURE0);
-        gl.bind_texture(GL::TEXTURE_2D, Some(&self.texture));
-        gl.uniform1i(Some(&sampler_location), 0);
+impl Material for UnlitEquirectangular3D {
+    fn init_uniforms(&self, backend: &dyn GraphicsBackend) {
+        // The shader picks the mipmap itself from its own derivatives
+        backend.bind_texture(0, self.texture.id());
+        backend.set_uniform(self.program, "tex", UniformValue::Int(0));
+    }
 
+    fn get_program(&self) -> ProgramId {
+        self.program
     }
 
-    fn get_program(&self) -> WebGlProgram {
-        self.program.clone()
+    fn texture(&self) -> Option<&TextureHandle> {
+        Some(&self.texture)
     }
 }
 
-fn load_texture(
-    gl: &GL,
-    img_src: &str,
-) -> Result<Rc<WebGlTexture>, JsValue> {
-    let texture = gl.create_texture().expect("Cannot create gl texture");
-    gl.bind_texture(GL::TEXTURE_2D, Some(&texture));
-    let level = 0;
-    let internal_format = GL::RGBA;
-    let width = 1;
-    let height = 1;
-    let border = 0;
-    let src_format = GL::RGBA;
-    let src_type = GL::UNSIGNED_BYTE;
-    let pixel: [u8; 4] = [200, 95, 10, 255];
-    // This is the worst method signature I have ever seen
-    gl.tex_image_2d_with_i32_and_i32_and_i32_and_format_and_type_and_opt_u8_array(
-        GL::TEXTURE_2D, 
-        level, 
-        internal_format as i32, 
-        width, 
-        height, 
-        border, 
-        src_format, 
-        src_type, 
-        Some(&pixel),
-    )?;
-
-
-    let img = HtmlImageElement::new().unwrap();
-
-    img.set_cross_origin(Some(""));
-    let imgrc = Rc::new(img);
-    let texture = Rc::new(texture);
-
-    {
-
-        let img = imgrc.clone();
-        let texture = texture.clone();
-        let gl = Rc::new(gl.clone());
-        let a = Closure::wrap(Box::new(move || {
-            gl.bind_texture(GL::TEXTURE_2D, Some(&texture));
-
-            if let Err(e) = gl.tex_image_2d_with_u32_and_u32_and_html_image_element(
-                GL::TEXTURE_2D, 
-                level, 
-                internal_format as i32, 
-                src_format, 
-                src_type, 
-        &img
-            ) {
-                js_log(&e.as_string().unwrap());
-                return;
-            }
-
-            gl.generate_mipmap(GL::TEXTURE_2D);
-        }) as Box<dyn FnMut()>);
-        imgrc.set_onload(Some(a.as_ref().unchecked_ref()));
-
-        // This is a literal memory leak but it's okay :D
-        a.forget();
-    }
-
-    imgrc.set_src(img_src);
-
-    Ok(texture)
-
-}
\ No newline at end of file
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::backend::{graphics_backend::CubeFace, recording::{Command, RecordingBackend}};
+    use crate::graphics::camera::Camera;
+    use crate::math::mat4::Matrix4;
+
+    #[test]
+    fn test_textured_material_binds_texture() {
+        let backend = RecordingBackend::new();
+        let mut programs = ShaderProgramManager::new();
+        programs.expose_program(ProgramId(7), "textured_lit");
+
+        let mut textures = TextureManager::new();
+        let material = UnlitTextured3D::new(&backend, &mut textures, "/res/earth.png", &programs).unwrap();
+        let texture = material.texture.id();
+        assert_eq!(backend.take_commands(), vec![
+            Command::CreateTexture(texture),
+            Command::SetSampler { texture, sampler: SamplerDesc::default() },
+            Command::UploadTexture { texture, width: 1, height: 1 },
+            Command::LoadTextureImage { texture, url: String::from("/res/earth.png") },
+        ]);
+
+        material.use_material(&backend, &Camera::new(), &Matrix4::identity());
+        let commands = backend.take_commands();
+
+        assert_eq!(commands[0], Command::UseProgram(ProgramId(7)));
+        assert!(commands.contains(&Command::BindTexture { unit: 0, texture }));
+        assert!(commands.contains(&Command::SetUniform {
+            program: ProgramId(7),
+            name: String::from("tex"),
+            value: UniformValue::Int(0)
+        }));
+    }
+
+    #[test]
+    fn test_cube_map_material_binds_one_texture() {
+        let backend = RecordingBackend::new();
+        let mut programs = ShaderProgramManager::new();
+        programs.expose_program(ProgramId(3), "cube_mapped");
+
+        let srcs = ["/res/0.png", "/res/1.png", "/res/2.png", "/res/3.png", "/res/4.png", "/res/5.png"];
+        let cube = TextureCube::from_faces(&backend, &mut TextureManager::new(), srcs).unwrap();
+        let commands = backend.take_commands();
+
+        assert_eq!(commands[0], Command::CreateTextureCube(cube.texture()));
+        assert_eq!(commands.iter().filter(|c| matches!(c, Command::UploadCubeFace { .. })).count(), 6);
+        assert!(commands.contains(&Command::LoadCubeFaceImage {
+            texture: cube.texture(),
+            face: CubeFace::NegativeZ,
+            url: String::from("/res/5.png")
+        }));
+
+        let material = UnlitCubeMap3D::new(cube, &programs).unwrap();
+        material.use_material(&backend, &Camera::new(), &Matrix4::identity());
+        let commands = backend.take_commands();
+
+        let binds: Vec<&Command> = commands.iter().filter(|c| matches!(c, Command::BindTexture { .. })).collect();
+        assert_eq!(binds, vec![&Command::BindTexture { unit: 0, texture: material.cube.texture() }]);
+    }
+}
@@ -1,19 +1,12 @@
-use std::{collections::HashMap, sync::{Arc, Mutex}};
+use std::collections::HashMap;
 
-use lazy_static::lazy_static;
 // use wasm_bindgen::{prelude::Closure, JsCast};
-use web_sys::{WebGlShader, WebGlProgram};
+use super::backend::graphics_backend::{ProgramId, ShaderId};
 
-use crate::{js_log};
-
-lazy_static! {
-    static ref SHADER_MANAGER: Arc<Mutex<HashMap<String, usize>>> = Arc::new(Mutex::new(HashMap::new()));
-}
-
-thread_local! { static SHADER_VEC: Vec<WebGlShader> = Vec::new() }
+use crate::error::RendererError;
 
 pub struct ShaderManager {
-    shaders: HashMap<String, WebGlShader>
+    shaders: HashMap<String, ShaderId>
 }
 
 impl ShaderManager {
@@ -23,24 +16,21 @@ impl ShaderManager {
 
     pub fn expose_shader(
         &mut self,
-        shader: WebGlShader,
+        shader: ShaderId,
         key: &str
     ) {
         self.shaders.insert(String::from(key), shader);
     }
 
-    pub fn get_shader(&self, key: &str) -> &WebGlShader {
-        if self.shaders.contains_key(key) {
-            self.shaders.get(key).unwrap()
-        } else {
-            js_log("Could not find shader");
-            panic!()
-        }
+    pub fn get_shader(&self, key: &str) -> Result<ShaderId, RendererError> {
+        self.shaders.get(key)
+            .copied()
+            .ok_or_else(|| RendererError::UnknownShader(String::from(key)))
     }
 }
 
 pub struct ShaderProgramManager {
-    programs: HashMap<String, WebGlProgram>
+    programs: HashMap<String, ProgramId>
 }
 
 impl ShaderProgramManager {
@@ -48,16 +38,13 @@ impl ShaderProgramManager {
         Self { programs: HashMap::new() }
     }
 
-    pub fn expose_program(&mut self, program: WebGlProgram, key: &str) {
+    pub fn expose_program(&mut self, program: ProgramId, key: &str) {
         self.programs.insert(String::from(key), program);
     }
 
-    pub fn get_program(&self, key: &str) -> &WebGlProgram {
-        if self.programs.contains_key(key) {
-            self.programs.get(key).unwrap()
-        } else {
-            js_log("Could not find program");
-            panic!()
-        }
+    pub fn get_program(&self, key: &str) -> Result<ProgramId, RendererError> {
+        self.programs.get(key)
+            .copied()
+            .ok_or_else(|| RendererError::UnknownProgram(String::from(key)))
     }
 }
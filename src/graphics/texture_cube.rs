@@ -0,0 +1,125 @@
+use crate::error::RendererError;
+
+use super::backend::graphics_backend::{Filter, GraphicsBackend, SamplerDesc, TextureId, Wrap};
+use super::texture_manager::{TextureHandle, TextureManager};
+
+/// A cube map texture, sampled by direction
+///
+/// Faces are given in the order of `CubeFace::ALL`. For the globe the cube
+/// map has longitude 0 at +z, 90E at +x and the north pole at +y, and each
+/// face is the image seen from outside the globe. The side faces have north
+/// up, the top face has longitude 180 up and the bottom face longitude 0.
+pub struct TextureCube {
+    texture: TextureHandle
+}
+
+/// Sampler of every cube map, linear filtered without mipmaps
+const SAMPLER: SamplerDesc = SamplerDesc {
+    wrap_s: Wrap::ClampToEdge,
+    wrap_t: Wrap::ClampToEdge,
+    min_filter: Filter::Linear,
+    mag_filter: Filter::Linear,
+    mipmaps: false,
+    anisotropy: 1.0
+};
+
+#[allow(dead_code)]
+impl TextureCube {
+    /// Load each face from its own image
+    pub fn from_faces(
+        backend: &dyn GraphicsBackend,
+        textures: &mut TextureManager,
+        urls: [&str; 6]
+    ) -> Result<Self, RendererError> {
+        Ok(Self { texture: textures.load_cube_faces(backend, urls, SAMPLER)? })
+    }
+
+    /// Load every face from one image with the faces laid out as a cross,
+    /// see `split_cross_net`
+    pub fn from_cross_net(
+        backend: &dyn GraphicsBackend,
+        textures: &mut TextureManager,
+        url: &str
+    ) -> Result<Self, RendererError> {
+        Ok(Self { texture: textures.load_cube_net(backend, url, SAMPLER)? })
+    }
+
+    pub fn texture(&self) -> TextureId {
+        self.texture.id()
+    }
+
+    pub fn handle(&self) -> &TextureHandle {
+        &self.texture
+    }
+}
+
+/// Split the RGBA pixels of a cube net into its faces
+///
+/// The net is 4 faces wide and 3 high, laid out as a horizontal cross:
+///
+/// ```text
+///      +y
+/// -x   +z   +x   -z
+///      -y
+/// ```
+///
+/// Returns the size of a face and the pixels of each face in the order of
+/// `CubeFace::ALL`.
+pub fn split_cross_net(width: u32, height: u32, pixels: &[u8]) -> Result<(u32, [Vec<u8>; 6]), String> {
+    if width == 0 || !width.is_multiple_of(4) || width / 4 * 3 != height {
+        return Err(format!("A cube net has to be 4 by 3 square faces, not {}x{} pixels", width, height));
+    }
+
+    if pixels.len() != (width * height * 4) as usize {
+        return Err(String::from("Cube net data does not match its size"));
+    }
+
+    let size = width / 4;
+
+    // Column and row of each face in the net
+    let cells = [(2, 1), (0, 1), (1, 0), (1, 2), (1, 1), (3, 1)];
+
+    let faces = cells.map(|(column, row)| {
+        let mut face = Vec::with_capacity((size * size * 4) as usize);
+
+        for y in 0..size {
+            let start = (((row * size + y) * width + column * size) * 4) as usize;
+            face.extend_from_slice(&pixels[start..start + (size * 4) as usize]);
+        }
+
+        face
+    });
+
+    Ok((size, faces))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_cross_net() {
+        // 2x2 pixel faces, every pixel holding the column and row of its face
+        let (width, height) = (8, 6);
+        let pixels: Vec<u8> = (0..width * height)
+            .flat_map(|i| [((i % width) / 2) as u8, ((i / width) / 2) as u8, 0, 255])
+            .collect();
+
+        let (size, faces) = split_cross_net(width, height, &pixels).unwrap();
+        assert_eq!(size, 2);
+
+        let cells: Vec<[u8; 2]> = faces.iter().map(|face| {
+            assert_eq!(face.len(), 16);
+            assert!(face.chunks_exact(4).all(|pixel| pixel == &face[..4]));
+            [face[0], face[1]]
+        }).collect();
+
+        assert_eq!(cells, vec![[2, 1], [0, 1], [1, 0], [1, 2], [1, 1], [3, 1]]);
+    }
+
+    #[test]
+    fn test_split_cross_net_rejects_other_layouts() {
+        assert!(split_cross_net(6, 8, &[0; 6 * 8 * 4]).is_err());
+        assert!(split_cross_net(8, 6, &[0; 12]).is_err());
+    }
+}
@@ -0,0 +1,325 @@
+use std::rc::Rc;
+
+use crate::error::RendererError;
+
+use super::backend::graphics_backend::{CubeFace, GraphicsBackend, LoadState, SamplerDesc, TextureId};
+
+/// Color of every texture until its image loaded
+const PLACEHOLDER: [u8; 4] = [200, 95, 10, 255];
+
+/// Images a texture is loaded from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TextureSource {
+    Image(String),
+    /// One image per face of a cube map, in the order of `CubeFace::ALL`
+    CubeFaces([String; 6]),
+    /// A cube map from one image, see `split_cross_net`
+    CubeNet(String)
+}
+
+impl TextureSource {
+    /// Whether one of the images is at `url`
+    fn loads(&self, url: &str) -> bool {
+        match self {
+            TextureSource::Image(src) | TextureSource::CubeNet(src) => src == url,
+            TextureSource::CubeFaces(srcs) => srcs.iter().any(|src| src == url)
+        }
+    }
+}
+
+/// Shared reference to a texture of a `TextureManager`
+///
+/// The texture is deleted once the last clone is dropped.
+#[derive(Debug, Clone)]
+pub struct TextureHandle(Rc<TextureId>);
+
+impl TextureHandle {
+    pub fn id(&self) -> TextureId {
+        *self.0
+    }
+}
+
+struct Entry {
+    source: TextureSource,
+    sampler: SamplerDesc,
+    handle: TextureHandle,
+    state: LoadState
+}
+
+/// Textures loaded from URLs, shared by everything showing the same images
+///
+/// Loading a source again with the same sampler returns the texture already
+/// made for it, unless that one failed. Every texture shows an orange placeholder until its
+/// images loaded. `update` polls the loads once per frame and deletes the
+/// textures no handle is left for.
+pub struct TextureManager {
+    entries: Vec<Entry>,
+    /// Whether a load was started or pending since the textures were last
+    /// all ready
+    loading: bool
+}
+
+#[allow(dead_code)]
+impl TextureManager {
+    pub fn new() -> Self {
+        Self { entries: Vec::new(), loading: false }
+    }
+
+    /// Texture showing the image at `url`
+    pub fn load(
+        &mut self,
+        backend: &dyn GraphicsBackend,
+        url: &str,
+        sampler: SamplerDesc
+    ) -> Result<TextureHandle, RendererError> {
+        self.get_or_create(TextureSource::Image(String::from(url)), sampler, || {
+            let texture = backend.create_texture()?;
+            backend.set_sampler(texture, &sampler);
+            backend.upload_texture(texture, 1, 1, &PLACEHOLDER)?;
+            backend.load_texture_image(texture, url)?;
+
+            Ok(texture)
+        })
+    }
+
+    /// Cube map with one image per face, in the order of `CubeFace::ALL`
+    pub fn load_cube_faces(
+        &mut self,
+        backend: &dyn GraphicsBackend,
+        urls: [&str; 6],
+        sampler: SamplerDesc
+    ) -> Result<TextureHandle, RendererError> {
+        self.get_or_create(TextureSource::CubeFaces(urls.map(String::from)), sampler, || {
+            let texture = cube_with_placeholder(backend, &sampler)?;
+
+            for (face, url) in CubeFace::ALL.into_iter().zip(urls) {
+                backend.load_cube_face_image(texture, face, url)?;
+            }
+
+            Ok(texture)
+        })
+    }
+
+    /// Cube map with every face from the cross shaped net at `url`
+    pub fn load_cube_net(
+        &mut self,
+        backend: &dyn GraphicsBackend,
+        url: &str,
+        sampler: SamplerDesc
+    ) -> Result<TextureHandle, RendererError> {
+        self.get_or_create(TextureSource::CubeNet(String::from(url)), sampler, || {
+            let texture = cube_with_placeholder(backend, &sampler)?;
+            backend.load_cube_net_image(texture, url)?;
+
+            Ok(texture)
+        })
+    }
+
+    /// How a texture is sampled
+    pub fn sampler(&self, texture: &TextureHandle) -> Option<SamplerDesc> {
+        self.entries.iter()
+            .find(|entry| Rc::ptr_eq(&entry.handle.0, &texture.0))
+            .map(|entry| entry.sampler)
+    }
+
+    /// Change how a texture is sampled, for everything sharing it
+    ///
+    /// Mipmaps turned on here are generated for the images the texture
+    /// already has.
+    pub fn set_sampler(&mut self, backend: &dyn GraphicsBackend, texture: &TextureHandle, sampler: SamplerDesc) {
+        let had_mipmaps = match self.entries.iter_mut().find(|entry| Rc::ptr_eq(&entry.handle.0, &texture.0)) {
+            Some(entry) => std::mem::replace(&mut entry.sampler, sampler).mipmaps,
+            None => false
+        };
+
+        backend.set_sampler(texture.id(), &sampler);
+
+        if sampler.mipmaps && !had_mipmaps {
+            backend.generate_mipmaps(texture.id());
+        }
+    }
+
+    /// State of the texture loading the image at `url`, if any
+    pub fn state(&self, url: &str) -> Option<&LoadState> {
+        self.entries.iter()
+            .rev()
+            .find(|entry| entry.source.loads(url))
+            .map(|entry| &entry.state)
+    }
+
+    /// Number of textures still loading
+    pub fn pending(&self) -> usize {
+        self.entries.iter().filter(|entry| entry.state == LoadState::Pending).count()
+    }
+
+    /// Error of every texture that failed to load
+    pub fn failed(&self) -> Vec<&str> {
+        self.entries.iter()
+            .filter_map(|entry| match &entry.state {
+                LoadState::Failed(error) => Some(error.as_str()),
+                _ => None
+            })
+            .collect()
+    }
+
+    /// Delete unused textures and poll the pending ones
+    ///
+    /// Returns true when the last pending texture finished loading, whether
+    /// it loaded or failed.
+    pub fn update(&mut self, backend: &dyn GraphicsBackend) -> bool {
+        self.entries.retain(|entry| {
+            let used = Rc::strong_count(&entry.handle.0) > 1;
+            if !used {
+                backend.delete_texture(entry.handle.id());
+            }
+            used
+        });
+
+        for entry in &mut self.entries {
+            if entry.state == LoadState::Pending {
+                entry.state = backend.load_state(entry.handle.id());
+            }
+        }
+
+        let ready = self.loading && self.pending() == 0;
+        if ready {
+            self.loading = false;
+        }
+
+        ready
+    }
+
+    fn get_or_create(
+        &mut self,
+        source: TextureSource,
+        sampler: SamplerDesc,
+        create: impl FnOnce() -> Result<TextureId, String>
+    ) -> Result<TextureHandle, RendererError> {
+        let existing = self.entries.iter().find(|entry| {
+            entry.source == source && entry.sampler == sampler && !matches!(entry.state, LoadState::Failed(_))
+        });
+
+        if let Some(entry) = existing {
+            return Ok(entry.handle.clone());
+        }
+
+        let handle = TextureHandle(Rc::new(create().map_err(RendererError::Resource)?));
+        self.entries.push(Entry { source, sampler, handle: handle.clone(), state: LoadState::Pending });
+        self.loading = true;
+
+        Ok(handle)
+    }
+}
+
+/// Create a cube map with every face showing the placeholder
+fn cube_with_placeholder(backend: &dyn GraphicsBackend, sampler: &SamplerDesc) -> Result<TextureId, String> {
+    let texture = backend.create_texture_cube()?;
+    backend.set_sampler(texture, sampler);
+
+    for face in CubeFace::ALL {
+        backend.upload_cube_face(texture, face, 1, 1, &PLACEHOLDER)?;
+    }
+
+    Ok(texture)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::backend::recording::{Command, RecordingBackend};
+    use crate::graphics::backend::software::SoftwareBackend;
+
+    #[test]
+    fn test_same_source_shares_texture() {
+        let backend = SoftwareBackend::new(4, 4);
+        let mut textures = TextureManager::new();
+
+        let a = textures.load(&backend, "/res/a.png", SamplerDesc::default()).unwrap();
+        let b = textures.load(&backend, "/res/a.png", SamplerDesc::default()).unwrap();
+        let net = textures.load_cube_net(&backend, "/res/a.png", SamplerDesc::default()).unwrap();
+        let unfiltered = textures.load(&backend, "/res/a.png", SamplerDesc { mipmaps: false, ..Default::default() }).unwrap();
+
+        assert_eq!(a.id(), b.id());
+        assert_ne!(a.id(), net.id());
+        assert_ne!(a.id(), unfiltered.id());
+    }
+
+    #[test]
+    fn test_ready_once_all_finished() {
+        let backend = SoftwareBackend::new(4, 4);
+        let mut textures = TextureManager::new();
+
+        let _a = textures.load(&backend, "/res/a.png", SamplerDesc::default()).unwrap();
+        let _b = textures.load(&backend, "/res/b.png", SamplerDesc::default()).unwrap();
+        assert!(!textures.update(&backend));
+        assert_eq!(textures.pending(), 2);
+
+        backend.add_image("/res/a.png", 1, 1, vec![0, 0, 255, 255]);
+        assert!(!textures.update(&backend));
+        assert_eq!(textures.state("/res/a.png"), Some(&LoadState::Loaded));
+
+        backend.fail_image("/res/b.png");
+        assert!(textures.update(&backend));
+        assert_eq!(textures.failed(), vec!["Could not load image /res/b.png"]);
+
+        // Reported once, until another load starts
+        assert!(!textures.update(&backend));
+        backend.add_image("/res/c.png", 1, 1, vec![0, 0, 255, 255]);
+        let _c = textures.load(&backend, "/res/c.png", SamplerDesc::default()).unwrap();
+        assert!(textures.update(&backend));
+    }
+
+    #[test]
+    fn test_failed_texture_is_retried() {
+        let backend = SoftwareBackend::new(4, 4);
+        let mut textures = TextureManager::new();
+
+        backend.fail_image("/res/a.png");
+        let failed = textures.load(&backend, "/res/a.png", SamplerDesc::default()).unwrap();
+        textures.update(&backend);
+
+        let retried = textures.load(&backend, "/res/a.png", SamplerDesc::default()).unwrap();
+        assert_ne!(failed.id(), retried.id());
+
+        textures.update(&backend);
+        assert_eq!(textures.state("/res/a.png"), Some(&LoadState::Failed(String::from("Could not load image /res/a.png"))));
+    }
+
+    #[test]
+    fn test_mipmaps_turned_on_are_generated() {
+        let backend = RecordingBackend::new();
+        let mut textures = TextureManager::new();
+
+        let unfiltered = SamplerDesc { mipmaps: false, ..Default::default() };
+        let texture = textures.load(&backend, "/res/a.png", unfiltered).unwrap();
+        textures.update(&backend);
+        backend.take_commands();
+
+        textures.set_sampler(&backend, &texture, SamplerDesc::default());
+        assert_eq!(backend.take_commands(), vec![
+            Command::SetSampler { texture: texture.id(), sampler: SamplerDesc::default() },
+            Command::GenerateMipmaps(texture.id())
+        ]);
+        assert_eq!(textures.sampler(&texture), Some(SamplerDesc::default()));
+
+        // Already on, or turned off
+        textures.set_sampler(&backend, &texture, SamplerDesc { anisotropy: 4.0, ..Default::default() });
+        textures.set_sampler(&backend, &texture, unfiltered);
+        assert!(!backend.take_commands().contains(&Command::GenerateMipmaps(texture.id())));
+    }
+
+    #[test]
+    fn test_unused_texture_is_deleted() {
+        let backend = SoftwareBackend::new(4, 4);
+        let mut textures = TextureManager::new();
+
+        let a = textures.load(&backend, "/res/a.png", SamplerDesc::default()).unwrap();
+        textures.update(&backend);
+        assert_eq!(textures.pending(), 1);
+
+        drop(a);
+        textures.update(&backend);
+        assert_eq!(textures.state("/res/a.png"), None);
+        assert_eq!(textures.pending(), 0);
+    }
+}
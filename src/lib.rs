@@ -1,23 +1,36 @@
 // Hamilton Rice
 
-use app_state::{set_mouse_pos, set_mouse_down, update_projection_matrix, update_view_matrix, move_camera, set_mouse_drag, get_mouse_drag};
-use common::compile_shader;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use app_state::{Action, InputEvent, InputState};
+use graphics::backend::graphics_backend::{GraphicsBackend, LoadState, SamplerDesc, ShaderKind};
+use graphics::backend::webgl::WebGl2Backend;
+use graphics::camera::{Camera, OrbitCamera};
 use graphics::mesh::Mesh;
 use graphics::mesh_renderer::MeshRenderer;
 use graphics::{frag_shaders, vert_shaders};
 use graphics::shader_manager::{ShaderManager, ShaderProgramManager};
 use math::vec2::Vector2;
 use wasm_bindgen::{prelude::*, JsCast};
-use web_sys::{WebGl2RenderingContext, HtmlCanvasElement};
-
-use crate::app_state::{get_mouse_delta, get_mouse_pos, update_mouse_delta, set_camera_position};
-use crate::graphics::programs::unlit_3d::{UnlitTextured3D, Unlit3D};
-use crate::math::quaternion::Quaternion;
-use crate::math::vec3::Vector3;
+use web_sys::HtmlCanvasElement;
+
+use crate::graphics::programs::unlit_3d::{UnlitCubeMap3D, UnlitEquirectangular3D, Unlit3D};
+use crate::graphics::texture_cube::TextureCube;
+use crate::graphics::texture_manager::TextureManager;
+use crate::data::cneos::{self, FireballRecord, Import, ImportError};
+use crate::error::RendererError;
+use crate::math::geo::EarthModel;
+use crate::scene::fireball::{Fireball, FireballRegistry};
+use crate::scene::marker_style::{ColorRamp, MarkerStyle, ScaleMode, StyleAttribute};
+use crate::scene::picking::{self, Pick};
+use crate::scene::scene_graph::{NodeId, SceneGraph};
 use crate::scene::scene_node::Node;
 
 mod app_state;
 mod common;
+mod data;
+mod error;
 mod graphics;
 mod scene;
 mod math;
@@ -31,378 +44,766 @@ extern "C" {
 
 #[wasm_bindgen]
 pub struct App {
-    gl: WebGl2RenderingContext,
-    root: Node,
+    canvas: HtmlCanvasElement,
+    backend: WebGl2Backend,
+    scene: SceneGraph,
+    globe: NodeId,
     program_manager: ShaderProgramManager,
-    cache_mouse_pos: Vector2
+    textures: TextureManager,
+    fireballs: FireballRegistry,
+    /// Shape of the earth fireballs are placed on and picks are read from
+    earth: EarthModel,
+    orbit: OrbitCamera,
+    camera: Camera,
+    input: Rc<RefCell<InputState>>,
+    click_callback: Option<js_sys::Function>,
+    hovered: Option<u32>,
+    hover_callback: Option<js_sys::Function>,
+    textures_ready_callback: Option<js_sys::Function>
 }
 
 #[wasm_bindgen]
 impl App {
     #[wasm_bindgen(constructor)]
-    pub fn new(element_id: &str) -> Self {
+    pub fn new(element_id: &str) -> Result<App, JsValue> {
+
+        let canvas = common::get_canvas(element_id)?;
+        let gl = common::get_gl_context(&canvas)?;
 
-        let gl = common::get_gl_context(element_id).unwrap();
-        // TODO: Actually throw an error here instead of just assuming it's going to work
+        let input = Rc::new(RefCell::new(InputState::new()));
+        register_input_events(&canvas, &input)?;
 
-        gl.enable(WebGl2RenderingContext::CULL_FACE); // Cull backfaces
-        gl.enable(WebGl2RenderingContext::DEPTH_TEST); // Sort by depth
-        gl.cull_face(WebGl2RenderingContext::BACK);
-        gl.clear_color(0.0, 0.0, 0.0, 1.0);
+        let backend = WebGl2Backend::new(gl);
+
+        backend.set_cull_back_faces(true); // Cull backfaces
+        backend.set_depth_test(true); // Sort by depth
+        backend.set_clear_color([0.0, 0.0, 0.0, 1.0]);
 
         log("Compiling Shaders");
-        let shader_manager = precompile_shaders(&gl);
-        let program_manager = link_programs(&gl, &shader_manager);
+        let shader_manager = precompile_shaders(&backend)?;
+        let program_manager = link_programs(&backend, &shader_manager)?;
 
         log("Creating mesh renderer");
 
-        let r = MeshRenderer::new(
-            &gl,
-            Mesh::normal_cube_unit_sphere_face(32),
-            // Mesh::texture_quad(),
-            Box::new(
-                UnlitTextured3D::new(
-                    &gl, 
-                    "/res/world_cube_net_strip1.png",
-                    &program_manager
-                )
-            )
-        );
-
-        let r2 = MeshRenderer::new(
-            &gl, 
-            Mesh::normal_cube_unit_sphere_face(32), 
-            // Mesh::texture_quad(),
-            Box::new(
-                UnlitTextured3D::new(
-                    &gl, 
-                    "/res/world_cube_net_strip4.png",
-                    &program_manager
-                )
-            )
-        );
-
-        let r3 = MeshRenderer::new(
-            &gl, 
-            Mesh::normal_cube_unit_sphere_face(32), 
-            // Mesh::texture_quad(),
-            Box::new(
-                UnlitTextured3D::new(
-                    &gl, 
-                    "/res/world_cube_net_strip3.png",
-                    &program_manager
-                )
-            )
-        );
-
-        let r4 = MeshRenderer::new(
-            &gl, 
-            Mesh::normal_cube_unit_sphere_face(32), 
-            // Mesh::texture_quad(),
-            Box::new(
-                UnlitTextured3D::new(
-                    &gl, 
-                    "/res/world_cube_net_strip2.png",
-                    &program_manager
-                )
-            )
-        );
-
-        let r5 = MeshRenderer::new(
-            &gl, 
-            Mesh::normal_cube_unit_sphere_face(32), 
-            // Mesh::texture_quad(),
-            Box::new(
-                UnlitTextured3D::new(
-                    &gl, 
-                    "/res/world_cube_net_strip5.png",
-                    &program_manager
-                )
-            )
-        );
-
-        let r6 = MeshRenderer::new(
-            &gl, 
-            Mesh::normal_cube_unit_sphere_face(32), 
-            // Mesh::texture_quad(),
-            Box::new(
-                UnlitTextured3D::new(
-                    &gl, 
-                    "/res/world_cube_net_strip6.png",
-                    &program_manager
-                )
-            )
-        );
+        let mut scene = SceneGraph::new();
+        let mut textures = TextureManager::new();
+        let globe = build_globe(&backend, &program_manager, &mut textures, &mut scene)?;
 
         log("Created mesh renderer");
-        let deg_to_rad = std::f32::consts::PI / 180.0;
-
-        // TODO: find a better way of doing this
-        let mut root_node = Node::new();
-        let mut quad_sphere_node = Node::new();
-        let mut quad_sphere_node_2 = Node::new();
-        let mut quad_sphere_node_3 = Node::new();
-        let mut quad_sphere_node_4 = Node::new();
-        let mut quad_sphere_node_5 = Node::new();
-        let mut quad_sphere_node_6 = Node::new();
-        quad_sphere_node.add_renderer(r);
-        quad_sphere_node_2.add_renderer(r2);
-        quad_sphere_node_3.add_renderer(r3);
-        quad_sphere_node_4.add_renderer(r4);
-        quad_sphere_node_5.add_renderer(r6);
-        quad_sphere_node_6.add_renderer(r5);
-
-        quad_sphere_node_2.rotation = Quaternion::euler(
-            0.0, 
-            90.0 * deg_to_rad, 
-            0.0
-        );
-
-        quad_sphere_node_3.rotation = Quaternion::euler(
-            0.0, 
-            180.0 * deg_to_rad, 
-            0.0
-        );
-
-        quad_sphere_node_4.rotation = Quaternion::euler(
-            0.0, 
-            270.0 * deg_to_rad, 
-            0.0
-        );
-
-        quad_sphere_node_5.rotation = Quaternion::euler(
-            270.0 * deg_to_rad, 
-            0.0, 
-            0.0
-        );
-
-        quad_sphere_node_6.rotation = Quaternion::euler(
-            90.0 * deg_to_rad, 
-            0.0, 
-            0.0
-        );
-
-        // root_node.add_renderer(r);
-        root_node.add_child(quad_sphere_node);
-        root_node.add_child(quad_sphere_node_2);
-        root_node.add_child(quad_sphere_node_3);
-        root_node.add_child(quad_sphere_node_4);
-        root_node.add_child(quad_sphere_node_5);
-        root_node.add_child(quad_sphere_node_6);
-
-        set_camera_position(Vector3::new(
-            0.0,
-            0.0,
-            15.0
-        ));
+
+        // Start above longitude 180, looking down -z like the old fixed camera
+        let orbit = OrbitCamera::new(std::f32::consts::PI, 0.0, 15.0);
+        let mut camera = Camera::new();
+        orbit.apply(&mut camera);
+
         
-        App{
-            gl: gl,
-            root: root_node,
+        Ok(App{
+            canvas,
+            backend,
+            scene,
+            globe,
             program_manager: program_manager,
-            cache_mouse_pos: get_mouse_pos()
-        }
+            textures,
+            fireballs: FireballRegistry::new(),
+            earth: EarthModel::default(),
+            orbit,
+            camera,
+            input,
+            click_callback: None,
+            hovered: None,
+            hover_callback: None,
+            textures_ready_callback: None
+        })
     }
 
     pub fn update(&mut self, delta_time: f32, canvas_height: i32, canvas_width: i32) -> Result<(), JsValue> {
         
-        app_state::update_dynamic_data(canvas_height as f32, canvas_width as f32);
+        let mut input = self.input.borrow_mut();
+        app_state::control_orbit(&mut input, &mut self.orbit, delta_time);
+
+        let click = input.take_click();
+        let double_tap = input.take_double_tap();
+        let hover = Some(input.pos()).filter(|_| input.over() && !input.down());
+        drop(input);
 
-        let new_mouse_pos = get_mouse_pos();
-        let delta = self.cache_mouse_pos - get_mouse_pos();
-        update_mouse_delta(delta[0], delta[1]);
-        self.cache_mouse_pos = new_mouse_pos;
+        self.backend.viewport(0, 0, canvas_width, canvas_height);
 
-        let mouse_delta = get_mouse_delta();
-        self.root.scale += Vector3::new(mouse_delta[1], mouse_delta[0], 0.0) * delta_time;
+        self.camera.update_projection_matrix(canvas_width as f32 / canvas_height as f32);
+        self.orbit.apply(&mut self.camera);
 
-        // self.root.position -= Vector3::new(0.0, 0.0, delta_time * 0.01);
-        self.gl.viewport(0, 0, canvas_width, canvas_height);
+        if let Some(click) = click {
+            self.handle_click(click)?;
+        }
+
+        if let Some(tap) = double_tap {
+            self.fly_to_pick(tap);
+        }
 
-        self.root.rotation = Quaternion::euler(
-            self.root.scale[0],
-            self.root.scale[1],
-            0.0//self.root.scale[2]
-        );
+        self.update_hover(hover)?;
+        self.update_textures()?;
 
-        // self.root.position = Vector3::new(0.0, (self.root.scale[1] as f32).sin(), 0.0);
-        update_camera();
         Ok(())
     }
 
-    pub fn render(&self) -> Result<(), JsValue> {
-        self.gl.clear(WebGl2RenderingContext::DEPTH_BUFFER_BIT);
-        self.gl.clear(WebGl2RenderingContext::COLOR_BUFFER_BIT);
-        self.root.render(&self.gl);
+    /// Animate the camera to look down on a point of the globe
+    /// 
+    /// `lat` and `lon` are in degrees and `duration` in seconds. `distance`
+    /// is measured from the center of the globe in globe units, where `1.0`
+    /// is the radius of the earth, and is clamped to the zoom limits.
+    /// Dragging or scrolling cancels the flight.
+    pub fn fly_to(&mut self, lat: f32, lon: f32, distance: f32, duration: f32) {
+        self.orbit.fly_to(lat, lon, distance, duration);
+    }
+
+    /// Bind a key to a camera action, replacing the key's previous binding
+    /// 
+    /// `key` is a `KeyboardEvent.key` value such as `"ArrowLeft"` or `"q"`,
+    /// matched ignoring case. `action` is one of `"rotate_left"`,
+    /// `"rotate_right"`, `"rotate_up"`, `"rotate_down"`, `"zoom_in"`,
+    /// `"zoom_out"` or `"reset"`. Keys only reach the canvas while it has
+    /// focus.
+    pub fn bind_key(&mut self, key: &str, action: &str) -> Result<(), JsValue> {
+        let action = Action::from_name(action)
+            .ok_or_else(|| JsValue::from_str(&format!("Unknown action {}", action)))?;
+
+        self.input.borrow_mut().bindings.bind(key, action);
         Ok(())
     }
 
-    pub fn add_fireball(&mut self, lat: f32, lon: f32, alt: f32) -> Result<(), JsValue> {
-        
-        // Create a new node with the rotation
-
-        // Create a new node with the translation, scale, and mesh
-        let deg_to_rad = std::f32::consts::PI / 180.0;
-        let mut base_rot_lon = Node::new(); // y rot
-        let mut base_rot_lat = Node::new(); // x rot
-
-        base_rot_lon.rotation = Quaternion::euler(
-            0.0, 
-            deg_to_rad * (-lon),
-            0.0, 
-            // 0.0
-        );
-
-        base_rot_lat.rotation = Quaternion::euler(
-            deg_to_rad * (-lat + 90.0),
-            0.0,
-            0.0,
-        );
-
-        let mut base_pos = Node::new();
-        base_pos.position = Vector3::new(
-            0.0,
-            1.0 + alt,
-            0.0
-        );
-        
-        // TODO: Scale
-        base_pos.add_renderer(
-            MeshRenderer::new(
-                &self.gl,
-                Mesh::fireball(),
-                Box::new(Unlit3D::new(&self.gl, &self.program_manager))
-            )
-        );
-
-        base_rot_lat.add_child(base_pos);
-        base_rot_lon.add_child(base_rot_lat);
-        // base_rot.add_child(base_pos);
-        let _ = &self.root.add_child(base_rot_lon);
-        
+    /// Remove the binding of a key
+    pub fn unbind_key(&mut self, key: &str) {
+        self.input.borrow_mut().bindings.unbind(key);
+    }
+
+    /// Restore the default arrow key, WASD, +/- and R bindings
+    pub fn reset_key_bindings(&mut self) {
+        self.input.borrow_mut().bindings = Default::default();
+    }
+
+    /// Object mapping every bound key, in lower case, to its action name
+    pub fn key_bindings(&self) -> Result<JsValue, JsValue> {
+        let out = js_sys::Object::new();
+
+        for (key, action) in self.input.borrow().bindings.iter() {
+            js_sys::Reflect::set(&out, &key.into(), &action.name().into())?;
+        }
+
+        Ok(out.into())
+    }
+
+    /// Set the function called when the canvas is clicked
+    /// 
+    /// The function is called with an object describing what was clicked:
+    /// `{ type: "fireball", id, record }` for a fireball marker,
+    /// `{ type: "globe", lat, lon }` for a point on the globe, or `null`
+    /// if the click missed both. Dragging the globe is not a click.
+    pub fn set_click_callback(&mut self, callback: js_sys::Function) {
+        self.click_callback = Some(callback);
+    }
+
+    /// Remove the function set by `set_click_callback`
+    pub fn clear_click_callback(&mut self) {
+        self.click_callback = None;
+    }
+
+    /// ID of the fireball under the mouse, if any
+    /// 
+    /// Updated once per frame in `update`. Nothing is hovered while a
+    /// mouse button is held down.
+    pub fn hovered_fireball(&self) -> Option<u32> {
+        self.hovered.filter(|id| self.fireballs.get(*id).is_some())
+    }
+
+    /// Set the function called when the hovered fireball changes
+    /// 
+    /// The function is called with the ID of the newly hovered fireball, or
+    /// `null` when the mouse leaves a marker.
+    pub fn set_hover_callback(&mut self, callback: js_sys::Function) {
+        self.hover_callback = Some(callback);
+    }
+
+    /// Remove the function set by `set_hover_callback`
+    pub fn clear_hover_callback(&mut self) {
+        self.hover_callback = None;
+    }
+
+    /// Set the function called when every texture finished loading
+    /// 
+    /// The function is called with an array of the errors of the textures
+    /// that failed, which is empty if all loaded. It is called again after
+    /// textures loaded later, e.g. by `set_globe_texture`, are finished.
+    pub fn set_textures_ready_callback(&mut self, callback: js_sys::Function) {
+        self.textures_ready_callback = Some(callback);
+    }
+
+    /// Remove the function set by `set_textures_ready_callback`
+    pub fn clear_textures_ready_callback(&mut self) {
+        self.textures_ready_callback = None;
+    }
+
+    /// State of the texture loading the image at `url`: `"pending"`,
+    /// `"loaded"` or `"failed"`, or `undefined` if no texture uses it
+    pub fn texture_state(&self, url: &str) -> Option<String> {
+        self.textures.state(url).map(|state| String::from(match state {
+            LoadState::Pending => "pending",
+            LoadState::Loaded => "loaded",
+            LoadState::Failed(_) => "failed"
+        }))
+    }
+
+    /// Number of textures still loading
+    pub fn pending_textures(&self) -> u32 {
+        self.textures.pending() as u32
+    }
+
+    /// Texture the globe with an equirectangular image, such as the Blue
+    /// Marble, instead of the default cube map
+    /// 
+    /// The image spans longitude -180 to 180 from left to right and latitude
+    /// 90 to -90 from top to bottom. The globe shows the orange placeholder
+    /// until the image loaded, see `set_textures_ready_callback`.
+    pub fn set_globe_texture(&mut self, url: &str) -> Result<(), JsValue> {
+        set_globe_texture(&self.backend, &self.program_manager, &mut self.textures, &mut self.scene, self.globe, url)?;
+
+        Ok(())
+    }
+
+    /// Turn the mipmaps of the globe texture on or off
+    /// 
+    /// Mipmaps keep the globe from shimmering when zoomed out, at the cost
+    /// of a slightly blurrier image. The default cube map has none, an
+    /// image set with `set_globe_texture` has them.
+    pub fn set_globe_mipmaps(&mut self, enabled: bool) -> Result<(), JsValue> {
+        set_globe_mipmaps(&self.backend, &mut self.textures, &self.scene, self.globe, enabled)?;
+
+        Ok(())
+    }
+
+    pub fn render(&mut self) -> Result<(), JsValue> {
+        self.backend.clear();
+        self.scene.update_transforms();
+        self.scene.render(&self.backend, &self.camera);
+        Ok(())
+    }
+
+    /// Add a fireball marker to the globe and return its ID
+    /// 
+    /// `alt` is measured in globe units, where `1.0` is the radius of the
+    /// earth. The ID can be passed to `update_fireball` and `remove_fireball`.
+    pub fn add_fireball(&mut self, lat: f32, lon: f32, alt: f32) -> Result<u32, JsValue> {
+        let id = self.spawn_fireball(FireballRecord::new(lat, lon, Some(alt * self.earth.radius_km())))?;
+        self.fireballs.style_added(&mut self.scene, id);
+
+        Ok(id)
+    }
+
+    /// Add every fireball in a CNEOS JSON document
+    /// 
+    /// Accepts the response of the CNEOS fireball API or an array of
+    /// objects using the same field names. Returns an object with the
+    /// `ids` of the added fireballs and a list of `errors`, each with the
+    /// `row` and `message` of a record that could not be read or added.
+    pub fn load_fireballs_json(&mut self, src: &str) -> Result<JsValue, JsValue> {
+        let import = cneos::parse_json(src).map_err(|e| JsValue::from_str(&e))?;
+
+        self.load_fireballs(import)
+    }
+
+    /// Add every fireball in a CNEOS CSV export
+    /// 
+    /// See `load_fireballs_json` for the returned value.
+    pub fn load_fireballs_csv(&mut self, src: &str) -> Result<JsValue, JsValue> {
+        let import = cneos::parse_csv(src).map_err(|e| JsValue::from_str(&e))?;
+
+        self.load_fireballs(import)
+    }
+
+    /// Get the record of a fireball as a JavaScript object
+    pub fn get_fireball(&self, id: u32) -> Result<JsValue, JsValue> {
+        self.fireballs.get(id)
+            .map(|fireball| fireball.record.to_js())
+            .ok_or_else(|| JsValue::from_str(&format!("No fireball with id {}", id)))
+    }
+
+    /// Remove a fireball from the globe
+    /// 
+    /// Returns `false` if there is no fireball with the ID.
+    pub fn remove_fireball(&mut self, id: u32) -> bool {
+        match self.fireballs.remove(id) {
+            Some(fireball) => fireball.despawn(&mut self.scene, &self.backend),
+            None => false
+        }
+    }
+
+    /// Move an existing fireball to a new location
+    pub fn update_fireball(&mut self, id: u32, lat: f32, lon: f32, alt: f32) -> Result<(), JsValue> {
+        let fireball = self.fireballs.get_mut(id)
+            .ok_or_else(|| JsValue::from_str(&format!("No fireball with id {}", id)))?;
+
+        fireball.move_to(&mut self.scene, lat, lon, Some(alt * self.earth.radius_km()), &self.earth);
+
         Ok(())
     }
 
-    pub fn set_filter(_list: Vec<usize>) {
-        // Enable or disable certain renderer elements
+    /// Remove every fireball from the globe
+    pub fn clear_fireballs(&mut self) {
+        clear_fireballs(&self.backend, &mut self.scene, &mut self.fireballs);
+    }
+
+    /// Only render the fireballs with the IDs in `ids`
+    /// 
+    /// Hidden fireballs keep their GPU buffers, so changing the filter is
+    /// cheap. Use `clear_filter` to show every fireball again.
+    pub fn set_filter(&mut self, ids: Vec<u32>) {
+        self.fireballs.set_filter(&mut self.scene, Some(ids.into_iter().collect()));
+    }
+
+    /// Remove the filter set by `set_filter`
+    pub fn clear_filter(&mut self) {
+        self.fireballs.set_filter(&mut self.scene, None);
+    }
+
+    /// Size and color every fireball marker by one of its attributes
+    /// 
+    /// Sizes are multipliers of the default marker size. Values of the
+    /// attribute are mapped onto `[min_size, max_size]` and the color ramp
+    /// after applying the scale mode.
+    pub fn set_marker_style(
+        &mut self,
+        attribute: StyleAttribute,
+        scale: ScaleMode,
+        ramp: ColorRamp,
+        min_size: f32,
+        max_size: f32
+    ) {
+        let style = MarkerStyle { attribute, scale, ramp, min_size, max_size };
+        self.fireballs.set_style(&mut self.scene, style);
+    }
+
+    /// Place fireballs on a spherical earth with a radius in km, the default
+    /// with a radius of 6371 km
+    /// 
+    /// The globe is one radius across, so this only changes how far
+    /// altitudes in km lift the markers.
+    pub fn set_earth_sphere(&mut self, radius_km: f32) -> Result<(), JsValue> {
+        if !radius_km.is_finite() || radius_km <= 0.0 {
+            return Err(JsValue::from_str(&format!("radius {} is not positive", radius_km)));
+        }
 
+        self.set_earth(EarthModel::Sphere { radius_km });
+        Ok(())
+    }
+
+    /// Place fireballs on the WGS84 ellipsoid, which is flattened towards
+    /// the poles by about 21 km
+    pub fn set_earth_wgs84(&mut self) {
+        self.set_earth(EarthModel::Wgs84);
     }
 }
 
-fn precompile_shaders(gl: &WebGl2RenderingContext) -> ShaderManager {
-    let mut shader_manager = ShaderManager::new();
+impl App {
+    /// Cast a ray through the canvas position `pos` into the scene
+    /// 
+    /// `pos` is in CSS pixels relative to the top left of the canvas.
+    fn pick(&mut self, pos: Vector2) -> Option<Pick> {
+        let width = self.canvas.client_width() as f32;
+        let height = self.canvas.client_height() as f32;
+
+        let ray = self.camera.canvas_ray(pos, width, height)?;
+        self.scene.update_transforms();
+
+        picking::pick(&self.scene, self.globe, &self.fireballs, &ray, &self.earth)
+    }
+
+    /// Change the earth model and move every fireball onto it
+    fn set_earth(&mut self, earth: EarthModel) {
+        self.earth = earth;
+
+        for (_, fireball) in self.fireballs.iter() {
+            fireball.place(&mut self.scene, &self.earth);
+        }
+    }
+
+    /// Fly halfway towards the globe point or fireball at `pos`
+    fn fly_to_pick(&mut self, pos: Vector2) {
+        let (lat, lon) = match self.pick(pos) {
+            Some(Pick::Globe { lat, lon, .. }) => (lat, lon),
+            Some(Pick::Fireball { id, .. }) => match self.fireballs.get(id) {
+                Some(fireball) => (fireball.record.lat, fireball.record.lon),
+                None => return
+            },
+            None => return
+        };
+
+        let distance = 1.0 + (self.orbit.distance() - 1.0) * 0.5;
+        self.orbit.fly_to(lat, lon, distance, 1.0);
+    }
+
+    /// Pick at a clicked position and pass the result to the click callback
+    fn handle_click(&mut self, pos: Vector2) -> Result<(), JsValue> {
+        if self.click_callback.is_none() {
+            return Ok(());
+        }
+
+        let event = match self.pick(pos) {
+            Some(Pick::Fireball { id, .. }) => {
+                let event = js_sys::Object::new();
+                let _ = js_sys::Reflect::set(&event, &JsValue::from_str("type"), &JsValue::from_str("fireball"));
+                let _ = js_sys::Reflect::set(&event, &JsValue::from_str("id"), &JsValue::from(id));
+                if let Some(fireball) = self.fireballs.get(id) {
+                    let _ = js_sys::Reflect::set(&event, &JsValue::from_str("record"), &fireball.record.to_js());
+                }
+
+                event.into()
+            },
+            Some(Pick::Globe { lat, lon, .. }) => {
+                let event = js_sys::Object::new();
+                let _ = js_sys::Reflect::set(&event, &JsValue::from_str("type"), &JsValue::from_str("globe"));
+                let _ = js_sys::Reflect::set(&event, &JsValue::from_str("lat"), &JsValue::from(lat));
+                let _ = js_sys::Reflect::set(&event, &JsValue::from_str("lon"), &JsValue::from(lon));
+
+                event.into()
+            },
+            None => JsValue::NULL
+        };
+
+        if let Some(callback) = &self.click_callback {
+            callback.call1(&JsValue::NULL, &event)?;
+        }
+
+        Ok(())
+    }
+
+    /// Pick the fireball under the mouse and highlight it
+    /// 
+    /// Calls the hover callback if the hovered fireball changed. `pos` is
+    /// `None` when nothing should be hovered.
+    fn update_hover(&mut self, pos: Option<Vector2>) -> Result<(), JsValue> {
+        let hovered = match pos.and_then(|pos| self.pick(pos)) {
+            Some(Pick::Fireball { id, .. }) => Some(id),
+            _ => None
+        };
+
+        if hovered == self.hovered {
+            return Ok(());
+        }
+
+        self.hovered = hovered;
+        self.fireballs.set_highlighted(&self.scene, hovered);
+
+        if let Some(callback) = &self.hover_callback {
+            let id = hovered.map(JsValue::from).unwrap_or(JsValue::NULL);
+            callback.call1(&JsValue::NULL, &id)?;
+        }
+
+        Ok(())
+    }
+
+    /// Poll the textures and call the ready callback once all finished
+    fn update_textures(&mut self) -> Result<(), JsValue> {
+        if !self.textures.update(&self.backend) {
+            return Ok(());
+        }
+
+        if let Some(callback) = &self.textures_ready_callback {
+            let errors = js_sys::Array::new();
+            for error in self.textures.failed() {
+                errors.push(&JsValue::from_str(error));
+            }
+            callback.call1(&JsValue::NULL, &errors)?;
+        }
 
-    let shader = compile_shader(
-        &gl, 
-        WebGl2RenderingContext::FRAGMENT_SHADER, 
-        frag_shaders::simple_unlit::SHADER
-    ).unwrap();
+        Ok(())
+    }
 
-    shader_manager.expose_shader(shader, "frag_simple_unlit");
+    fn spawn_fireball(&mut self, record: FireballRecord) -> Result<u32, RendererError> {
+        let marker = fireball_marker(&self.backend, &self.program_manager)?;
+        let fireball = Fireball::spawn(&mut self.scene, self.globe, record, marker, &self.earth);
+        let id = self.fireballs.insert(fireball);
+        self.fireballs.apply_filter(&mut self.scene, id);
+
+        Ok(id)
+    }
 
-    let shader = compile_shader(
-        &gl, 
-        WebGl2RenderingContext::FRAGMENT_SHADER, 
-        frag_shaders::simple_unlit_shaded::SHADER
-    ).unwrap();
+    fn load_fireballs(&mut self, import: Import) -> Result<JsValue, JsValue> {
+        let mut errors = import.errors;
+        let ids = js_sys::Array::new();
+
+        // A record that cannot be spawned is reported like one that cannot
+        // be parsed, so the others still show up and get styled
+        for (record, row) in import.records.into_iter().zip(import.rows) {
+            match self.spawn_fireball(record) {
+                Ok(id) => {
+                    ids.push(&JsValue::from(id));
+                }
+                Err(error) => errors.push(ImportError { row, message: error.to_string() })
+            }
+        }
+        self.fireballs.restyle(&mut self.scene);
 
-    shader_manager.expose_shader(shader, "frag_simple_unlit_shaded");
+        errors.sort_by_key(|error| error.row);
+        let errors: js_sys::Array = errors.iter().map(ImportError::to_js).collect();
 
-    let shader = compile_shader(
-        &gl, 
-        WebGl2RenderingContext::VERTEX_SHADER, 
-        vert_shaders::vert_shader_3d::SHADER
-    ).unwrap();
+        let result = js_sys::Object::new();
+        let _ = js_sys::Reflect::set(&result, &JsValue::from_str("ids"), &ids);
+        let _ = js_sys::Reflect::set(&result, &JsValue::from_str("errors"), &errors);
 
-    shader_manager.expose_shader(shader, "vert_3d");
+        Ok(result.into())
+    }
+}
 
-    shader_manager
+/// Add the textured globe to `scene` as a new node named `"globe"` and
+/// return it
+fn build_globe(
+    backend: &dyn GraphicsBackend,
+    program_manager: &ShaderProgramManager,
+    textures: &mut TextureManager,
+    scene: &mut SceneGraph
+) -> Result<NodeId, RendererError> {
+    // One image per face, in the order of `CubeFace::ALL`
+    let cube = TextureCube::from_faces(backend, textures, [
+        "/res/world_cube_net_strip2.png",
+        "/res/world_cube_net_strip4.png",
+        "/res/world_cube_net_strip6.png",
+        "/res/world_cube_net_strip5.png",
+        "/res/world_cube_net_strip1.png",
+        "/res/world_cube_net_strip3.png",
+    ])?;
+
+    let renderer = MeshRenderer::new(
+        backend,
+        Mesh::quad_sphere(32),
+        Box::new(UnlitCubeMap3D::new(cube, program_manager)?)
+    )?;
+
+    let mut globe = Node::named("globe");
+    globe.add_renderer(renderer);
+
+    Ok(scene.add_child(scene.root(), globe))
 }
 
-fn link_programs(gl: &WebGl2RenderingContext, shader_manager: &ShaderManager) -> ShaderProgramManager {
-    let mut program_manager = ShaderProgramManager::new();
+/// Swap the material of the globe for one showing the equirectangular
+/// image at `url`
+fn set_globe_texture(
+    backend: &dyn GraphicsBackend,
+    program_manager: &ShaderProgramManager,
+    textures: &mut TextureManager,
+    scene: &mut SceneGraph,
+    globe: NodeId,
+    url: &str
+) -> Result<(), RendererError> {
+    let material = UnlitEquirectangular3D::new(backend, textures, url, program_manager)?;
+
+    scene[globe].renderer_mut()
+        .ok_or_else(|| RendererError::Resource(String::from("The globe has no renderer")))?
+        .set_material(backend, Box::new(material))
+}
 
-    let program = common::link_program(
-        &gl, 
-        shader_manager.get_shader("vert_3d"), 
-        shader_manager.get_shader("frag_simple_unlit")
-    ).unwrap();
+/// Change the sampler of the globe texture to use mipmaps or not
+fn set_globe_mipmaps(
+    backend: &dyn GraphicsBackend,
+    textures: &mut TextureManager,
+    scene: &SceneGraph,
+    globe: NodeId,
+    enabled: bool
+) -> Result<(), RendererError> {
+    let texture = scene[globe].renderer()
+        .and_then(|renderer| renderer.material().texture())
+        .ok_or_else(|| RendererError::Resource(String::from("The globe has no texture")))?
+        .clone();
+
+    let sampler = textures.sampler(&texture).unwrap_or_default();
+    textures.set_sampler(backend, &texture, SamplerDesc { mipmaps: enabled, ..sampler });
 
-    program_manager.expose_program(program, "simple_unlit");
+    Ok(())
+}
+
+/// Despawn every fireball of `fireballs`, freeing their GPU resources
+fn clear_fireballs(backend: &dyn GraphicsBackend, scene: &mut SceneGraph, fireballs: &mut FireballRegistry) {
+    for fireball in fireballs.drain() {
+        fireball.despawn(scene, backend);
+    }
+}
+
+/// Node holding the renderer of a fireball marker
+fn fireball_marker(backend: &dyn GraphicsBackend, program_manager: &ShaderProgramManager) -> Result<Node, RendererError> {
+    let mut marker = Node::new();
+    marker.add_renderer(
+        MeshRenderer::new(
+            backend,
+            Mesh::fireball(),
+            Box::new(Unlit3D::new(program_manager)?)
+        )?
+    );
+
+    Ok(marker)
+}
+
+fn precompile_shaders(backend: &dyn GraphicsBackend) -> Result<ShaderManager, RendererError> {
+    let mut shader_manager = ShaderManager::new();
+
+    let shaders = [
+        (ShaderKind::Fragment, frag_shaders::simple_unlit::SHADER, "frag_simple_unlit"),
+        (ShaderKind::Fragment, frag_shaders::simple_unlit_shaded::SHADER, "frag_simple_unlit_shaded"),
+        (ShaderKind::Fragment, frag_shaders::cube_map::SHADER, "frag_cube_map"),
+        (ShaderKind::Fragment, frag_shaders::equirectangular::SHADER, "frag_equirectangular"),
+        (ShaderKind::Vertex, vert_shaders::vert_shader_3d::SHADER, "vert_3d"),
+    ];
 
-    let program = common::link_program(
-        &gl,
-        shader_manager.get_shader("vert_3d"),
-        shader_manager.get_shader("frag_simple_unlit_shaded")
-    ).unwrap();
+    for (kind, source, name) in shaders {
+        let shader = backend.compile_shader(kind, source)
+            .map_err(|log| RendererError::ShaderCompile { name: String::from(name), log })?;
 
-    program_manager.expose_program(program, "textured_lit");
+        shader_manager.expose_shader(shader, name);
+    }
+
+    Ok(shader_manager)
+}
+
+fn link_programs(backend: &dyn GraphicsBackend, shader_manager: &ShaderManager) -> Result<ShaderProgramManager, RendererError> {
+    let mut program_manager = ShaderProgramManager::new();
 
-    program_manager
+    let programs = [
+        ("vert_3d", "frag_simple_unlit", "simple_unlit"),
+        ("vert_3d", "frag_simple_unlit_shaded", "textured_lit"),
+        ("vert_3d", "frag_cube_map", "cube_mapped"),
+        ("vert_3d", "frag_equirectangular", "equirectangular"),
+    ];
+
+    for (vert_shader, frag_shader, name) in programs {
+        let program = backend.link_program(
+            shader_manager.get_shader(vert_shader)?,
+            shader_manager.get_shader(frag_shader)?
+        ).map_err(|log| RendererError::ProgramLink { name: String::from(name), log })?;
+
+        program_manager.expose_program(program, name);
+    }
+
+    Ok(program_manager)
 }
 
 pub fn js_log(_msg: &str) {
     // log(msg)
 }
 
-pub fn register_mouse_events(canvas: &HtmlCanvasElement) -> Result<(), JsValue> {
-    
+/// Register the pointer and keyboard event handlers of an `App` on its canvas
+/// 
+/// Uses pointer events, so mouse, touch and pen feed the same state. The
+/// handlers only write to `input`, which is read by `App::update`.
+pub fn register_input_events(canvas: &HtmlCanvasElement, input: &Rc<RefCell<InputState>>) -> Result<(), JsValue> {
+
+    // Keep the browser from scrolling or zooming the page on touch
+    canvas.style().set_property("touch-action", "none")?;
+
+    let state = input.clone();
     let mouse_wheel_handler = move |event: web_sys::WheelEvent| {
-        log(&format!("{}", event.delta_y()));
-        // Move camera by sign of delta_y
-        move_camera(
-            Vector3::new(
-                0.0, 
-                0.0, 
-                (event.delta_y() * 0.01) as f32
-            )
-        );
+        if state.borrow_mut().process(InputEvent::Wheel { delta: event.delta_y() as f32 }) {
+            event.prevent_default();
+        }
     };
 
     let mouse_wheel_handler = Closure::wrap(Box::new(mouse_wheel_handler) as Box<dyn FnMut(_)>);
     canvas.add_event_listener_with_callback("wheel", mouse_wheel_handler.as_ref().unchecked_ref())?;
     mouse_wheel_handler.forget();
 
-    let mouse_down_handler = move |_event: web_sys::MouseEvent| {
-        // 0 = left
-        // 1 = middle
-        // 2 = right
-        set_mouse_down(true);
+    let state = input.clone();
+    let target = canvas.clone();
+    let pointer_down_handler = move |event: web_sys::PointerEvent| {
+        // Keep receiving moves of this pointer when it leaves the canvas
+        let _ = target.set_pointer_capture(event.pointer_id());
+        state.borrow_mut().process(InputEvent::PointerDown {
+            id: event.pointer_id(),
+            x: event.offset_x() as f32,
+            y: event.offset_y() as f32
+        });
+    };
+
+    let pointer_down_handler = Closure::wrap(Box::new(pointer_down_handler) as Box<dyn FnMut(_)>);
+    canvas.add_event_listener_with_callback("pointerdown", pointer_down_handler.as_ref().unchecked_ref())?;
+    pointer_down_handler.forget();
+
+    let state = input.clone();
+    let pointer_up_handler = move |event: web_sys::PointerEvent| {
+        state.borrow_mut().process(InputEvent::PointerUp {
+            id: event.pointer_id(),
+            x: event.offset_x() as f32,
+            y: event.offset_y() as f32,
+            time: event.time_stamp(),
+            touch: event.pointer_type() == "touch"
+        });
+    };
+
+    let pointer_up_handler = Closure::wrap(Box::new(pointer_up_handler) as Box<dyn FnMut(_)>);
+    canvas.add_event_listener_with_callback("pointerup", pointer_up_handler.as_ref().unchecked_ref())?;
+    pointer_up_handler.forget();
+
+    let state = input.clone();
+    let pointer_cancel_handler = move |_event: web_sys::PointerEvent| {
+        state.borrow_mut().process(InputEvent::PointerCancel);
+    };
+
+    let pointer_cancel_handler = Closure::wrap(Box::new(pointer_cancel_handler) as Box<dyn FnMut(_)>);
+    canvas.add_event_listener_with_callback("pointercancel", pointer_cancel_handler.as_ref().unchecked_ref())?;
+    pointer_cancel_handler.forget();
+
+    let state = input.clone();
+    let pointer_move_handler = move |event: web_sys::PointerEvent| {
+        state.borrow_mut().process(InputEvent::PointerMove {
+            id: event.pointer_id(),
+            x: event.offset_x() as f32,
+            y: event.offset_y() as f32
+        });
     };
 
-    let mouse_down_handler = Closure::wrap(Box::new(mouse_down_handler) as Box<dyn FnMut(_)>);
-    canvas.add_event_listener_with_callback("mousedown", mouse_down_handler.as_ref().unchecked_ref())?;
-    mouse_down_handler.forget();
+    let pointer_move_handler = Closure::wrap(Box::new(pointer_move_handler) as Box<dyn FnMut(_)>);
+    canvas.add_event_listener_with_callback("pointermove", pointer_move_handler.as_ref().unchecked_ref())?;
+    pointer_move_handler.forget(); // forgor
 
-    let mouse_up_handler = move |_event: web_sys::MouseEvent| {
-        set_mouse_down(false);
-        if get_mouse_drag() {
-            log("drag")
-        } else {
-            log("click")
+    let state = input.clone();
+    let pointer_leave_handler = move |_event: web_sys::PointerEvent| {
+        state.borrow_mut().process(InputEvent::PointerLeave);
+    };
+
+    let pointer_leave_handler = Closure::wrap(Box::new(pointer_leave_handler) as Box<dyn FnMut(_)>);
+    canvas.add_event_listener_with_callback("pointerleave", pointer_leave_handler.as_ref().unchecked_ref())?;
+    pointer_leave_handler.forget();
+
+    // Let the canvas take focus, so it receives key events
+    if !canvas.has_attribute("tabindex") {
+        canvas.set_attribute("tabindex", "0")?;
+    }
+
+    let state = input.clone();
+    let key_down_handler = move |event: web_sys::KeyboardEvent| {
+        // Keep bound keys like the arrows from scrolling the page
+        if state.borrow_mut().process(InputEvent::Key { key: event.key(), down: true }) {
+            event.prevent_default();
         }
-        set_mouse_drag(false);
     };
 
-    let mouse_up_handler = Closure::wrap(Box::new(mouse_up_handler) as Box<dyn FnMut(_)>);
-    canvas.add_event_listener_with_callback("mouseup", mouse_up_handler.as_ref().unchecked_ref())?;
-    mouse_up_handler.forget();
+    let key_down_handler = Closure::wrap(Box::new(key_down_handler) as Box<dyn FnMut(_)>);
+    canvas.add_event_listener_with_callback("keydown", key_down_handler.as_ref().unchecked_ref())?;
+    key_down_handler.forget();
 
-    let mouse_move_handler = move |event: web_sys::MouseEvent| {
-        set_mouse_pos(event.screen_x() as f32, event.screen_y() as f32);
-        set_mouse_drag(true);
+    let state = input.clone();
+    let key_up_handler = move |event: web_sys::KeyboardEvent| {
+        state.borrow_mut().process(InputEvent::Key { key: event.key(), down: false });
     };
 
-    let mouse_move_handler = Closure::wrap(Box::new(mouse_move_handler) as Box<dyn FnMut(_)>);
-    canvas.add_event_listener_with_callback("mousemove", mouse_move_handler.as_ref().unchecked_ref())?;
-    mouse_move_handler.forget(); // forgor
+    let key_up_handler = Closure::wrap(Box::new(key_up_handler) as Box<dyn FnMut(_)>);
+    canvas.add_event_listener_with_callback("keyup", key_up_handler.as_ref().unchecked_ref())?;
+    key_up_handler.forget();
+
+    // Key up events are lost while the canvas is not focused
+    let state = input.clone();
+    let blur_handler = move |_event: web_sys::Event| {
+        state.borrow_mut().process(InputEvent::Blur);
+    };
+
+    let blur_handler = Closure::wrap(Box::new(blur_handler) as Box<dyn FnMut(_)>);
+    canvas.add_event_listener_with_callback("blur", blur_handler.as_ref().unchecked_ref())?;
+    blur_handler.forget();
 
     Ok(())
     
 }
-
-fn update_camera()
-{
-    update_projection_matrix();
-    update_view_matrix();
-}
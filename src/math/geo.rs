@@ -0,0 +1,260 @@
+//! Geographic coordinates and their position on the globe
+//!
+//! Globe space has the north pole on +y, latitude 0 / longitude 0 on -z
+//! and longitude 90 east on -x. One globe unit is the equatorial radius of
+//! the `EarthModel`, so the equator lies on the unit sphere, and with the
+//! default model the whole surface does.
+
+use super::vec3::Vector3;
+
+/// Mean radius of the earth, used to turn kilometers into globe units
+pub const EARTH_RADIUS_KM: f32 = 6371.0;
+
+/// Semi-major axis of the WGS84 ellipsoid in km
+pub const WGS84_A_KM: f64 = 6378.137;
+
+/// Flattening of the WGS84 ellipsoid
+pub const WGS84_F: f64 = 1.0 / 298.257223563;
+
+/// Shape of the earth used to convert between coordinates and globe space
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EarthModel {
+    /// A sphere with a radius in km
+    Sphere { radius_km: f32 },
+    /// The WGS84 ellipsoid, slightly flattened at the poles
+    Wgs84
+}
+
+#[allow(dead_code)]
+impl EarthModel {
+    /// Equatorial radius in km, the length of one globe unit
+    pub fn radius_km(&self) -> f32 {
+        self.ellipsoid().0 as f32
+    }
+
+    /// Semi-major axis and squared eccentricity of the model in km
+    fn ellipsoid(&self) -> (f64, f64) {
+        match self {
+            EarthModel::Sphere { radius_km } => (*radius_km as f64, 0.0),
+            EarthModel::Wgs84 => (WGS84_A_KM, WGS84_F * (2.0 - WGS84_F))
+        }
+    }
+}
+
+impl Default for EarthModel {
+    fn default() -> Self {
+        EarthModel::Sphere { radius_km: EARTH_RADIUS_KM }
+    }
+}
+
+/// A point given by latitude and longitude in degrees and altitude in km
+///
+/// Latitude is positive north, longitude positive east. The altitude is
+/// measured above the surface of the earth model.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct GeoCoord {
+    pub lat: f32,
+    pub lon: f32,
+    pub alt_km: f32
+}
+
+#[allow(dead_code)]
+impl GeoCoord {
+    pub fn new(lat: f32, lon: f32, alt_km: f32) -> Self {
+        Self { lat, lon, alt_km }
+    }
+
+    /// Check that latitude and longitude are in range
+    pub fn validate(&self) -> Result<(), String> {
+        if !(-90.0..=90.0).contains(&self.lat) {
+            return Err(format!("lat {} is out of range", self.lat));
+        }
+
+        if !(-180.0..=180.0).contains(&self.lon) {
+            return Err(format!("lon {} is out of range", self.lon));
+        }
+
+        Ok(())
+    }
+
+    /// Position of the coordinate in globe space
+    pub fn to_globe(self, model: &EarthModel) -> Vector3 {
+        let (a, e2) = model.ellipsoid();
+        let (lat, lon) = ((self.lat as f64).to_radians(), (self.lon as f64).to_radians());
+        let h = self.alt_km as f64;
+
+        // Radius of curvature in the prime vertical
+        let n = a / (1.0 - e2 * lat.sin() * lat.sin()).sqrt();
+
+        let x = (n + h) * lat.cos() * lon.cos();
+        let y = (n + h) * lat.cos() * lon.sin();
+        let z = (n * (1.0 - e2) + h) * lat.sin();
+
+        from_ecef(x, y, z, model)
+    }
+
+    /// Coordinate of a point in globe space
+    ///
+    /// Returns the origin as latitude and longitude 0 at an altitude of
+    /// minus the radius.
+    pub fn from_globe(p: Vector3, model: &EarthModel) -> Self {
+        let (a, e2) = model.ellipsoid();
+        let (x, y, z) = to_ecef(p, model);
+
+        let lon = y.atan2(x);
+        let r = (x * x + y * y).sqrt();
+
+        if e2 == 0.0 {
+            let lat = z.atan2(r);
+            let h = (r * r + z * z).sqrt() - a;
+
+            return Self::new(lat.to_degrees() as f32, lon.to_degrees() as f32, h as f32);
+        }
+
+        // Iterate from the spherical latitude, converges in a few steps
+        let mut lat = z.atan2(r * (1.0 - e2));
+        let mut h = 0.0;
+
+        for _ in 0..5 {
+            let n = a / (1.0 - e2 * lat.sin() * lat.sin()).sqrt();
+
+            h = if lat.cos().abs() > 1e-9 {
+                r / lat.cos() - n
+            } else {
+                z.abs() - n * (1.0 - e2)
+            };
+
+            lat = z.atan2(r * (1.0 - e2 * n / (n + h)));
+        }
+
+        Self::new(lat.to_degrees() as f32, lon.to_degrees() as f32, h as f32)
+    }
+
+    /// Distance along the surface of a sphere with `EARTH_RADIUS_KM` in km
+    ///
+    /// Ignores the altitude of both coordinates.
+    pub fn great_circle_distance(&self, other: &GeoCoord) -> f32 {
+        let (lat1, lat2) = ((self.lat as f64).to_radians(), (other.lat as f64).to_radians());
+        let d_lat = lat2 - lat1;
+        let d_lon = ((other.lon - self.lon) as f64).to_radians();
+
+        // Haversine formula, accurate for short distances
+        let h = (d_lat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (d_lon / 2.0).sin().powi(2);
+        let angle = 2.0 * h.sqrt().min(1.0).asin();
+
+        (angle * EARTH_RADIUS_KM as f64) as f32
+    }
+
+    /// Initial bearing of the great circle path to `other`
+    ///
+    /// In degrees clockwise from north, in `[0, 360)`.
+    pub fn initial_bearing(&self, other: &GeoCoord) -> f32 {
+        let (lat1, lat2) = ((self.lat as f64).to_radians(), (other.lat as f64).to_radians());
+        let d_lon = ((other.lon - self.lon) as f64).to_radians();
+
+        let y = d_lon.sin() * lat2.cos();
+        let x = lat1.cos() * lat2.sin() - lat1.sin() * lat2.cos() * d_lon.cos();
+
+        (y.atan2(x).to_degrees().rem_euclid(360.0) as f32) % 360.0
+    }
+}
+
+/// Earth centered, earth fixed km to globe space of `model`
+fn from_ecef(x: f64, y: f64, z: f64, model: &EarthModel) -> Vector3 {
+    let scale = 1.0 / model.ellipsoid().0;
+
+    Vector3::new((-y * scale) as f32, (z * scale) as f32, (-x * scale) as f32)
+}
+
+/// Globe space of `model` to earth centered, earth fixed km
+fn to_ecef(p: Vector3, model: &EarthModel) -> (f64, f64, f64) {
+    let scale = model.ellipsoid().0;
+
+    (-p.data[2] as f64 * scale, -p.data[0] as f64 * scale, p.data[1] as f64 * scale)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_geo_eq(a: GeoCoord, b: GeoCoord) {
+        assert!((a.lat - b.lat).abs() < 1e-3, "{:?} != {:?}", a, b);
+        assert!((a.lon - b.lon).abs() < 1e-3, "{:?} != {:?}", a, b);
+        assert!((a.alt_km - b.alt_km).abs() < 1e-2, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn test_sphere_axes() {
+        let model = EarthModel::default();
+        let p = |lat, lon| GeoCoord::new(lat, lon, 0.0).to_globe(&model);
+
+        assert!((p(0.0, 0.0) - Vector3::new(0.0, 0.0, -1.0)).magnitude() < 1e-6);
+        assert!((p(90.0, 0.0) - Vector3::up()).magnitude() < 1e-6);
+        assert!((p(0.0, 90.0) - Vector3::new(-1.0, 0.0, 0.0)).magnitude() < 1e-6);
+
+        let high = GeoCoord::new(0.0, 0.0, EARTH_RADIUS_KM).to_globe(&model);
+        assert!((high.magnitude() - 2.0).abs() < 1e-6);
+
+        // Altitudes are relative to the radius of the model
+        let small = EarthModel::Sphere { radius_km: 1000.0 };
+        let high = GeoCoord::new(45.0, 30.0, 500.0).to_globe(&small);
+        assert!((high.magnitude() - 1.5).abs() < 1e-6);
+        assert_geo_eq(GeoCoord::from_globe(high, &small), GeoCoord::new(45.0, 30.0, 500.0));
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let coords = [
+            GeoCoord::new(35.0, -120.0, 30.0),
+            GeoCoord::new(-62.5, 171.2, 0.0),
+            GeoCoord::new(89.9, 10.0, 80.0),
+            GeoCoord::new(-90.0, 0.0, 12.0),
+        ];
+
+        for model in [EarthModel::default(), EarthModel::Wgs84] {
+            for coord in coords {
+                assert_geo_eq(GeoCoord::from_globe(coord.to_globe(&model), &model), coord);
+            }
+        }
+    }
+
+    #[test]
+    fn test_wgs84_is_flattened() {
+        let equator = GeoCoord::new(0.0, 0.0, 0.0).to_globe(&EarthModel::Wgs84).magnitude();
+        let pole = GeoCoord::new(90.0, 0.0, 0.0).to_globe(&EarthModel::Wgs84).magnitude();
+
+        assert!((equator - 1.0).abs() < 1e-6);
+        assert!((pole * EarthModel::Wgs84.radius_km() - 6356.752).abs() < 1e-2);
+    }
+
+    #[test]
+    fn test_great_circle_distance() {
+        let london = GeoCoord::new(51.5074, -0.1278, 0.0);
+        let paris = GeoCoord::new(48.8566, 2.3522, 0.0);
+
+        assert!((london.great_circle_distance(&paris) - 343.5).abs() < 1.0);
+        assert_eq!(london.great_circle_distance(&london), 0.0);
+
+        // A quarter of the way around the earth
+        let quarter = GeoCoord::new(0.0, 0.0, 0.0).great_circle_distance(&GeoCoord::new(0.0, 90.0, 0.0));
+        assert!((quarter - EARTH_RADIUS_KM * std::f32::consts::FRAC_PI_2).abs() < 1e-2);
+    }
+
+    #[test]
+    fn test_initial_bearing() {
+        let origin = GeoCoord::new(0.0, 0.0, 0.0);
+
+        assert!((origin.initial_bearing(&GeoCoord::new(10.0, 0.0, 0.0)) - 0.0).abs() < 1e-4);
+        assert!((origin.initial_bearing(&GeoCoord::new(0.0, 10.0, 0.0)) - 90.0).abs() < 1e-4);
+        assert!((origin.initial_bearing(&GeoCoord::new(-10.0, 0.0, 0.0)) - 180.0).abs() < 1e-4);
+        assert!((origin.initial_bearing(&GeoCoord::new(0.0, -10.0, 0.0)) - 270.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(GeoCoord::new(90.0, -180.0, 0.0).validate().is_ok());
+        assert_eq!(GeoCoord::new(91.0, 0.0, 0.0).validate(), Err(String::from("lat 91 is out of range")));
+        assert_eq!(GeoCoord::new(0.0, 181.0, 0.0).validate(), Err(String::from("lon 181 is out of range")));
+    }
+}
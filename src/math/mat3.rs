@@ -0,0 +1,183 @@
+use std::ops::{Index, IndexMut, Mul};
+
+use super::{mat4::Matrix4, vec3::Vector3};
+
+/// A 3x3 matrix stored in column major order, like `Matrix4`
+///
+/// Mostly used for the normal matrix, which transforms normals by the
+/// rotation and scale of an object without its translation.
+#[derive(Debug, Clone, Copy)]
+pub struct Matrix3 {
+    pub data: [f32; 9]
+}
+
+#[allow(dead_code)]
+impl Matrix3 {
+    /// Create a new 3x3 identity matrix
+    pub fn identity() -> Self {
+        Self { data: [
+            1.0, 0.0, 0.0,
+            0.0, 1.0, 0.0,
+            0.0, 0.0, 1.0
+        ] }
+    }
+
+    /// Upper left 3x3 of a 4x4 matrix, dropping its translation
+    pub fn from_matrix4(m: &Matrix4) -> Self {
+        let mut out = Self::identity();
+
+        for row in 0..3 {
+            for col in 0..3 {
+                out[(row, col)] = m[(row, col)];
+            }
+        }
+
+        out
+    }
+
+    /// Create the normal matrix of a transform
+    ///
+    /// The normal matrix is the inverse transpose of the upper left 3x3 of
+    /// the transform. Unlike the transform itself, it keeps normals
+    /// perpendicular to the surface under non-uniform scale. Returns `None`
+    /// if the transform can not be inverted.
+    pub fn normal_matrix(transform: &Matrix4) -> Option<Self> {
+        Some(Self::from_matrix4(transform).inverse()?.transpose())
+    }
+
+    pub fn transpose(self) -> Self {
+        let mut out = self;
+
+        for row in 0..3 {
+            for col in 0..3 {
+                out[(row, col)] = self[(col, row)];
+            }
+        }
+
+        out
+    }
+
+    pub fn determinant(&self) -> f32 {
+        let m = |row, col| self[(row, col)];
+
+        m(0, 0) * (m(1, 1) * m(2, 2) - m(1, 2) * m(2, 1)) -
+        m(0, 1) * (m(1, 0) * m(2, 2) - m(1, 2) * m(2, 0)) +
+        m(0, 2) * (m(1, 0) * m(2, 1) - m(1, 1) * m(2, 0))
+    }
+
+    /// Invert the matrix
+    ///
+    /// Returns `None` if the matrix is singular.
+    pub fn inverse(&self) -> Option<Self> {
+        let det = self.determinant();
+
+        if det == 0.0 {
+            return None;
+        }
+
+        let m = |row, col| self[(row, col)];
+        let mut out = Self::identity();
+
+        // Transposed cofactors divided by the determinant
+        for row in 0..3 {
+            for col in 0..3 {
+                let (r0, r1) = ((col + 1) % 3, (col + 2) % 3);
+                let (c0, c1) = ((row + 1) % 3, (row + 2) % 3);
+
+                out[(row, col)] = (m(r0, c0) * m(r1, c1) - m(r0, c1) * m(r1, c0)) / det;
+            }
+        }
+
+        Some(out)
+    }
+
+    /// Transform a direction by the matrix
+    pub fn transform_vector(&self, v: Vector3) -> Vector3 {
+        let mut out = Vector3::zero();
+
+        for row in 0..3 {
+            out[row] =
+                self[(row, 0)] * v[0] +
+                self[(row, 1)] * v[1] +
+                self[(row, 2)] * v[2];
+        }
+
+        out
+    }
+}
+
+impl Default for Matrix3 {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
+impl Index<(usize, usize)> for Matrix3 {
+    type Output = f32;
+
+    fn index(&self, index: (usize, usize)) -> &Self::Output {
+        &self.data[ index.0 + 3 * index.1 ]
+    }
+}
+
+impl IndexMut<(usize, usize)> for Matrix3 {
+    fn index_mut(&mut self, index: (usize, usize)) -> &mut Self::Output {
+        &mut self.data[ index.0 + 3 * index.1 ]
+    }
+}
+
+impl Mul<Matrix3> for Matrix3 {
+    type Output = Matrix3;
+
+    /// Multiply in the same order as `Matrix4`, so `a * b` applies `a` first
+    fn mul(self, rhs: Matrix3) -> Self::Output {
+        let mut out = Matrix3::identity();
+
+        for row in 0..3 {
+            for col in 0..3 {
+                out[(row, col)] =
+                    rhs[(row, 0)] * self[(0, col)] +
+                    rhs[(row, 1)] * self[(1, col)] +
+                    rhs[(row, 2)] * self[(2, col)];
+            }
+        }
+
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matrix3_inverse() {
+        let m = Matrix3 { data: [
+            2.0, 0.0, 1.0,
+            1.0, 3.0, 0.0,
+            0.0, 1.0, 4.0
+        ] };
+
+        assert_eq!(m.determinant(), 25.0);
+
+        let identity = m * m.inverse().unwrap();
+        for (a, b) in identity.data.iter().zip(Matrix3::identity().data.iter()) {
+            assert!((a - b).abs() < 1e-6);
+        }
+
+        assert!(Matrix3 { data: [0.0; 9] }.inverse().is_none());
+    }
+
+    #[test]
+    fn test_normal_matrix_keeps_normals_perpendicular() {
+        // Squash a 45 degree slope, the surface and its normal
+        let transform = Matrix4::scale(1.0, 0.5, 1.0) * Matrix4::translate(3.0, 2.0, 1.0);
+        let tangent = transform.transform_vector(Vector3::new(1.0, 1.0, 0.0));
+
+        let normal = Matrix3::normal_matrix(&transform).unwrap()
+            .transform_vector(Vector3::new(-1.0, 1.0, 0.0));
+
+        assert_eq!(Vector3::dot(tangent, normal), 0.0);
+        assert!(Matrix3::normal_matrix(&Matrix4::scale(0.0, 1.0, 1.0)).is_none());
+    }
+}
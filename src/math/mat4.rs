@@ -147,6 +147,8 @@ impl Matrix4 {
     }
 
     /// Rotate based on a specified quaternion
+    /// 
+    /// Rotates points the same way as multiplying them with the quaternion.
     pub fn rotate(q: Quaternion) -> Self {
         let s = q.data[3];
         let q1 = q.data[0];
@@ -154,9 +156,9 @@ impl Matrix4 {
         let q3 = q.data[2];
 
         Self { data: [
-            (s * s) + (q1 * q1) - (q2 * q2) - (q3 * q3), 2.0 * (q1 * q2 - s * q3), 2.0 * (s * q2 + q1 * q3), 0.0,
-            2.0 * (q1 * q2 + s * q3), (s * s) - (q1 * q1) + (q2 * q2) - (q3 * q3), 2.0 * (q2 * q3 - s * q1), 0.0,
-            2.0 * (q1 * q3 - s * q2), 2.0 * (s * q1 + q2 * q3), (s * s) - (q1 * q1) - (q2 * q2) + (q3 * q3), 0.0,
+            (s * s) + (q1 * q1) - (q2 * q2) - (q3 * q3), 2.0 * (q1 * q2 + s * q3), 2.0 * (q1 * q3 - s * q2), 0.0,
+            2.0 * (q1 * q2 - s * q3), (s * s) - (q1 * q1) + (q2 * q2) - (q3 * q3), 2.0 * (s * q1 + q2 * q3), 0.0,
+            2.0 * (s * q2 + q1 * q3), 2.0 * (q2 * q3 - s * q1), (s * s) - (q1 * q1) - (q2 * q2) + (q3 * q3), 0.0,
             0.0, 0.0, 0.0, 1.0
         ] }
     }
@@ -209,18 +211,157 @@ impl Matrix4 {
         Self::scale(scale, scale, scale)
     }
 
+    /// Transform a point by the matrix
+    /// 
+    /// The point is treated as a column vector with a `w` of 1.0, so the
+    /// translation of the matrix is applied.
+    /// 
+    /// # Examples
+    /// ```
+    /// let transform = Matrix4::translate(1.0, 2.0, 3.0);
+    /// let p = transform.transform_point(Vector3::zero());
+    /// 
+    /// assert_eq!(p.data, [1.0, 2.0, 3.0]);
+    /// ```
+    pub fn transform_point(&self, p: Vector3) -> Vector3 {
+        let mut out = Vector3::zero();
+
+        for row in 0..4 {
+            let value = 
+                self[(row, 0)] * p[0] +
+                self[(row, 1)] * p[1] +
+                self[(row, 2)] * p[2] +
+                self[(row, 3)];
+
+            if row < 3 {
+                out[row] = value;
+            } else if value != 0.0 && value != 1.0 {
+                // Perspective divide
+                out *= 1.0 / value;
+            }
+        }
+
+        out
+    }
+
+    /// Transform a direction by the matrix
+    /// 
+    /// The direction is treated as a column vector with a `w` of 0.0, so
+    /// the translation of the matrix is ignored.
+    pub fn transform_vector(&self, v: Vector3) -> Vector3 {
+        let mut out = Vector3::zero();
+
+        for row in 0..3 {
+            out[row] = 
+                self[(row, 0)] * v[0] +
+                self[(row, 1)] * v[1] +
+                self[(row, 2)] * v[2];
+        }
+
+        out
+    }
+
     pub fn transpose(self) -> Self {
 
-        let mut out = self.clone();
+        let mut out = self;
 
         for x in 0 as usize..4 {
             for y in 0 as usize..4 {
-                out[(x, y)] = out[(y, x)]
+                out[(x, y)] = self[(y, x)]
             }
         }
 
         out
     }
+
+    /// Determinant of the matrix
+    /// 
+    /// Zero if the matrix can not be inverted. Negative if the matrix
+    /// mirrors the space it transforms.
+    pub fn determinant(&self) -> f32 {
+        let m = |row, col| self[(row, col)];
+
+        // 2x2 determinants of the bottom two rows
+        let s0 = m(2, 0) * m(3, 1) - m(2, 1) * m(3, 0);
+        let s1 = m(2, 0) * m(3, 2) - m(2, 2) * m(3, 0);
+        let s2 = m(2, 0) * m(3, 3) - m(2, 3) * m(3, 0);
+        let s3 = m(2, 1) * m(3, 2) - m(2, 2) * m(3, 1);
+        let s4 = m(2, 1) * m(3, 3) - m(2, 3) * m(3, 1);
+        let s5 = m(2, 2) * m(3, 3) - m(2, 3) * m(3, 2);
+
+        // 2x2 determinants of the top two rows
+        let c0 = m(0, 0) * m(1, 1) - m(0, 1) * m(1, 0);
+        let c1 = m(0, 0) * m(1, 2) - m(0, 2) * m(1, 0);
+        let c2 = m(0, 0) * m(1, 3) - m(0, 3) * m(1, 0);
+        let c3 = m(0, 1) * m(1, 2) - m(0, 2) * m(1, 1);
+        let c4 = m(0, 1) * m(1, 3) - m(0, 3) * m(1, 1);
+        let c5 = m(0, 2) * m(1, 3) - m(0, 3) * m(1, 2);
+
+        c0 * s5 - c1 * s4 + c2 * s3 + c3 * s2 - c4 * s1 + c5 * s0
+    }
+
+    /// Invert the matrix
+    /// 
+    /// Returns `None` if the matrix is singular. Uses the cofactors of the
+    /// matrix, so the result is exact up to floating point error.
+    /// 
+    /// # Examples
+    /// ```
+    /// let transform = Matrix4::translate(1.0, 2.0, 3.0);
+    /// let inverse = transform.inverse().unwrap();
+    /// 
+    /// assert_eq!(inverse.transform_point(Vector3::new(1.0, 2.0, 3.0)).data, [0.0, 0.0, 0.0]);
+    /// ```
+    pub fn inverse(&self) -> Option<Self> {
+        let m = &self.data;
+        let mut inv = [0.0; 16];
+
+        inv[0] = m[5] * m[10] * m[15] - m[5] * m[11] * m[14] - m[9] * m[6] * m[15]
+            + m[9] * m[7] * m[14] + m[13] * m[6] * m[11] - m[13] * m[7] * m[10];
+        inv[4] = -m[4] * m[10] * m[15] + m[4] * m[11] * m[14] + m[8] * m[6] * m[15]
+            - m[8] * m[7] * m[14] - m[12] * m[6] * m[11] + m[12] * m[7] * m[10];
+        inv[8] = m[4] * m[9] * m[15] - m[4] * m[11] * m[13] - m[8] * m[5] * m[15]
+            + m[8] * m[7] * m[13] + m[12] * m[5] * m[11] - m[12] * m[7] * m[9];
+        inv[12] = -m[4] * m[9] * m[14] + m[4] * m[10] * m[13] + m[8] * m[5] * m[14]
+            - m[8] * m[6] * m[13] - m[12] * m[5] * m[10] + m[12] * m[6] * m[9];
+        inv[1] = -m[1] * m[10] * m[15] + m[1] * m[11] * m[14] + m[9] * m[2] * m[15]
+            - m[9] * m[3] * m[14] - m[13] * m[2] * m[11] + m[13] * m[3] * m[10];
+        inv[5] = m[0] * m[10] * m[15] - m[0] * m[11] * m[14] - m[8] * m[2] * m[15]
+            + m[8] * m[3] * m[14] + m[12] * m[2] * m[11] - m[12] * m[3] * m[10];
+        inv[9] = -m[0] * m[9] * m[15] + m[0] * m[11] * m[13] + m[8] * m[1] * m[15]
+            - m[8] * m[3] * m[13] - m[12] * m[1] * m[11] + m[12] * m[3] * m[9];
+        inv[13] = m[0] * m[9] * m[14] - m[0] * m[10] * m[13] - m[8] * m[1] * m[14]
+            + m[8] * m[2] * m[13] + m[12] * m[1] * m[10] - m[12] * m[2] * m[9];
+        inv[2] = m[1] * m[6] * m[15] - m[1] * m[7] * m[14] - m[5] * m[2] * m[15]
+            + m[5] * m[3] * m[14] + m[13] * m[2] * m[7] - m[13] * m[3] * m[6];
+        inv[6] = -m[0] * m[6] * m[15] + m[0] * m[7] * m[14] + m[4] * m[2] * m[15]
+            - m[4] * m[3] * m[14] - m[12] * m[2] * m[7] + m[12] * m[3] * m[6];
+        inv[10] = m[0] * m[5] * m[15] - m[0] * m[7] * m[13] - m[4] * m[1] * m[15]
+            + m[4] * m[3] * m[13] + m[12] * m[1] * m[7] - m[12] * m[3] * m[5];
+        inv[14] = -m[0] * m[5] * m[14] + m[0] * m[6] * m[13] + m[4] * m[1] * m[14]
+            - m[4] * m[2] * m[13] - m[12] * m[1] * m[6] + m[12] * m[2] * m[5];
+        inv[3] = -m[1] * m[6] * m[11] + m[1] * m[7] * m[10] + m[5] * m[2] * m[11]
+            - m[5] * m[3] * m[10] - m[9] * m[2] * m[7] + m[9] * m[3] * m[6];
+        inv[7] = m[0] * m[6] * m[11] - m[0] * m[7] * m[10] - m[4] * m[2] * m[11]
+            + m[4] * m[3] * m[10] + m[8] * m[2] * m[7] - m[8] * m[3] * m[6];
+        inv[11] = -m[0] * m[5] * m[11] + m[0] * m[7] * m[9] + m[4] * m[1] * m[11]
+            - m[4] * m[3] * m[9] - m[8] * m[1] * m[7] + m[8] * m[3] * m[5];
+        inv[15] = m[0] * m[5] * m[10] - m[0] * m[6] * m[9] - m[4] * m[1] * m[10]
+            + m[4] * m[2] * m[9] + m[8] * m[1] * m[6] - m[8] * m[2] * m[5];
+
+        let det = m[0] * inv[0] + m[1] * inv[4] + m[2] * inv[8] + m[3] * inv[12];
+
+        if det == 0.0 {
+            return None;
+        }
+
+        let det_inv = 1.0 / det;
+        for value in inv.iter_mut() {
+            *value *= det_inv;
+        }
+
+        Some(Self { data: inv })
+    }
 }
 
 impl Default for Matrix4 {
@@ -344,6 +485,38 @@ mod tests {
         assert_eq!(mat[(1, 2)], 15.0);
     }
 
+    #[test]
+    fn test_matrix_determinant() {
+        assert_eq!(Matrix4::identity().determinant(), 1.0);
+        assert_eq!(Matrix4::zero().determinant(), 0.0);
+        assert_eq!((Matrix4::scale(2.0, 3.0, -4.0) * Matrix4::translate(5.0, 6.0, 7.0)).determinant(), -24.0);
+
+        let m = Matrix4 { data: [
+            1.0, 3.0, 5.0, 9.0,
+            1.0, 3.0, 1.0, 7.0,
+            4.0, 3.0, 9.0, 7.0,
+            5.0, 2.0, 0.0, 9.0
+        ] };
+        assert_eq!(m.determinant(), -376.0);
+        assert_eq!(m.transpose().determinant(), -376.0);
+    }
+
+    #[test]
+    fn test_matrix_inverse() {
+        let transform = 
+            Matrix4::scale(2.0, 3.0, 4.0) *
+            Matrix4::rotate(Quaternion::euler(0.3, 1.2, -0.4)) *
+            Matrix4::translate(1.0, -2.0, 5.0);
+
+        let identity = transform * transform.inverse().unwrap();
+        for (a, b) in identity.data.iter().zip(Matrix4::identity().data.iter()) {
+            assert!((a - b).abs() < 1e-5);
+        }
+
+        assert!(Matrix4::zero().inverse().is_none());
+        assert!(Matrix4::scale(1.0, 0.0, 1.0).inverse().is_none());
+    }
+
     #[test]
     fn test_matrix_multiplication() {
 
@@ -1,6 +1,9 @@
+pub mod mat3;
 pub mod mat4;
 pub mod vec2;
 pub mod vec3;
 pub mod vec4;
 pub mod axis;
-pub mod quaternion;
\ No newline at end of file
+pub mod geo;
+pub mod quaternion;
+pub mod ray;
@@ -1,5 +1,12 @@
+use std::ops::{Mul, MulAssign, Neg};
+
 use super::vec3::Vector3;
 
+/// A rotation stored as a unit quaternion `[x, y, z, w]`
+///
+/// Multiplying two quaternions composes their rotations, where `a * b`
+/// rotates by `b` first and then by `a`. Multiplying a quaternion with a
+/// `Vector3` rotates the vector.
 #[derive(Debug, Clone, Copy)]
 pub struct Quaternion {
     pub data: [f32; 4]
@@ -16,6 +23,9 @@ impl Quaternion {
         ] }
     }
 
+    /// Create a rotation from euler angles in radians
+    ///
+    /// Rotates around x first, then around y and finally around z.
     pub fn euler(x: f32, y: f32, z: f32) -> Self {
         let cz = (z * 0.5).cos();
         let sz = (z * 0.5).sin();
@@ -33,35 +43,187 @@ impl Quaternion {
     }
 
     pub fn identity() -> Self {
-        Self::euler(0.0, 0.0, 0.0)
+        Self::new(0.0, 0.0, 0.0, 1.0)
     }
 
-    pub fn euler_angles(&self) -> Vector3 {
-        // TODO: figure out what is wrong with this function
-        let x: usize = 0;
-        let y: usize = 1;
-        let z: usize = 2;
-        let w: usize = 3;
-
-        let sinr_cosp = 2.0 * (self.data[w] * self.data[x] + self.data[y] + self.data[z]);
-        let cosr_cosp = 1.0 - (2.0 * ((self.data[x] * self.data[x]) + (self.data[y] * self.data[y])));
-        
-        let out_y: f32;
-        let sinp = 2.0 * (self.data[w] * self.data[y] - self.data[z] * self.data[x]);
-        
-        if sinp.abs() >= 1.0 {
-            out_y = std::f32::consts::FRAC_PI_2.copysign(sinp);
+    /// Create a rotation of `angle` radians around `axis`
+    ///
+    /// The axis does not have to be normalized. Rotations are counter
+    /// clockwise when looking down the axis towards the origin.
+    pub fn from_axis_angle(axis: Vector3, angle: f32) -> Self {
+        let axis = axis.normalize() * (angle * 0.5).sin();
+
+        Self::new(axis.data[0], axis.data[1], axis.data[2], (angle * 0.5).cos())
+    }
+
+    /// Create a rotation that turns `Vector3::forward()` towards `forward`
+    ///
+    /// `Vector3::up()` is turned as close to `up` as possible while staying
+    /// perpendicular to `forward`. Returns the identity if `forward` is zero
+    /// or parallel to `up`.
+    pub fn look_rotation(forward: Vector3, up: Vector3) -> Self {
+        let forward = forward.normalize();
+        let right = Vector3::cross(up, forward);
+
+        if forward.sqr_magnitude() == 0.0 || right.sqr_magnitude() < 1e-12 {
+            return Self::identity();
+        }
+
+        let right = right.normalize();
+        let up = Vector3::cross(forward, right);
+
+        Self::from_basis(right, up, forward)
+    }
+
+    /// Create a rotation from the rotated x, y and z axes
+    ///
+    /// The axes have to be orthonormal and right handed.
+    fn from_basis(x: Vector3, y: Vector3, z: Vector3) -> Self {
+        let (m00, m01, m02) = (x.data[0], y.data[0], z.data[0]);
+        let (m10, m11, m12) = (x.data[1], y.data[1], z.data[1]);
+        let (m20, m21, m22) = (x.data[2], y.data[2], z.data[2]);
+        let trace = m00 + m11 + m22;
+
+        // Divide by the largest component to stay numerically stable
+        let q = if trace > 0.0 {
+            let s = (trace + 1.0).sqrt() * 2.0;
+            Self::new((m21 - m12) / s, (m02 - m20) / s, (m10 - m01) / s, 0.25 * s)
+        } else if m00 > m11 && m00 > m22 {
+            let s = (1.0 + m00 - m11 - m22).sqrt() * 2.0;
+            Self::new(0.25 * s, (m01 + m10) / s, (m02 + m20) / s, (m21 - m12) / s)
+        } else if m11 > m22 {
+            let s = (1.0 + m11 - m00 - m22).sqrt() * 2.0;
+            Self::new((m01 + m10) / s, 0.25 * s, (m12 + m21) / s, (m02 - m20) / s)
         } else {
-            out_y = sinp.asin();
+            let s = (1.0 + m22 - m00 - m11).sqrt() * 2.0;
+            Self::new((m02 + m20) / s, (m12 + m21) / s, 0.25 * s, (m10 - m01) / s)
+        };
+
+        q.normalize()
+    }
+
+    /// Euler angles in radians that recreate the rotation with `euler`
+    ///
+    /// The y angle is in `[-PI/2, PI/2]`. Near those limits x and z rotate
+    /// around the same axis, and the rotation is returned in x alone.
+    pub fn euler_angles(&self) -> Vector3 {
+        let [x, y, z, w] = self.data;
+
+        let sinr_cosp = 2.0 * (w * x + y * z);
+        let cosr_cosp = 1.0 - 2.0 * (x * x + y * y);
+
+        let sinp = 2.0 * (w * y - z * x);
+
+        if sinp.abs() >= 1.0 - 1e-6 {
+            // Gimbal lock, fold z into x
+            let out_y = std::f32::consts::FRAC_PI_2.copysign(sinp);
+            let out_x = 2.0 * x.atan2(w);
+
+            return Vector3::new(out_x, out_y, 0.0);
         }
 
-        let siny_cosp= 2.0 * (self.data[w] * self.data[z] + self.data[x] * self.data[y]);
-        let cosy_cosp = 1.0 - (2.0 * ((self.data[y] * self.data[y]) + (self.data[z] * self.data[z])));
+        let siny_cosp = 2.0 * (w * z + x * y);
+        let cosy_cosp = 1.0 - 2.0 * (y * y + z * z);
 
-        let out_x = sinr_cosp.atan2(cosr_cosp);
-        let out_z = siny_cosp.atan2(cosy_cosp);
+        Vector3::new(
+            sinr_cosp.atan2(cosr_cosp),
+            sinp.asin(),
+            siny_cosp.atan2(cosy_cosp)
+        )
+    }
+
+    pub fn dot(a: Quaternion, b: Quaternion) -> f32 {
+        a.data[0] * b.data[0] +
+        a.data[1] * b.data[1] +
+        a.data[2] * b.data[2] +
+        a.data[3] * b.data[3]
+    }
 
-        Vector3::new(out_x, out_y, out_z)
+    pub fn magnitude(&self) -> f32 {
+        Self::dot(*self, *self).sqrt()
+    }
+
+    /// Return a unit quaternion with the same rotation
+    ///
+    /// Returns the identity for a zero quaternion.
+    pub fn normalize(&self) -> Self {
+        let mag = self.magnitude();
+
+        if mag == 0.0 {
+            return Self::identity();
+        }
+
+        let [x, y, z, w] = self.data;
+        Self::new(x / mag, y / mag, z / mag, w / mag)
+    }
+
+    /// Quaternion with the vector part negated
+    ///
+    /// For unit quaternions this is the inverse rotation.
+    pub fn conjugate(&self) -> Self {
+        let [x, y, z, w] = self.data;
+
+        Self::new(-x, -y, -z, w)
+    }
+
+    /// Inverse of the quaternion, which does not have to be a unit quaternion
+    ///
+    /// Returns `None` for a zero quaternion.
+    pub fn inverse(&self) -> Option<Self> {
+        let sqr_mag = Self::dot(*self, *self);
+
+        if sqr_mag == 0.0 {
+            return None;
+        }
+
+        let [x, y, z, w] = self.conjugate().data;
+        Some(Self::new(x / sqr_mag, y / sqr_mag, z / sqr_mag, w / sqr_mag))
+    }
+
+    /// Normalized linear interpolation from `a` to `b`
+    ///
+    /// Cheaper than `slerp` but does not rotate at a constant speed. Takes
+    /// the shortest path between the rotations.
+    pub fn nlerp(a: Quaternion, b: Quaternion, t: f32) -> Self {
+        let b = if Self::dot(a, b) < 0.0 { -b } else { b };
+
+        Self { data: [
+            a.data[0] + (b.data[0] - a.data[0]) * t,
+            a.data[1] + (b.data[1] - a.data[1]) * t,
+            a.data[2] + (b.data[2] - a.data[2]) * t,
+            a.data[3] + (b.data[3] - a.data[3]) * t,
+        ] }.normalize()
+    }
+
+    /// Spherical linear interpolation from `a` to `b`
+    ///
+    /// Rotates at a constant speed along the shortest path between the
+    /// rotations. `t` is not clamped.
+    pub fn slerp(a: Quaternion, b: Quaternion, t: f32) -> Self {
+        let mut cos_theta = Self::dot(a, b);
+        let mut b = b;
+
+        if cos_theta < 0.0 {
+            b = -b;
+            cos_theta = -cos_theta;
+        }
+
+        // Nearly the same rotation, avoid dividing by sin(theta) ~ 0
+        if cos_theta > 0.9995 {
+            return Self::nlerp(a, b, t);
+        }
+
+        let theta = cos_theta.acos();
+        let sin_theta = theta.sin();
+        let wa = ((1.0 - t) * theta).sin() / sin_theta;
+        let wb = (t * theta).sin() / sin_theta;
+
+        Self { data: [
+            a.data[0] * wa + b.data[0] * wb,
+            a.data[1] * wa + b.data[1] * wb,
+            a.data[2] * wa + b.data[2] * wb,
+            a.data[3] * wa + b.data[3] * wb,
+        ] }
     }
 }
 
@@ -69,4 +231,155 @@ impl Default for Quaternion {
     fn default() -> Self {
         Self::identity()
     }
-}
\ No newline at end of file
+}
+
+impl Mul<Quaternion> for Quaternion {
+    type Output = Quaternion;
+
+    /// Hamilton product, rotating by `rhs` first and then by `self`
+    fn mul(self, rhs: Quaternion) -> Self::Output {
+        let [ax, ay, az, aw] = self.data;
+        let [bx, by, bz, bw] = rhs.data;
+
+        Quaternion::new(
+            aw * bx + ax * bw + ay * bz - az * by,
+            aw * by - ax * bz + ay * bw + az * bx,
+            aw * bz + ax * by - ay * bx + az * bw,
+            aw * bw - ax * bx - ay * by - az * bz
+        )
+    }
+}
+
+impl MulAssign<Quaternion> for Quaternion {
+    fn mul_assign(&mut self, rhs: Quaternion) {
+        *self = *self * rhs;
+    }
+}
+
+impl Mul<Vector3> for Quaternion {
+    type Output = Vector3;
+
+    /// Rotate a vector, assuming the quaternion is normalized
+    fn mul(self, rhs: Vector3) -> Self::Output {
+        let [x, y, z, w] = self.data;
+        let u = Vector3::new(x, y, z);
+
+        let uv = Vector3::cross(u, rhs);
+        let uuv = Vector3::cross(u, uv);
+
+        rhs + (uv * w + uuv) * 2.0
+    }
+}
+
+impl Neg for Quaternion {
+    type Output = Quaternion;
+
+    /// Negate every component, which represents the same rotation
+    fn neg(self) -> Self::Output {
+        let [x, y, z, w] = self.data;
+
+        Quaternion::new(-x, -y, -z, -w)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::mat4::Matrix4;
+    use std::f32::consts::{FRAC_PI_2, PI};
+
+    fn assert_vec_eq(a: Vector3, b: Vector3) {
+        assert!((a - b).magnitude() < 1e-5, "{:?} != {:?}", a, b);
+    }
+
+    /// Quaternions `q` and `-q` are the same rotation
+    fn assert_rot_eq(a: Quaternion, b: Quaternion) {
+        assert!(Quaternion::dot(a, b).abs() > 1.0 - 1e-5, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn test_axis_angle_rotates_vectors() {
+        let q = Quaternion::from_axis_angle(Vector3::up(), FRAC_PI_2);
+        assert_vec_eq(q * Vector3::right(), Vector3::new(0.0, 0.0, -1.0));
+
+        let q = Quaternion::from_axis_angle(Vector3::new(0.0, 0.0, 2.0), PI);
+        assert_vec_eq(q * Vector3::right(), Vector3::new(-1.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn test_mul_composes_rotations() {
+        let a = Quaternion::euler(0.3, -1.2, 0.7);
+        let b = Quaternion::from_axis_angle(Vector3::new(1.0, 2.0, 3.0), 0.9);
+        let v = Vector3::new(0.5, -2.0, 4.0);
+
+        assert_vec_eq((a * b) * v, a * (b * v));
+        assert_vec_eq((a * a.conjugate()) * v, v);
+
+        let c = Quaternion::new(0.2, 0.4, -0.6, 1.6);
+        let identity = c.inverse().unwrap() * c;
+        assert!((identity.data[3] - 1.0).abs() < 1e-6);
+        assert!(Quaternion::new(0.0, 0.0, 0.0, 0.0).inverse().is_none());
+    }
+
+    #[test]
+    fn test_matches_rotation_matrix() {
+        let q = Quaternion::euler(0.3, -1.2, 0.7);
+        let v = Vector3::new(0.5, -2.0, 4.0);
+
+        assert_vec_eq(Matrix4::rotate(q).transform_vector(v), q * v);
+    }
+
+    #[test]
+    fn test_euler_order() {
+        let (x, y, z) = (0.3, -1.2, 0.7);
+        let composed =
+            Quaternion::from_axis_angle(Vector3::forward(), z) *
+            Quaternion::from_axis_angle(Vector3::up(), y) *
+            Quaternion::from_axis_angle(Vector3::right(), x);
+
+        assert_rot_eq(Quaternion::euler(x, y, z), composed);
+    }
+
+    #[test]
+    fn test_euler_angles_round_trip() {
+        for angles in [[0.3, -1.2, 0.7], [-2.5, 0.4, 3.0], [0.0, 0.0, 0.0], [1.0, 0.0, -1.0]] {
+            let q = Quaternion::euler(angles[0], angles[1], angles[2]);
+            assert_vec_eq(q.euler_angles(), Vector3 { data: angles });
+        }
+
+        // Gimbal lock still recreates the same rotation
+        let q = Quaternion::euler(0.4, FRAC_PI_2, 0.3);
+        let e = q.euler_angles();
+        assert_rot_eq(Quaternion::euler(e.data[0], e.data[1], e.data[2]), q);
+    }
+
+    #[test]
+    fn test_look_rotation() {
+        let forward = Vector3::new(1.0, 1.0, 0.0);
+        let q = Quaternion::look_rotation(forward, Vector3::up());
+
+        assert_vec_eq(q * Vector3::forward(), forward.normalize());
+        assert!(Vector3::dot(q * Vector3::up(), Vector3::up()) > 0.0);
+        assert_vec_eq(q * Vector3::right(), Vector3::new(0.0, 0.0, -1.0));
+
+        assert_rot_eq(Quaternion::look_rotation(Vector3::forward(), Vector3::up()), Quaternion::identity());
+        assert_rot_eq(Quaternion::look_rotation(Vector3::up(), Vector3::up()), Quaternion::identity());
+
+        let back = Quaternion::look_rotation(-Vector3::forward(), Vector3::up());
+        assert_vec_eq(back * Vector3::forward(), -Vector3::forward());
+    }
+
+    #[test]
+    fn test_slerp() {
+        let a = Quaternion::identity();
+        let b = Quaternion::from_axis_angle(Vector3::up(), FRAC_PI_2);
+
+        assert_rot_eq(Quaternion::slerp(a, b, 0.0), a);
+        assert_rot_eq(Quaternion::slerp(a, b, 1.0), b);
+        assert_rot_eq(Quaternion::slerp(a, b, 0.5), Quaternion::from_axis_angle(Vector3::up(), PI / 4.0));
+
+        // Takes the short way around, even when b is negated
+        assert_rot_eq(Quaternion::slerp(a, -b, 0.5), Quaternion::from_axis_angle(Vector3::up(), PI / 4.0));
+        assert_rot_eq(Quaternion::nlerp(a, -b, 0.5), Quaternion::from_axis_angle(Vector3::up(), PI / 4.0));
+    }
+}
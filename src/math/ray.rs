@@ -0,0 +1,64 @@
+use super::vec3::Vector3;
+
+/// A half-line starting at `origin`
+/// 
+/// The direction is always a unit vector, so distances along the ray are
+/// measured in world units.
+#[derive(Debug, Clone, Copy)]
+pub struct Ray {
+    pub origin: Vector3,
+    pub direction: Vector3
+}
+
+#[allow(dead_code)]
+impl Ray {
+    pub fn new(origin: Vector3, direction: Vector3) -> Self {
+        Self { origin, direction: direction.normalize() }
+    }
+
+    /// Point at distance `t` along the ray
+    pub fn at(&self, t: f32) -> Vector3 {
+        self.origin + self.direction * t
+    }
+
+    /// Distance to the closest intersection with a sphere
+    /// 
+    /// Returns `None` if the ray misses the sphere or if the sphere is
+    /// entirely behind the origin. A ray starting inside the sphere hits
+    /// it where it leaves.
+    pub fn intersect_sphere(&self, center: Vector3, radius: f32) -> Option<f32> {
+        let offset = self.origin - center;
+        let b = Vector3::dot(offset, self.direction);
+        let c = offset.sqr_magnitude() - radius * radius;
+        let discriminant = b * b - c;
+
+        if discriminant < 0.0 {
+            return None;
+        }
+
+        let root = discriminant.sqrt();
+        [-b - root, -b + root].into_iter().find(|t| *t >= 0.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ray_sphere_hit() {
+        let ray = Ray::new(Vector3::new(0.0, 0.0, 10.0), Vector3::new(0.0, 0.0, -2.0));
+
+        assert_eq!(ray.intersect_sphere(Vector3::zero(), 1.0), Some(9.0));
+        assert_eq!(ray.at(9.0).data, [0.0, 0.0, 1.0]);
+        assert_eq!(ray.intersect_sphere(Vector3::new(0.0, 2.0, 0.0), 1.0), None);
+    }
+
+    #[test]
+    fn test_ray_sphere_behind_and_inside() {
+        let ray = Ray::new(Vector3::zero(), Vector3::forward());
+
+        assert_eq!(ray.intersect_sphere(Vector3::new(0.0, 0.0, -5.0), 1.0), None);
+        assert_eq!(ray.intersect_sphere(Vector3::zero(), 2.0), Some(2.0));
+    }
+}
@@ -27,6 +27,15 @@ impl Vector3 {
         ] }
     }
 
+    /// Vector where all components are `1.0`
+    pub fn one() -> Self {
+        Self { data: [
+            1.0,
+            1.0,
+            1.0
+        ] }
+    }
+
     /// Unit vector pointing to absolute forward (`+z`)
     pub fn forward() -> Self {
         Self { data: [
@@ -71,7 +80,7 @@ impl Vector3 {
 
         Vector3 { data: [
             a[Axis::Y] * b[Axis::Z] - a[Axis::Z] * b[Axis::Y],
-            a[Axis::Z] * b[Axis::X] - a[Axis::X] * b[Axis::Y],
+            a[Axis::Z] * b[Axis::X] - a[Axis::X] * b[Axis::Z],
             a[Axis::X] * b[Axis::Y] - a[Axis::Y] * b[Axis::X]
         ] }
     }
@@ -192,7 +201,7 @@ impl IndexMut<Axis> for Vector3 {
             Axis::X => &mut self.data[0],
             Axis::Y => &mut self.data[1],
             Axis::Z => &mut self.data[2],
-            _ => &mut self.data[3]
+            _ => panic!("Vector3 has no W component")
         }
     }
 }
@@ -292,3 +301,16 @@ impl Neg for &Vector3 {
         ] }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cross_product() {
+        assert_eq!(Vector3::cross(Vector3::right(), Vector3::up()).data, [0.0, 0.0, 1.0]);
+        assert_eq!(Vector3::cross(Vector3::up(), Vector3::forward()).data, [1.0, 0.0, 0.0]);
+        assert_eq!(Vector3::cross(Vector3::forward(), Vector3::right()).data, [0.0, 1.0, 0.0]);
+        assert_eq!(Vector3::cross(Vector3::new(1.0, 2.0, 3.0), Vector3::new(4.0, 5.0, 6.0)).data, [-3.0, 6.0, -3.0]);
+    }
+}
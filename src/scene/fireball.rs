@@ -0,0 +1,530 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{data::cneos::FireballRecord, graphics::backend::graphics_backend::GraphicsBackend, math::{geo::EarthModel, quaternion::Quaternion, vec3::Vector3}};
+
+use super::{marker_style::{MarkerAppearance, MarkerStyle, HIGHLIGHT_COLOR}, scene_graph::{NodeId, SceneGraph}, scene_node::Node};
+
+/// Radius of a sphere around the unscaled fireball mesh, used for picking
+pub const MARKER_RADIUS: f32 = 0.0434;
+
+/// A fireball event placed on the globe
+///
+/// Owns the node of its marker, so the marker can be moved or removed
+/// without touching the rest of the scene. The full record of the event is
+/// kept alongside it.
+pub struct Fireball {
+    pub record: FireballRecord,
+    appearance: MarkerAppearance,
+    /// Holds the renderer, placed at the location and altitude of the fireball
+    marker: NodeId
+}
+
+#[allow(dead_code)]
+impl Fireball {
+    /// Place a new fireball marker under `parent`
+    ///
+    /// `marker` is the node holding the renderer of the fireball.
+    pub fn spawn(graph: &mut SceneGraph, parent: NodeId, record: FireballRecord, marker: Node, earth: &EarthModel) -> Self {
+        let marker = graph.add_child(parent, marker);
+        let appearance = MarkerAppearance { size: 1.0, color: [0.9, 0.15, 0.05, 1.0] };
+
+        let fireball = Self { record, appearance, marker };
+        fireball.place(graph, earth);
+
+        fireball
+    }
+
+    /// Move the marker to a new location
+    /// 
+    /// `alt` is in km, as in `FireballRecord`.
+    pub fn move_to(&mut self, graph: &mut SceneGraph, lat: f32, lon: f32, alt: Option<f32>, earth: &EarthModel) {
+        self.record.lat = lat;
+        self.record.lon = lon;
+        self.record.alt = alt;
+
+        self.place(graph, earth);
+    }
+
+    /// Node of the marker, holding its renderer
+    pub fn marker(&self) -> NodeId {
+        self.marker
+    }
+
+    /// World space bounding sphere of the marker as a center and radius
+    /// 
+    /// Uses the cached world transform of the marker, so it is only up to
+    /// date after `SceneGraph::update_transforms` has run. Assumes the
+    /// marker is scaled uniformly.
+    pub fn bounds(&self, graph: &SceneGraph) -> Option<(Vector3, f32)> {
+        let world = graph.world_transform(self.marker)?;
+        let center = world.transform_point(Vector3::zero());
+        let radius = MARKER_RADIUS * world.transform_vector(Vector3::right()).magnitude();
+
+        Some((center, radius))
+    }
+
+    pub fn is_visible(&self, graph: &SceneGraph) -> bool {
+        graph.get(self.marker).map(|n| n.visible).unwrap_or(false)
+    }
+
+    /// Show or hide the marker
+    pub fn set_visible(&self, graph: &mut SceneGraph, visible: bool) {
+        if let Some(node) = graph.get_mut(self.marker) {
+            node.visible = visible;
+        }
+    }
+
+    pub fn appearance(&self) -> MarkerAppearance {
+        self.appearance
+    }
+
+    /// Resize and recolor the marker
+    /// 
+    /// The color is set on the material of the marker, the size is applied
+    /// as the scale of the marker node.
+    pub fn set_appearance(&mut self, graph: &mut SceneGraph, appearance: MarkerAppearance) {
+        if let Some(marker) = graph.get_mut(self.marker) {
+            if let Some(renderer) = marker.renderer() {
+                renderer.material().set_color(appearance.color);
+            }
+
+            marker.set_scale(Vector3::new(appearance.size, appearance.size, appearance.size));
+        }
+
+        self.appearance = appearance;
+    }
+
+    /// Color the marker with `HIGHLIGHT_COLOR`, or back to its appearance
+    pub fn set_highlighted(&self, graph: &SceneGraph, highlighted: bool) {
+        let color = if highlighted { HIGHLIGHT_COLOR } else { self.appearance.color };
+
+        if let Some(renderer) = graph.get(self.marker).and_then(|marker| marker.renderer()) {
+            renderer.material().set_color(color);
+        }
+    }
+
+    /// Remove the marker from the scene and free its GPU resources
+    pub fn despawn(&self, graph: &mut SceneGraph, backend: &dyn GraphicsBackend) -> bool {
+        for node in graph.descendants(self.marker) {
+            if let Some(renderer) = graph[node].renderer() {
+                renderer.release(backend);
+            }
+        }
+
+        graph.remove(self.marker)
+    }
+
+    /// Position the marker at the location of the record on `earth`
+    /// 
+    /// It is tilted from the north pole down to the latitude, then turned
+    /// around the poles to the longitude, so its up axis points away from
+    /// the center of the globe.
+    pub fn place(&self, graph: &mut SceneGraph, earth: &EarthModel) {
+        let geo = self.record.geo();
+        let (lat, lon) = (geo.lat.to_radians(), geo.lon.to_radians());
+
+        let rotation =
+            Quaternion::from_axis_angle(Vector3::up(), lon) *
+            Quaternion::from_axis_angle(Vector3::right(), lat - std::f32::consts::FRAC_PI_2);
+
+        if let Some(node) = graph.get_mut(self.marker) {
+            node.set_rotation(rotation);
+            node.set_position(geo.to_globe(earth));
+        }
+    }
+}
+
+/// Keeps track of every fireball in the scene by a stable ID
+///
+/// IDs are handed out in increasing order and are never reused, so a
+/// stale ID held by JavaScript can not refer to a different fireball.
+#[derive(Default)]
+pub struct FireballRegistry {
+    next_id: u32,
+    fireballs: HashMap<u32, Fireball>,
+    filter: Option<HashSet<u32>>,
+    style: MarkerStyle,
+    /// Range of the styled values at the last restyle
+    domain: Option<(f32, f32)>,
+    /// Whether a fireball at the edge of `domain` was removed since, so the
+    /// range may have narrowed
+    domain_stale: bool,
+    highlighted: Option<u32>
+}
+
+#[allow(dead_code)]
+impl FireballRegistry {
+    pub fn new() -> Self {
+        Self {
+            next_id: 0,
+            fireballs: HashMap::new(),
+            filter: None,
+            style: MarkerStyle::default(),
+            domain: None,
+            domain_stale: false,
+            highlighted: None
+        }
+    }
+
+    /// Register a fireball and return its ID
+    pub fn insert(&mut self, fireball: Fireball) -> u32 {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.fireballs.insert(id, fireball);
+
+        id
+    }
+
+    pub fn get(&self, id: u32) -> Option<&Fireball> {
+        self.fireballs.get(&id)
+    }
+
+    pub fn get_mut(&mut self, id: u32) -> Option<&mut Fireball> {
+        self.fireballs.get_mut(&id)
+    }
+
+    /// Iterate over every fireball with its ID, in no particular order
+    pub fn iter(&self) -> impl Iterator<Item = (u32, &Fireball)> {
+        self.fireballs.iter().map(|(id, fireball)| (*id, fireball))
+    }
+
+    pub fn remove(&mut self, id: u32) -> Option<Fireball> {
+        if self.highlighted == Some(id) {
+            self.highlighted = None;
+        }
+
+        let fireball = self.fireballs.remove(&id)?;
+
+        if let (Some((min, max)), Some(v)) = (self.domain, self.style.value(&fireball.record)) {
+            self.domain_stale |= v <= min || v >= max;
+        }
+
+        Some(fireball)
+    }
+
+    /// Remove every fireball, returning them so they can be despawned
+    pub fn drain(&mut self) -> Vec<Fireball> {
+        self.highlighted = None;
+        self.domain = None;
+        self.domain_stale = false;
+        self.fireballs.drain().map(|(_, fireball)| fireball).collect()
+    }
+
+    /// Only show the fireballs whose IDs are in `filter`
+    /// 
+    /// Passing `None` shows every fireball again. Fireballs added while a
+    /// filter is active are hidden until they are part of the filter.
+    pub fn set_filter(&mut self, graph: &mut SceneGraph, filter: Option<HashSet<u32>>) {
+        self.filter = filter;
+
+        let ids: Vec<u32> = self.fireballs.keys().copied().collect();
+        for id in ids {
+            self.apply_filter(graph, id);
+        }
+    }
+
+    /// Returns `true` if the fireball with `id` passes the current filter
+    pub fn passes_filter(&self, id: u32) -> bool {
+        match &self.filter {
+            Some(filter) => filter.contains(&id),
+            None => true
+        }
+    }
+
+    /// Update the visibility of a single fireball to match the filter
+    pub fn apply_filter(&mut self, graph: &mut SceneGraph, id: u32) {
+        let visible = self.passes_filter(id);
+
+        if let Some(fireball) = self.fireballs.get_mut(&id) {
+            fireball.set_visible(graph, visible);
+        }
+    }
+
+    /// ID of the highlighted fireball
+    pub fn highlighted(&self) -> Option<u32> {
+        self.highlighted
+    }
+
+    /// Highlight a single fireball, or none, restoring the previous one
+    pub fn set_highlighted(&mut self, graph: &SceneGraph, id: Option<u32>) {
+        if let Some(fireball) = self.highlighted.and_then(|id| self.fireballs.get(&id)) {
+            fireball.set_highlighted(graph, false);
+        }
+
+        self.highlighted = id.filter(|id| self.fireballs.contains_key(id));
+
+        if let Some(fireball) = self.highlighted.and_then(|id| self.fireballs.get(&id)) {
+            fireball.set_highlighted(graph, true);
+        }
+    }
+
+    pub fn style(&self) -> MarkerStyle {
+        self.style
+    }
+
+    /// Change the marker style and restyle every fireball
+    pub fn set_style(&mut self, graph: &mut SceneGraph, style: MarkerStyle) {
+        self.style = style;
+        self.restyle(graph);
+    }
+
+    /// Apply the marker style to every fireball
+    /// 
+    /// Needs to be called after fireballs are added, as the size and color
+    /// of each marker depend on the values of every other fireball.
+    pub fn restyle(&mut self, graph: &mut SceneGraph) {
+        let style = self.style;
+        let domain = style.domain(self.fireballs.values().map(|f| &f.record));
+        self.domain = domain;
+        self.domain_stale = false;
+
+        for fireball in self.fireballs.values_mut() {
+            let appearance = style.appearance(&fireball.record, domain);
+            fireball.set_appearance(graph, appearance);
+        }
+
+        // Restyling recolors the highlighted marker too
+        self.set_highlighted(graph, self.highlighted);
+    }
+
+    /// Apply the marker style to a fireball added since the last restyle
+    /// 
+    /// Restyles every fireball if the value of the new one widens the range
+    /// of the style or a removed one may have narrowed it, and only the new
+    /// one otherwise.
+    pub fn style_added(&mut self, graph: &mut SceneGraph, id: u32) {
+        let style = self.style;
+        let fireball = match self.fireballs.get_mut(&id) {
+            Some(fireball) => fireball,
+            None => return
+        };
+
+        let domain = match (self.domain, style.value(&fireball.record)) {
+            (Some((min, max)), Some(v)) => Some((f32::min(min, v), f32::max(max, v))),
+            (None, Some(v)) => Some((v, v)),
+            (domain, None) => domain
+        };
+
+        if domain != self.domain || self.domain_stale {
+            self.restyle(graph);
+        } else {
+            let appearance = style.appearance(&fireball.record, domain);
+            fireball.set_appearance(graph, appearance);
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.fireballs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fireballs.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::backend::recording::RecordingBackend;
+    use crate::scene::marker_style::StyleAttribute;
+
+    #[test]
+    fn test_registry_ids_are_not_reused() {
+        let backend = RecordingBackend::new();
+        let mut graph = SceneGraph::new();
+        let root = graph.root();
+        let mut registry = FireballRegistry::new();
+
+        let a = registry.insert(Fireball::spawn(&mut graph, root, FireballRecord::new(0.0, 0.0, None), Node::new(), &EarthModel::default()));
+        let b = registry.insert(Fireball::spawn(&mut graph, root, FireballRecord::new(10.0, 20.0, None), Node::new(), &EarthModel::default()));
+        assert_ne!(a, b);
+
+        registry.remove(b).unwrap().despawn(&mut graph, &backend);
+        let c = registry.insert(Fireball::spawn(&mut graph, root, FireballRecord::new(10.0, 20.0, None), Node::new(), &EarthModel::default()));
+        assert_ne!(b, c);
+        assert_eq!(registry.len(), 2);
+    }
+
+    #[test]
+    fn test_style_added_only_restyles_when_range_widens() {
+        let mut graph = SceneGraph::new();
+        let root = graph.root();
+        let mut registry = FireballRegistry::new();
+        registry.style = MarkerStyle { attribute: StyleAttribute::Energy, min_size: 1.0, max_size: 3.0, ..Default::default() };
+
+        let mut add = |registry: &mut FireballRegistry, energy: f32| {
+            let mut record = FireballRecord::new(0.0, 0.0, None);
+            record.energy = Some(energy);
+            let id = registry.insert(Fireball::spawn(&mut graph, root, record, Node::new(), &EarthModel::default()));
+            registry.style_added(&mut graph, id);
+            id
+        };
+
+        let low = add(&mut registry, 1.0);
+        let high = add(&mut registry, 3.0);
+        assert_eq!(registry.get(high).unwrap().appearance().size, 3.0);
+
+        // Inside the range, only the new marker is styled
+        let middle = add(&mut registry, 2.0);
+        assert_eq!(registry.get(middle).unwrap().appearance().size, 2.0);
+        assert_eq!(registry.domain, Some((1.0, 3.0)));
+
+        // Outside of it, every marker is
+        let highest = add(&mut registry, 5.0);
+        assert_eq!(registry.domain, Some((1.0, 5.0)));
+        assert_eq!(registry.get(low).unwrap().appearance().size, 1.0);
+        assert_eq!(registry.get(high).unwrap().appearance().size, 2.0);
+
+        // Removing the highest may narrow the range, so the next marker
+        // restyles every marker even inside the old one
+        registry.remove(highest);
+        add(&mut registry, 2.5);
+        assert_eq!(registry.domain, Some((1.0, 3.0)));
+        assert_eq!(registry.get(high).unwrap().appearance().size, 3.0);
+
+        // Clearing forgets the range
+        registry.drain();
+        assert_eq!(registry.domain, None);
+        let first = add(&mut registry, 3.0);
+        let second = add(&mut registry, 4.0);
+        assert_eq!(registry.domain, Some((3.0, 4.0)));
+        assert_eq!(registry.get(first).unwrap().appearance().size, 1.0);
+        assert_eq!(registry.get(second).unwrap().appearance().size, 3.0);
+    }
+
+    #[test]
+    fn test_fireball_despawn() {
+        let backend = RecordingBackend::new();
+        let mut graph = SceneGraph::new();
+        let root = graph.root();
+        let fireball = Fireball::spawn(&mut graph, root, FireballRecord::new(45.0, -80.0, Some(30.0)), Node::new(), &EarthModel::default());
+        assert_eq!(graph.children(root).len(), 1);
+
+        assert_eq!(graph.len(), 2);
+
+        assert!(fireball.despawn(&mut graph, &backend));
+        assert!(graph.children(root).is_empty());
+        assert_eq!(graph.len(), 1);
+        assert!(!fireball.despawn(&mut graph, &backend));
+    }
+
+    #[test]
+    fn test_fireball_placement() {
+        let mut graph = SceneGraph::new();
+        let root = graph.root();
+
+        let expected = [
+            ((0.0, 0.0), [0.0, 0.0, -1.0]),
+            ((90.0, 0.0), [0.0, 1.0, 0.0]),
+            ((0.0, 90.0), [-1.0, 0.0, 0.0]),
+            ((0.0, 180.0), [0.0, 0.0, 1.0]),
+            ((-45.0, -90.0), [0.5_f32.sqrt(), -(0.5_f32.sqrt()), 0.0]),
+        ];
+
+        for ((lat, lon), position) in expected {
+            let fireball = Fireball::spawn(&mut graph, root, FireballRecord::new(lat, lon, None), Node::new(), &EarthModel::default());
+            graph.update_transforms();
+
+            let (center, _) = fireball.bounds(&graph).unwrap();
+            assert!((center - Vector3 { data: position }).magnitude() < 1e-5, "{} {} -> {:?}", lat, lon, center);
+        }
+
+        // The flattened poles of WGS84 lie below the unit sphere
+        let pole = Fireball::spawn(&mut graph, root, FireballRecord::new(90.0, 0.0, None), Node::new(), &EarthModel::Wgs84);
+        graph.update_transforms();
+        let (center, _) = pole.bounds(&graph).unwrap();
+        assert!((center.magnitude() - 6356.752 / 6378.137).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_fireball_move_keeps_nodes() {
+        let backend = RecordingBackend::new();
+        let mut graph = SceneGraph::new();
+        let root = graph.root();
+        let mut fireball = Fireball::spawn(&mut graph, root, FireballRecord::new(45.0, -80.0, Some(30.0)), Node::new(), &EarthModel::default());
+
+        let marker = fireball.marker();
+
+        fireball.move_to(&mut graph, -12.0, 30.0, None, &EarthModel::default());
+        assert_eq!(graph.children(root).len(), 1);
+        assert_eq!(fireball.marker(), marker);
+        assert!((graph[marker].position().magnitude() - 1.0).abs() < 1e-6);
+        assert_eq!(fireball.record.lat, -12.0);
+        assert_eq!(fireball.record.lon, 30.0);
+        assert_eq!(fireball.record.alt, None);
+
+        assert!(fireball.despawn(&mut graph, &backend));
+    }
+
+    #[test]
+    fn test_registry_restyle() {
+        use super::super::marker_style::{StyleAttribute, ScaleMode};
+
+        let mut graph = SceneGraph::new();
+        let root = graph.root();
+        let mut registry = FireballRegistry::new();
+
+        let records = [Some(1.0), Some(4.0), None].map(|energy| FireballRecord { energy, ..Default::default() });
+        let ids = records.map(|r| registry.insert(Fireball::spawn(&mut graph, root, r, Node::new(), &EarthModel::default())));
+
+        registry.set_style(&mut graph, MarkerStyle {
+            attribute: StyleAttribute::Energy,
+            scale: ScaleMode::Sqrt,
+            min_size: 1.0,
+            max_size: 2.0,
+            ..Default::default()
+        });
+
+        let sizes = ids.map(|id| registry.get(id).unwrap().appearance().size);
+        assert_eq!(sizes, [1.0, 2.0, 1.0]);
+        assert_eq!(graph.children(root).len(), 3);
+    }
+
+    #[test]
+    fn test_registry_filter() {
+        let mut graph = SceneGraph::new();
+        let root = graph.root();
+        let mut registry = FireballRegistry::new();
+
+        let a = registry.insert(Fireball::spawn(&mut graph, root, FireballRecord::new(0.0, 0.0, None), Node::new(), &EarthModel::default()));
+        let b = registry.insert(Fireball::spawn(&mut graph, root, FireballRecord::new(10.0, 20.0, None), Node::new(), &EarthModel::default()));
+
+        registry.set_filter(&mut graph, Some(HashSet::from([a])));
+        assert!(registry.get(a).unwrap().is_visible(&graph));
+        assert!(!registry.get(b).unwrap().is_visible(&graph));
+        assert_eq!(graph.children(root).iter().filter(|c| graph[**c].visible).count(), 1);
+
+        // New fireballs are hidden until they are part of the filter
+        let c = registry.insert(Fireball::spawn(&mut graph, root, FireballRecord::new(10.0, 20.0, None), Node::new(), &EarthModel::default()));
+        registry.apply_filter(&mut graph, c);
+        assert!(!registry.get(c).unwrap().is_visible(&graph));
+
+        registry.set_filter(&mut graph, None);
+        assert_eq!(graph.children(root).iter().filter(|c| graph[**c].visible).count(), 3);
+    }
+
+    #[test]
+    fn test_registry_highlight() {
+        let mut graph = SceneGraph::new();
+        let root = graph.root();
+        let mut registry = FireballRegistry::new();
+
+        let a = registry.insert(Fireball::spawn(&mut graph, root, FireballRecord::new(0.0, 0.0, None), Node::new(), &EarthModel::default()));
+        let b = registry.insert(Fireball::spawn(&mut graph, root, FireballRecord::new(10.0, 20.0, None), Node::new(), &EarthModel::default()));
+
+        registry.set_highlighted(&graph, Some(a));
+        assert_eq!(registry.highlighted(), Some(a));
+
+        // Restyling keeps the highlight
+        registry.restyle(&mut graph);
+        assert_eq!(registry.highlighted(), Some(a));
+
+        // Unknown IDs can not be highlighted
+        registry.set_highlighted(&graph, Some(b + 1));
+        assert_eq!(registry.highlighted(), None);
+
+        registry.set_highlighted(&graph, Some(b));
+        registry.remove(b);
+        assert_eq!(registry.highlighted(), None);
+    }
+}
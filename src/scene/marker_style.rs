@@ -0,0 +1,231 @@
+use wasm_bindgen::prelude::*;
+
+use crate::data::cneos::FireballRecord;
+
+/// Color of the marker under the pointer, regardless of the style
+pub const HIGHLIGHT_COLOR: [f32; 4] = [0.3, 0.9, 1.0, 1.0];
+
+/// Attribute of a fireball record used to style its marker
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StyleAttribute {
+    /// Total radiated energy
+    Energy,
+    /// Calculated total impact energy
+    ImpactEnergy,
+    /// Every marker gets the same size and color
+    None
+}
+
+/// How attribute values are mapped onto the size and color range
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ScaleMode {
+    Linear,
+    Log,
+    Sqrt
+}
+
+/// Color ramps markers can be colored with
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColorRamp {
+    /// Red through orange to pale yellow
+    Heat,
+    /// Perceptually uniform purple through green to yellow
+    Viridis,
+    /// Dark to bright blue
+    Ice,
+    /// Dark gray to white
+    Grayscale
+}
+
+impl ColorRamp {
+    fn stops(&self) -> &'static [[f32; 3]] {
+        match self {
+            ColorRamp::Heat => &[
+                [0.9, 0.15, 0.05],
+                [1.0, 0.5, 0.1],
+                [1.0, 0.95, 0.6],
+            ],
+            ColorRamp::Viridis => &[
+                [0.267, 0.005, 0.329],
+                [0.231, 0.322, 0.545],
+                [0.129, 0.569, 0.549],
+                [0.369, 0.788, 0.384],
+                [0.993, 0.906, 0.144],
+            ],
+            ColorRamp::Ice => &[
+                [0.05, 0.1, 0.45],
+                [0.2, 0.55, 0.95],
+                [0.85, 0.95, 1.0],
+            ],
+            ColorRamp::Grayscale => &[
+                [0.25, 0.25, 0.25],
+                [1.0, 1.0, 1.0],
+            ],
+        }
+    }
+
+    /// Sample the ramp at `t` in `[0, 1]`
+    pub fn sample(&self, t: f32) -> [f32; 4] {
+        let stops = self.stops();
+        let t = t.clamp(0.0, 1.0) * (stops.len() - 1) as f32;
+        let i = (t.floor() as usize).min(stops.len() - 2);
+        let f = t - i as f32;
+
+        let a = stops[i];
+        let b = stops[i + 1];
+
+        [
+            a[0] + (b[0] - a[0]) * f,
+            a[1] + (b[1] - a[1]) * f,
+            a[2] + (b[2] - a[2]) * f,
+            1.0
+        ]
+    }
+}
+
+/// Size and color of a single marker
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MarkerAppearance {
+    pub size: f32,
+    pub color: [f32; 4]
+}
+
+/// Maps a fireball attribute onto marker size and color
+///
+/// Values are normalized against the smallest and largest value of the
+/// attribute across every fireball, after applying the scale mode.
+/// Fireballs without a value for the attribute use the smallest size and
+/// the start of the ramp.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MarkerStyle {
+    pub attribute: StyleAttribute,
+    pub scale: ScaleMode,
+    pub ramp: ColorRamp,
+    /// Size multiplier of the smallest marker
+    pub min_size: f32,
+    /// Size multiplier of the largest marker
+    pub max_size: f32
+}
+
+impl MarkerStyle {
+    /// Get the value of the styled attribute for `record` after scaling
+    pub fn value(&self, record: &FireballRecord) -> Option<f32> {
+        let value = match self.attribute {
+            StyleAttribute::Energy => record.energy,
+            StyleAttribute::ImpactEnergy => record.impact_e,
+            StyleAttribute::None => None
+        }?;
+
+        let scaled = match self.scale {
+            ScaleMode::Linear => value,
+            ScaleMode::Log if value > 0.0 => value.log10(),
+            ScaleMode::Log => return None,
+            ScaleMode::Sqrt => value.max(0.0).sqrt()
+        };
+
+        Some(scaled)
+    }
+
+    /// Compute the appearance of a marker
+    ///
+    /// `domain` is the range of scaled values across all fireballs, as
+    /// returned by `domain`.
+    pub fn appearance(&self, record: &FireballRecord, domain: Option<(f32, f32)>) -> MarkerAppearance {
+        let t = match (self.value(record), domain) {
+            (Some(value), Some((min, max))) if max > min => (value - min) / (max - min),
+            (Some(_), Some(_)) => 0.5,
+            _ => 0.0
+        };
+
+        MarkerAppearance {
+            size: self.min_size + (self.max_size - self.min_size) * t.clamp(0.0, 1.0),
+            color: self.ramp.sample(t)
+        }
+    }
+
+    /// Range of scaled attribute values across `records`
+    pub fn domain<'a>(&self, records: impl Iterator<Item = &'a FireballRecord>) -> Option<(f32, f32)> {
+        records
+            .filter_map(|record| self.value(record))
+            .fold(None, |domain, v| match domain {
+                Some((min, max)) => Some((f32::min(min, v), f32::max(max, v))),
+                None => Some((v, v))
+            })
+    }
+}
+
+impl Default for MarkerStyle {
+    fn default() -> Self {
+        Self {
+            attribute: StyleAttribute::None,
+            scale: ScaleMode::Linear,
+            ramp: ColorRamp::Heat,
+            min_size: 1.0,
+            max_size: 1.0
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(energy: Option<f32>) -> FireballRecord {
+        FireballRecord { energy, ..Default::default() }
+    }
+
+    #[test]
+    fn test_ramp_endpoints() {
+        assert_eq!(ColorRamp::Grayscale.sample(0.0), [0.25, 0.25, 0.25, 1.0]);
+        assert_eq!(ColorRamp::Grayscale.sample(1.0), [1.0, 1.0, 1.0, 1.0]);
+        assert_eq!(ColorRamp::Grayscale.sample(2.0), [1.0, 1.0, 1.0, 1.0]);
+        assert_eq!(ColorRamp::Heat.sample(0.5), [1.0, 0.5, 0.1, 1.0]);
+    }
+
+    #[test]
+    fn test_log_scale_sizes() {
+        let style = MarkerStyle {
+            attribute: StyleAttribute::Energy,
+            scale: ScaleMode::Log,
+            min_size: 1.0,
+            max_size: 3.0,
+            ..Default::default()
+        };
+
+        let records = [record(Some(1.0)), record(Some(10.0)), record(Some(100.0)), record(None)];
+        let domain = style.domain(records.iter());
+        assert_eq!(domain, Some((0.0, 2.0)));
+
+        let sizes: Vec<f32> = records.iter().map(|r| style.appearance(r, domain).size).collect();
+        assert_eq!(sizes, vec![1.0, 2.0, 3.0, 1.0]);
+    }
+
+    #[test]
+    fn test_log_scale_ignores_non_positive_values() {
+        let style = MarkerStyle {
+            attribute: StyleAttribute::Energy,
+            scale: ScaleMode::Log,
+            ..Default::default()
+        };
+
+        assert_eq!(style.value(&record(Some(0.0))), None);
+    }
+
+    #[test]
+    fn test_single_value_domain() {
+        let style = MarkerStyle {
+            attribute: StyleAttribute::Energy,
+            scale: ScaleMode::Sqrt,
+            min_size: 1.0,
+            max_size: 2.0,
+            ..Default::default()
+        };
+
+        let r = record(Some(4.0));
+        let domain = style.domain([&r].into_iter());
+        assert_eq!(style.appearance(&r, domain).size, 1.5);
+    }
+}
@@ -1,2 +1,6 @@
 
+pub mod scene_graph;
 pub mod scene_node;
+pub mod fireball;
+pub mod marker_style;
+pub mod picking;
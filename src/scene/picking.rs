@@ -0,0 +1,131 @@
+use crate::math::{geo::{EarthModel, GeoCoord}, ray::Ray, vec3::Vector3};
+
+use super::{fireball::FireballRegistry, scene_graph::{NodeId, SceneGraph}};
+
+/// Radius of the globe mesh in its own space
+pub const GLOBE_RADIUS: f32 = 1.0;
+
+/// What a ray cast into the scene hit first
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Pick {
+    /// The marker of the fireball with `id`
+    Fireball { id: u32, distance: f32 },
+    /// A point on the surface of the globe, in degrees
+    Globe { lat: f32, lon: f32, distance: f32 }
+}
+
+impl Pick {
+    /// Distance from the origin of the ray to the hit
+    pub fn distance(&self) -> f32 {
+        match self {
+            Pick::Fireball { distance, .. } => *distance,
+            Pick::Globe { distance, .. } => *distance
+        }
+    }
+}
+
+/// Find the closest visible fireball marker or globe point hit by `ray`
+///
+/// Markers are tested against their bounding spheres and the globe against
+/// a sphere of `GLOBE_RADIUS` around `globe`, whose hits are converted to
+/// coordinates on `earth`. Markers win ties with the globe, as half of each
+/// marker sits below the surface. Uses the cached world transforms, so
+/// `SceneGraph::update_transforms` should run first.
+pub fn pick(graph: &SceneGraph, globe: NodeId, fireballs: &FireballRegistry, ray: &Ray, earth: &EarthModel) -> Option<Pick> {
+    let fireball = fireballs.iter()
+        .filter(|(_, fireball)| fireball.is_visible(graph))
+        .filter_map(|(id, fireball)| {
+            let (center, radius) = fireball.bounds(graph)?;
+            let distance = ray.intersect_sphere(center, radius)?;
+
+            Some(Pick::Fireball { id, distance })
+        })
+        .min_by(|a, b| a.distance().total_cmp(&b.distance()));
+
+    let globe = pick_globe(graph, globe, ray, earth);
+
+    match (fireball, globe) {
+        (Some(f), Some(g)) if g.distance() < f.distance() => Some(g),
+        (Some(f), _) => Some(f),
+        (None, g) => g
+    }
+}
+
+/// Intersect `ray` with the globe and convert the hit to latitude and longitude
+fn pick_globe(graph: &SceneGraph, globe: NodeId, ray: &Ray, earth: &EarthModel) -> Option<Pick> {
+    let world = graph.world_transform(globe)?;
+    let center = world.transform_point(Vector3::zero());
+    let radius = GLOBE_RADIUS * world.transform_vector(Vector3::right()).magnitude();
+
+    let distance = ray.intersect_sphere(center, radius)?;
+    let local = world.inverse()?.transform_point(ray.at(distance));
+    let geo = GeoCoord::from_globe(local, earth);
+
+    Some(Pick::Globe { lat: geo.lat, lon: geo.lon, distance })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{data::cneos::FireballRecord, math::quaternion::Quaternion, scene::{fireball::Fireball, scene_node::Node}};
+
+    fn scene() -> (SceneGraph, NodeId) {
+        let mut graph = SceneGraph::new();
+        let globe = graph.add_child(graph.root(), Node::named("globe"));
+        graph[globe].set_rotation(Quaternion::euler(0.4, -1.1, 0.0));
+
+        (graph, globe)
+    }
+
+    /// Ray from outside the globe straight at `p`
+    fn ray_at(p: Vector3) -> Ray {
+        Ray::new(p * 10.0, -p)
+    }
+
+    #[test]
+    fn test_pick_globe_matches_marker_placement() {
+        let (mut graph, globe) = scene();
+        let registry = FireballRegistry::new();
+
+        // Not registered, only used to find where the marker would be placed
+        let fireball = Fireball::spawn(&mut graph, globe, FireballRecord::new(35.0, -120.0, None), Node::new(), &EarthModel::default());
+        graph.update_transforms();
+        let (center, _) = fireball.bounds(&graph).unwrap();
+
+        match pick(&graph, globe, &registry, &ray_at(center), &EarthModel::default()) {
+            Some(Pick::Globe { lat, lon, distance }) => {
+                assert!((lat - 35.0).abs() < 1e-3);
+                assert!((lon + 120.0).abs() < 1e-3);
+                assert!((distance - 9.0 * center.magnitude()).abs() < 1e-3);
+            },
+            other => panic!("Expected a globe hit, got {:?}", other)
+        }
+    }
+
+    #[test]
+    fn test_pick_fireball_before_globe() {
+        let (mut graph, globe) = scene();
+        let mut registry = FireballRegistry::new();
+
+        let a = registry.insert(Fireball::spawn(&mut graph, globe, FireballRecord::new(10.0, 20.0, None), Node::new(), &EarthModel::default()));
+        registry.insert(Fireball::spawn(&mut graph, globe, FireballRecord::new(-40.0, 80.0, None), Node::new(), &EarthModel::default()));
+        graph.update_transforms();
+
+        let (center, _) = registry.get(a).unwrap().bounds(&graph).unwrap();
+        assert!(matches!(pick(&graph, globe, &registry, &ray_at(center), &EarthModel::default()), Some(Pick::Fireball { id, .. }) if id == a));
+
+        // Hidden markers can not be picked
+        registry.set_filter(&mut graph, Some(Default::default()));
+        assert!(matches!(pick(&graph, globe, &registry, &ray_at(center), &EarthModel::default()), Some(Pick::Globe { .. })));
+    }
+
+    #[test]
+    fn test_pick_miss() {
+        let (mut graph, globe) = scene();
+        let registry = FireballRegistry::new();
+        graph.update_transforms();
+
+        let ray = Ray::new(Vector3::new(0.0, 5.0, 15.0), Vector3::new(0.0, 0.0, -1.0));
+        assert_eq!(pick(&graph, globe, &registry, &ray, &EarthModel::default()), None);
+    }
+}
@@ -0,0 +1,424 @@
+use std::ops::{Index, IndexMut};
+
+use crate::{graphics::{backend::graphics_backend::GraphicsBackend, camera::Camera}, math::mat4::Matrix4};
+
+use super::scene_node::Node;
+
+/// Handle to a node in a `SceneGraph`
+///
+/// Handles stay valid until the node is removed. Slots of removed nodes
+/// are reused, and the generation makes sure an old handle can not refer
+/// to the new node in the same slot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct NodeId {
+    index: u32,
+    generation: u32
+}
+
+struct Slot {
+    generation: u32,
+    node: Option<Node>
+}
+
+/// Owns every node of the scene in a flat arena
+///
+/// The graph always has a root node, which can not be removed. Parent and
+/// child links are stored as `NodeId`s on each node.
+pub struct SceneGraph {
+    slots: Vec<Slot>,
+    free: Vec<u32>,
+    root: NodeId
+}
+
+#[allow(dead_code)]
+impl SceneGraph {
+    pub fn new() -> Self {
+        let root = NodeId { index: 0, generation: 0 };
+
+        Self {
+            slots: vec![Slot { generation: 0, node: Some(Node::named("root")) }],
+            free: Vec::new(),
+            root
+        }
+    }
+
+    pub fn root(&self) -> NodeId {
+        self.root
+    }
+
+    /// Returns `true` if `id` refers to a node in the graph
+    pub fn contains(&self, id: NodeId) -> bool {
+        self.get(id).is_some()
+    }
+
+    pub fn get(&self, id: NodeId) -> Option<&Node> {
+        self.slots
+            .get(id.index as usize)
+            .filter(|slot| slot.generation == id.generation)
+            .and_then(|slot| slot.node.as_ref())
+    }
+
+    pub fn get_mut(&mut self, id: NodeId) -> Option<&mut Node> {
+        self.slots
+            .get_mut(id.index as usize)
+            .filter(|slot| slot.generation == id.generation)
+            .and_then(|slot| slot.node.as_mut())
+    }
+
+    /// Add `node` as the last child of `parent`
+    ///
+    /// # Panics
+    ///
+    /// `parent` is not in the graph.
+    pub fn add_child(&mut self, parent: NodeId, mut node: Node) -> NodeId {
+        assert!(self.contains(parent), "Parent node is not in the scene graph");
+
+        node.parent = Some(parent);
+        node.children.clear();
+
+        let id = match self.free.pop() {
+            Some(index) => {
+                let slot = &mut self.slots[index as usize];
+                slot.node = Some(node);
+
+                NodeId { index, generation: slot.generation }
+            },
+            None => {
+                self.slots.push(Slot { generation: 0, node: Some(node) });
+
+                NodeId { index: (self.slots.len() - 1) as u32, generation: 0 }
+            }
+        };
+
+        self[parent].children.push(id);
+        id
+    }
+
+    /// Remove a node and every node below it
+    ///
+    /// Returns `false` if the node is not in the graph or is the root.
+    pub fn remove(&mut self, id: NodeId) -> bool {
+        if id == self.root || !self.contains(id) {
+            return false;
+        }
+
+        if let Some(parent) = self[id].parent {
+            self[parent].children.retain(|c| *c != id);
+        }
+
+        for removed in self.descendants(id) {
+            let slot = &mut self.slots[removed.index as usize];
+            slot.node = None;
+            slot.generation = slot.generation.wrapping_add(1);
+            self.free.push(removed.index);
+        }
+
+        true
+    }
+
+    /// Move a node, with its children, under a new parent
+    ///
+    /// Returns `false` if either node is not in the graph, if `id` is the
+    /// root, or if `new_parent` is `id` or one of its descendants.
+    pub fn reparent(&mut self, id: NodeId, new_parent: NodeId) -> bool {
+        if id == self.root || !self.contains(id) || !self.contains(new_parent) {
+            return false;
+        }
+
+        if self.ancestors(new_parent).any(|a| a == id) {
+            return false;
+        }
+
+        if let Some(parent) = self[id].parent {
+            self[parent].children.retain(|c| *c != id);
+        }
+
+        self[id].parent = Some(new_parent);
+        self[id].dirty = true;
+        self[new_parent].children.push(id);
+
+        true
+    }
+
+    pub fn parent(&self, id: NodeId) -> Option<NodeId> {
+        self.get(id).and_then(|node| node.parent)
+    }
+
+    pub fn children(&self, id: NodeId) -> &[NodeId] {
+        self.get(id).map(|node| node.children.as_slice()).unwrap_or(&[])
+    }
+
+    /// Iterate from `id` up to the root, starting with `id` itself
+    pub fn ancestors(&self, id: NodeId) -> impl Iterator<Item = NodeId> + '_ {
+        std::iter::successors(
+            Some(id).filter(|id| self.contains(*id)),
+            move |id| self.parent(*id)
+        )
+    }
+
+    /// Every node below `id`, depth first, starting with `id` itself
+    pub fn descendants(&self, id: NodeId) -> Vec<NodeId> {
+        let mut out = Vec::new();
+        let mut stack = vec![id];
+
+        while let Some(id) = stack.pop() {
+            if let Some(node) = self.get(id) {
+                out.push(id);
+                stack.extend(node.children.iter().rev());
+            }
+        }
+
+        out
+    }
+
+    /// Find the first node with `name`, searching depth first from the root
+    pub fn find(&self, name: &str) -> Option<NodeId> {
+        self.descendants(self.root)
+            .into_iter()
+            .find(|id| self[*id].name.as_deref() == Some(name))
+    }
+
+    /// Number of nodes in the graph, including the root
+    pub fn len(&self) -> usize {
+        self.slots.len() - self.free.len()
+    }
+
+    /// Cached world transform of a node
+    /// 
+    /// Only up to date after `update_transforms` has run.
+    pub fn world_transform(&self, id: NodeId) -> Option<Matrix4> {
+        self.get(id).map(|node| node.world)
+    }
+
+    /// Recompute the world transform of every node that moved
+    /// 
+    /// A node is recomputed if its own transform changed or if any of its
+    /// ancestors was recomputed. Returns the number of recomputed nodes.
+    pub fn update_transforms(&mut self) -> usize {
+        let mut count = 0;
+        let mut stack = vec![(self.root, Matrix4::identity(), false)];
+
+        while let Some((id, parent_world, parent_changed)) = stack.pop() {
+            let node = &mut self[id];
+            let changed = node.dirty || parent_changed;
+
+            if changed {
+                node.world = node.local_transform() * parent_world;
+                node.dirty = false;
+                count += 1;
+            }
+
+            let world = node.world;
+            stack.extend(node.children.iter().map(|child| (*child, world, changed)));
+        }
+
+        count
+    }
+
+    /// Draw every visible node using the cached world transforms
+    pub fn render(&self, backend: &dyn GraphicsBackend, camera: &Camera) {
+        self.render_node(self.root, backend, camera);
+    }
+
+    fn render_node(&self, id: NodeId, backend: &dyn GraphicsBackend, camera: &Camera) {
+        let node = &self[id];
+
+        if !node.visible {
+            return;
+        }
+
+        for child in &node.children {
+            self.render_node(*child, backend, camera);
+        }
+
+        if let Some(renderer) = node.renderer() {
+            renderer.render(backend, camera, &node.world)
+        }
+    }
+}
+
+impl Default for SceneGraph {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Index<NodeId> for SceneGraph {
+    type Output = Node;
+
+    fn index(&self, id: NodeId) -> &Self::Output {
+        self.get(id).expect("Node is not in the scene graph")
+    }
+}
+
+impl IndexMut<NodeId> for SceneGraph {
+    fn index_mut(&mut self, id: NodeId) -> &mut Self::Output {
+        self.get_mut(id).expect("Node is not in the scene graph")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_add_and_traverse() {
+        let mut graph = SceneGraph::new();
+        let root = graph.root();
+
+        let a = graph.add_child(root, Node::named("a"));
+        let b = graph.add_child(a, Node::named("b"));
+        let c = graph.add_child(root, Node::named("c"));
+
+        assert_eq!(graph.children(root), &[a, c]);
+        assert_eq!(graph.parent(b), Some(a));
+        assert_eq!(graph.descendants(root), vec![root, a, b, c]);
+        assert_eq!(graph.ancestors(b).collect::<Vec<_>>(), vec![b, a, root]);
+        assert_eq!(graph.len(), 4);
+    }
+
+    #[test]
+    fn test_remove_subtree() {
+        let mut graph = SceneGraph::new();
+        let root = graph.root();
+
+        let a = graph.add_child(root, Node::new());
+        let b = graph.add_child(a, Node::new());
+        let c = graph.add_child(root, Node::new());
+
+        assert!(graph.remove(a));
+        assert!(!graph.contains(a));
+        assert!(!graph.contains(b));
+        assert!(graph.contains(c));
+        assert_eq!(graph.children(root), &[c]);
+        assert_eq!(graph.len(), 2);
+
+        assert!(!graph.remove(a));
+        assert!(!graph.remove(root));
+    }
+
+    #[test]
+    fn test_stale_ids_after_slot_reuse() {
+        let mut graph = SceneGraph::new();
+        let root = graph.root();
+
+        let a = graph.add_child(root, Node::named("a"));
+        graph.remove(a);
+        let b = graph.add_child(root, Node::named("b"));
+
+        assert_ne!(a, b);
+        assert!(graph.get(a).is_none());
+        assert_eq!(graph[b].name.as_deref(), Some("b"));
+    }
+
+    #[test]
+    fn test_reparent() {
+        let mut graph = SceneGraph::new();
+        let root = graph.root();
+
+        let a = graph.add_child(root, Node::new());
+        let b = graph.add_child(a, Node::new());
+        let c = graph.add_child(root, Node::new());
+
+        assert!(graph.reparent(a, c));
+        assert_eq!(graph.children(root), &[c]);
+        assert_eq!(graph.parent(a), Some(c));
+        assert_eq!(graph.descendants(c), vec![c, a, b]);
+
+        // Can not create cycles
+        assert!(!graph.reparent(c, b));
+        assert!(!graph.reparent(a, a));
+        assert!(!graph.reparent(root, a));
+    }
+
+    #[test]
+    fn test_world_transforms_are_cached() {
+        use crate::math::vec3::Vector3;
+
+        let mut graph = SceneGraph::new();
+        let root = graph.root();
+
+        let a = graph.add_child(root, Node::new());
+        let b = graph.add_child(a, Node::new());
+        let c = graph.add_child(root, Node::new());
+        graph[b].set_position(Vector3::new(0.0, 1.0, 0.0));
+
+        assert_eq!(graph.update_transforms(), 4);
+        assert_eq!(graph.update_transforms(), 0);
+
+        // Moving a node recomputes its subtree only
+        graph[a].set_position(Vector3::new(2.0, 0.0, 0.0));
+        assert_eq!(graph.update_transforms(), 2);
+
+        let p = graph.world_transform(b).unwrap().transform_point(Vector3::zero());
+        assert_eq!(p.data, [2.0, 1.0, 0.0]);
+
+        // Reparenting moves the node into the space of its new parent
+        graph[c].set_position(Vector3::new(0.0, 0.0, 3.0));
+        graph.reparent(b, c);
+        assert_eq!(graph.update_transforms(), 2);
+
+        let p = graph.world_transform(b).unwrap().transform_point(Vector3::zero());
+        assert_eq!(p.data, [0.0, 1.0, 3.0]);
+    }
+
+    #[test]
+    fn test_find_by_name() {
+        let mut graph = SceneGraph::new();
+        let root = graph.root();
+
+        let globe = graph.add_child(root, Node::named("globe"));
+        let face = graph.add_child(globe, Node::named("face"));
+
+        assert_eq!(graph.find("face"), Some(face));
+        assert_eq!(graph.find("root"), Some(root));
+        assert_eq!(graph.find("missing"), None);
+
+        graph.remove(globe);
+        assert_eq!(graph.find("face"), None);
+    }
+
+    #[test]
+    fn test_render_frame_commands() {
+        use crate::graphics::backend::{graphics_backend::{ProgramId, UniformValue}, recording::RecordingBackend};
+        use crate::graphics::{mesh::Mesh, mesh_renderer::MeshRenderer, programs::unlit_3d::Unlit3D};
+        use crate::graphics::shader_manager::ShaderProgramManager;
+        use crate::math::vec3::Vector3;
+
+        let backend = RecordingBackend::new();
+        let mut programs = ShaderProgramManager::new();
+        programs.expose_program(ProgramId(0), "simple_unlit");
+
+        let marker = |position: Vector3| {
+            let mut node = Node::new();
+            node.set_position(position);
+            node.add_renderer(
+                MeshRenderer::new(&backend, Mesh::fireball(), Box::new(Unlit3D::new(&programs).unwrap())).unwrap()
+            );
+            node
+        };
+
+        let mut graph = SceneGraph::new();
+        let root = graph.root();
+        let group = graph.add_child(root, Node::new());
+        let a = graph.add_child(group, marker(Vector3::new(1.0, 0.0, 0.0)));
+        let b = graph.add_child(root, marker(Vector3::new(0.0, 2.0, 0.0)));
+        graph.update_transforms();
+        backend.take_commands();
+
+        let camera = Camera::new();
+        graph.render(&backend, &camera);
+        assert_eq!(backend.draw_count(), 2);
+
+        let transforms = backend.uniform_values("transform_matrix");
+        assert!(transforms.contains(&UniformValue::Mat4(graph[a].world.data)));
+        assert!(transforms.contains(&UniformValue::Mat4(graph[b].world.data)));
+
+        // Hidden subtrees are not drawn
+        backend.take_commands();
+        graph[group].visible = false;
+        graph.render(&backend, &camera);
+        assert_eq!(backend.draw_count(), 1);
+        assert_eq!(backend.uniform_values("transform_matrix"), vec![UniformValue::Mat4(graph[b].world.data)]);
+    }
+}
@@ -1,71 +1,144 @@
-use std::sync::Arc;
-use web_sys::WebGl2RenderingContext as GL;
-use crate::{graphics::{mesh_renderer::MeshRenderer}, math::{mat4::Matrix4, vec3::Vector3, quaternion::Quaternion}, app_state::{multiply_to_mat_stack, pop_from_mat_stack}};
+use crate::{graphics::{mesh_renderer::MeshRenderer}, math::{mat4::Matrix4, vec3::Vector3, quaternion::Quaternion}};
 
-#[derive(Default, Clone)]
+use super::scene_graph::NodeId;
+
+/// A transform in the scene with an optional renderer
+///
+/// Nodes are owned by a `SceneGraph`, which also keeps track of their
+/// parent and children. The world transform of the node is cached by the
+/// graph and only recomputed after the node or one of its ancestors moves.
 pub struct Node {
-    renderer: Option<Arc<MeshRenderer>>,
-    parent: Option<Arc<Node>>,
-    pub children: Vec<Arc<Node>>,
-    pub position: Vector3,
-    pub rotation: Quaternion,
-    pub scale: Vector3
+    renderer: Option<MeshRenderer>,
+    pub(super) parent: Option<NodeId>,
+    pub(super) children: Vec<NodeId>,
+    pub name: Option<String>,
+    position: Vector3,
+    rotation: Quaternion,
+    scale: Vector3,
+    pub(super) world: Matrix4,
+    pub(super) dirty: bool,
+    /// Hidden nodes skip rendering for themselves and their children
+    pub visible: bool
 }
 
+#[allow(dead_code)]
 impl Node {
     pub fn new() -> Self {
         Self {
             renderer: None,
             parent: None,
             children: Vec::new(),
+            name: None,
             position: Vector3::zero(),
             rotation: Quaternion::euler(0.0, 0.0, 0.0),
-            scale: Vector3::zero()
+            scale: Vector3::one(),
+            world: Matrix4::identity(),
+            dirty: true,
+            visible: true
+        }
+    }
+
+    /// Create a new node with a name that can be found with `SceneGraph::find`
+    pub fn named(name: &str) -> Self {
+        Self {
+            name: Some(String::from(name)),
+            ..Self::new()
         }
     }
 
     pub fn add_renderer(&mut self, mesh: MeshRenderer) {
-        self.renderer = Some(Arc::new(mesh))
+        self.renderer = Some(mesh)
     }
 
-    fn _add_parent(&mut self, parent: Node) {
-        self.parent = Some(Arc::new(parent))
+    pub fn renderer(&self) -> Option<&MeshRenderer> {
+        self.renderer.as_ref()
     }
 
-    pub fn add_child(&mut self, mut child: Node) {
-        
-        // This is probably going to create some issues down the line
-        // Consider comsuming self and chaining?
-        child._add_parent(self.to_owned());
+    pub fn renderer_mut(&mut self) -> Option<&mut MeshRenderer> {
+        self.renderer.as_mut()
+    }
 
-        self.children.push(
-            Arc::new(child)
-        )
-        
-        // TODO: Fix
+    pub fn position(&self) -> Vector3 {
+        self.position
     }
 
-    pub fn render(&self, gl: &GL) {
-        // Multiply my matrix onto the mat stack
-        let transform = 
-            Matrix4::scale_uniform(1.0) * 
-            Matrix4::rotate(self.rotation) *
-            Matrix4::translate(self.position[0], self.position[1], self.position[2]);
+    pub fn rotation(&self) -> Quaternion {
+        self.rotation
+    }
 
-        multiply_to_mat_stack(transform);
+    pub fn scale(&self) -> Vector3 {
+        self.scale
+    }
 
-        
+    pub fn set_position(&mut self, position: Vector3) {
+        self.position = position;
+        self.dirty = true;
+    }
 
-        for child in self.to_owned().children {
-            child.render(&gl);
-        }
+    pub fn set_rotation(&mut self, rotation: Quaternion) {
+        self.rotation = rotation;
+        self.dirty = true;
+    }
 
-        if self.renderer.is_some() {
-            // Very good not at all stupid or bad
-            self.renderer.as_ref().unwrap().render(&gl)
-        }
+    pub fn set_scale(&mut self, scale: Vector3) {
+        self.scale = scale;
+        self.dirty = true;
+    }
+
+    /// Cached transform of the node relative to the root of the scene
+    /// 
+    /// Only up to date after `SceneGraph::update_transforms` has run.
+    pub fn world_transform(&self) -> Matrix4 {
+        self.world
+    }
 
-        // Pop my matrix from the mat stack
-        pop_from_mat_stack();
+    /// Transform of the node relative to its parent
+    ///
+    /// Scales first, then rotates and finally translates the node. Scale
+    /// does not have to be uniform.
+    pub fn local_transform(&self) -> Matrix4 {
+        Matrix4::scale(self.scale[0], self.scale[1], self.scale[2]) *
+        Matrix4::rotate(self.rotation) *
+        Matrix4::translate(self.position[0], self.position[1], self.position[2])
     }
-}
\ No newline at end of file
+}
+
+impl Default for Node {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_scale_is_one() {
+        let node = Node::new();
+        let p = node.local_transform().transform_point(Vector3::new(1.0, 2.0, 3.0));
+
+        assert_eq!(p.data, [1.0, 2.0, 3.0]);
+    }
+
+    #[test]
+    fn test_local_transform_non_uniform_scale() {
+        let mut node = Node::new();
+        node.set_position(Vector3::new(1.0, 0.0, 0.0));
+        node.set_scale(Vector3::new(2.0, 3.0, 4.0));
+
+        let p = node.local_transform().transform_point(Vector3::new(1.0, 1.0, 1.0));
+        assert_eq!(p.data, [3.0, 3.0, 4.0]);
+    }
+
+    #[test]
+    fn test_local_transform_scales_before_rotating() {
+        let mut node = Node::new();
+        node.set_scale(Vector3::new(2.0, 1.0, 1.0));
+        node.set_rotation(Quaternion::euler(0.0, std::f32::consts::FRAC_PI_2, 0.0));
+
+        let p = node.local_transform().transform_point(Vector3::right());
+        assert!((p.magnitude() - 2.0).abs() < 1e-5);
+        assert!(p.data[0].abs() < 1e-5);
+    }
+}
@@ -24,4 +24,217 @@ mod tests {
     fn test_vector3_subassign() {
 
     }
-}
\ No newline at end of file
+}
+
+/// Setting up the renderer reports what failed instead of panicking
+#[cfg(test)]
+mod setup {
+    use crate::error::RendererError;
+    use crate::graphics::backend::recording::RecordingBackend;
+    use crate::graphics::programs::unlit_3d::Unlit3D;
+    use crate::graphics::shader_manager::{ShaderManager, ShaderProgramManager};
+    use crate::link_programs;
+
+    #[test]
+    fn test_missing_shader_and_program() {
+        let backend = RecordingBackend::new();
+
+        let error = link_programs(&backend, &ShaderManager::new()).err();
+        assert_eq!(error, Some(RendererError::UnknownShader(String::from("vert_3d"))));
+
+        let error = Unlit3D::new(&ShaderProgramManager::new()).err();
+        assert_eq!(error, Some(RendererError::UnknownProgram(String::from("simple_unlit"))));
+    }
+}
+
+/// Frames rendered with the software backend, compared to the reference
+/// images in `golden/`
+///
+/// Run with `UPDATE_GOLDEN=1` to accept changes to the rendering.
+#[cfg(test)]
+mod golden {
+    use crate::data::cneos::FireballRecord;
+    use crate::graphics::backend::graphics_backend::GraphicsBackend;
+    use crate::graphics::backend::software::{assert_golden, SoftwareBackend};
+    use crate::graphics::camera::{Camera, OrbitCamera};
+    use crate::graphics::shader_manager::ShaderProgramManager;
+    use crate::graphics::texture_manager::TextureManager;
+    use crate::math::geo::EarthModel;
+    use crate::scene::fireball::Fireball;
+    use crate::scene::scene_graph::{NodeId, SceneGraph};
+    use crate::{build_globe, fireball_marker, link_programs, precompile_shaders, set_globe_texture};
+
+    const WIDTH: u32 = 160;
+    const HEIGHT: u32 = 120;
+
+    /// Globe with a checkered texture in a different color on every face
+    fn globe_scene() -> (SoftwareBackend, ShaderProgramManager, TextureManager, SceneGraph, NodeId) {
+        let backend = SoftwareBackend::new(WIDTH, HEIGHT);
+        let colors = [[220, 60, 60], [60, 200, 60], [60, 90, 230], [230, 200, 40], [200, 60, 220], [40, 210, 210]];
+
+        for (i, color) in colors.iter().enumerate() {
+            let pixels = (0..64).flat_map(|p| {
+                let shade = if (p % 8 < 4) == (p / 8 < 4) { 1.0 } else { 0.5 };
+                [color[0] as f32 * shade, color[1] as f32 * shade, color[2] as f32 * shade, 255.0].map(|c| c as u8)
+            }).collect();
+
+            backend.add_image(&format!("/res/world_cube_net_strip{}.png", i + 1), 8, 8, pixels);
+        }
+
+        let shader_manager = precompile_shaders(&backend).unwrap();
+        let program_manager = link_programs(&backend, &shader_manager).unwrap();
+
+        let mut textures = TextureManager::new();
+        let mut scene = SceneGraph::new();
+        let globe = build_globe(&backend, &program_manager, &mut textures, &mut scene).unwrap();
+
+        (backend, program_manager, textures, scene, globe)
+    }
+
+    fn render(backend: &SoftwareBackend, scene: &mut SceneGraph, orbit: &OrbitCamera) {
+        let mut camera = Camera::new();
+        camera.update_projection_matrix(WIDTH as f32 / HEIGHT as f32);
+        orbit.apply(&mut camera);
+
+        backend.set_cull_back_faces(true);
+        backend.set_depth_test(true);
+        backend.set_clear_color([0.0, 0.0, 0.0, 1.0]);
+        backend.viewport(0, 0, WIDTH as i32, HEIGHT as i32);
+        backend.clear();
+
+        scene.update_transforms();
+        scene.render(backend, &camera);
+    }
+
+    #[test]
+    fn test_golden_globe_faces() {
+        let (backend, _, _, mut scene, _) = globe_scene();
+
+        // Looking down on a corner of the cube, showing three faces
+        render(&backend, &mut scene, &OrbitCamera::new(2.4, 0.6, 3.0));
+        assert_golden(&backend, "globe_faces");
+
+        render(&backend, &mut scene, &OrbitCamera::new(-0.8, -0.6, 3.0));
+        assert_golden(&backend, "globe_faces_opposite");
+    }
+
+    #[test]
+    fn test_golden_fireball_placement() {
+        let (backend, program_manager, _, mut scene, globe) = globe_scene();
+
+        for (lat, lon, alt) in [(20.0, 170.0, None), (28.0, 160.0, Some(600.0)), (10.0, -178.0, None), (32.0, -170.0, Some(300.0))] {
+            let marker = fireball_marker(&backend, &program_manager).unwrap();
+            Fireball::spawn(&mut scene, globe, FireballRecord::new(lat, lon, alt), marker, &EarthModel::default());
+        }
+
+        render(&backend, &mut scene, &OrbitCamera::new(175.0_f32.to_radians(), 20.0_f32.to_radians(), 1.6));
+        assert_golden(&backend, "fireball_placement");
+    }
+
+    #[test]
+    fn test_golden_equirectangular() {
+        let (backend, program_manager, mut textures, mut scene, globe) = globe_scene();
+
+        // A quarter of the longitudes in each color, with darker bands of
+        // latitude and white and black rows at the poles
+        let (width, height) = (32, 16);
+        let quarters = [[220, 60, 60], [60, 200, 60], [60, 90, 230], [230, 200, 40]];
+        let pixels = (0..width * height).flat_map(|p| {
+            let (x, y) = (p % width, p / width);
+            let color = match y {
+                0 => [255, 255, 255],
+                y if y == height - 1 => [0, 0, 0],
+                y => quarters[x * 4 / width].map(|c| if y % 4 < 2 { c } else { c / 2 })
+            };
+            [color[0], color[1], color[2], 255].map(|c| c as u8)
+        }).collect();
+
+        backend.add_image("/res/blue_marble.png", width as u32, height as u32, pixels);
+        set_globe_texture(&backend, &program_manager, &mut textures, &mut scene, globe, "/res/blue_marble.png").unwrap();
+
+        // The cube map is no longer used and deleted, the new texture loaded
+        assert!(textures.update(&backend));
+        assert_eq!(textures.state("/res/world_cube_net_strip1.png"), None);
+        assert!(textures.failed().is_empty());
+
+        // Longitude 45 east, in the third quarter
+        render(&backend, &mut scene, &OrbitCamera::new(45.0_f32.to_radians(), 0.0, 3.0));
+        let [r, g, b, _] = backend.pixel(WIDTH / 2, HEIGHT / 2);
+        assert!(b > r && b > g);
+
+        // The antimeridian, between the first and last quarter
+        render(&backend, &mut scene, &OrbitCamera::new(std::f32::consts::PI, 0.3, 3.0));
+        assert_golden(&backend, "equirectangular_antimeridian");
+
+        render(&backend, &mut scene, &OrbitCamera::new(0.5, 1.4, 3.0));
+        assert_golden(&backend, "equirectangular_north_pole");
+    }
+}
+
+/// What the app sends to the GPU when changing its resources
+#[cfg(test)]
+mod resources {
+    use crate::data::cneos::FireballRecord;
+    use crate::graphics::backend::recording::{Command, RecordingBackend};
+    use crate::graphics::texture_manager::TextureManager;
+    use crate::math::geo::EarthModel;
+    use crate::scene::fireball::{Fireball, FireballRegistry};
+    use crate::scene::scene_graph::SceneGraph;
+    use crate::scene::scene_node::Node;
+    use crate::{build_globe, clear_fireballs, fireball_marker, link_programs, precompile_shaders, set_globe_mipmaps};
+
+    #[test]
+    fn test_globe_mipmaps_are_generated() {
+        let backend = RecordingBackend::new();
+        let program_manager = link_programs(&backend, &precompile_shaders(&backend).unwrap()).unwrap();
+        let mut textures = TextureManager::new();
+        let mut scene = SceneGraph::new();
+        let globe = build_globe(&backend, &program_manager, &mut textures, &mut scene).unwrap();
+        textures.update(&backend);
+
+        let cube = scene[globe].renderer().unwrap().material().texture().unwrap().id();
+        backend.take_commands();
+
+        set_globe_mipmaps(&backend, &mut textures, &scene, globe, true).unwrap();
+        let commands = backend.take_commands();
+
+        assert!(matches!(commands[0], Command::SetSampler { texture, sampler } if texture == cube && sampler.mipmaps));
+        assert_eq!(commands[1], Command::GenerateMipmaps(cube));
+    }
+
+    #[test]
+    fn test_clear_fireballs_deletes_markers() {
+        let backend = RecordingBackend::new();
+        let program_manager = link_programs(&backend, &precompile_shaders(&backend).unwrap()).unwrap();
+        let mut scene = SceneGraph::new();
+        let globe = scene.add_child(scene.root(), Node::named("globe"));
+        let mut fireballs = FireballRegistry::new();
+        backend.take_commands();
+
+        // Like `App::add_fireball`
+        for lat in [0.0, 10.0, 20.0] {
+            let marker = fireball_marker(&backend, &program_manager).unwrap();
+            fireballs.insert(Fireball::spawn(&mut scene, globe, FireballRecord::new(lat, 0.0, None), marker, &EarthModel::default()));
+        }
+
+        let mut created: Vec<Command> = backend.take_commands().into_iter()
+            .filter_map(|command| match command {
+                Command::CreateVertexBuffer { buffer, .. } | Command::CreateIndexBuffer { buffer, .. } => {
+                    Some(Command::DeleteBuffer(buffer))
+                },
+                Command::CreateVertexArray { vertex_array, .. } => Some(Command::DeleteVertexArray(vertex_array)),
+                _ => None
+            })
+            .collect();
+
+        clear_fireballs(&backend, &mut scene, &mut fireballs);
+        let mut deleted = backend.take_commands();
+
+        created.sort_by_key(|command| format!("{:?}", command));
+        deleted.sort_by_key(|command| format!("{:?}", command));
+        assert_eq!(created.len(), 9);
+        assert_eq!(deleted, created);
+        assert!(fireballs.is_empty());
+        assert_eq!(scene.children(globe).len(), 0);
+    }
+}